@@ -0,0 +1,2 @@
+drop index conversations_user_id_idx;
+drop table conversations;
\ No newline at end of file
@@ -0,0 +1,10 @@
+create table conversations(
+    id integer primary key autoincrement not null,
+    user_id integer not null references users(id),
+    opened_at bigint not null,
+    first_response_at bigint,
+    unanswered_since bigint,
+    closed_at bigint
+);
+
+create index conversations_user_id_idx on conversations(user_id);
\ No newline at end of file
@@ -0,0 +1 @@
+alter table conversations drop column breached_at;
\ No newline at end of file
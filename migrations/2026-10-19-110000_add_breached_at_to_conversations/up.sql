@@ -0,0 +1 @@
+alter table conversations add column breached_at bigint;
\ No newline at end of file
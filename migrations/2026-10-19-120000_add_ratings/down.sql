@@ -0,0 +1,4 @@
+drop index ratings_user_id_idx;
+drop index ratings_conversation_id_idx;
+drop table ratings;
+alter table conversations drop column staff_id;
\ No newline at end of file
@@ -0,0 +1,15 @@
+alter table conversations add column staff_id bigint;
+
+create table ratings(
+    id integer primary key autoincrement not null,
+    conversation_id integer not null references conversations(id),
+    user_id integer not null references users(id),
+    staff_id bigint,
+    prompt_message bigint not null,
+    rating smallint,
+    comment text,
+    awaiting_comment boolean not null default false
+);
+
+create index ratings_conversation_id_idx on ratings(conversation_id);
+create index ratings_user_id_idx on ratings(user_id);
\ No newline at end of file
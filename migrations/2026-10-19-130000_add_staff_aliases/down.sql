@@ -0,0 +1 @@
+drop table staff_aliases;
\ No newline at end of file
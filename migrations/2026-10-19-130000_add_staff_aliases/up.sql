@@ -0,0 +1,5 @@
+create table staff_aliases(
+    id integer primary key autoincrement not null,
+    telegram_id bigint not null unique,
+    alias text not null
+);
\ No newline at end of file
@@ -0,0 +1,4 @@
+alter table users drop column username;
+alter table users drop column is_premium;
+alter table users drop column first_contact;
+alter table users drop column banned;
\ No newline at end of file
@@ -0,0 +1,4 @@
+alter table users add column username text;
+alter table users add column is_premium boolean not null default false;
+alter table users add column first_contact bigint;
+alter table users add column banned boolean not null default false;
\ No newline at end of file
@@ -0,0 +1,3 @@
+drop index note_history_user_id_key_idx;
+drop table note_history;
+alter table notes drop column type_;
\ No newline at end of file
@@ -0,0 +1,13 @@
+alter table notes add column type_ smallint not null default 0;
+
+create table note_history(
+    id integer primary key autoincrement not null,
+    user_id integer not null references users(id),
+    key text not null,
+    value text,
+    type_ smallint not null default 0,
+    staff_id bigint,
+    created_at bigint not null
+);
+
+create index note_history_user_id_key_idx on note_history(user_id, key);
\ No newline at end of file
@@ -0,0 +1,2 @@
+drop index user_tags_tag_idx;
+drop table user_tags;
\ No newline at end of file
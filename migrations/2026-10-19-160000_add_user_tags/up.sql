@@ -0,0 +1,8 @@
+create table user_tags(
+    id integer primary key autoincrement not null,
+    user_id integer not null references users(id),
+    tag text not null,
+    unique(user_id, tag)
+);
+
+create index user_tags_tag_idx on user_tags(tag);
\ No newline at end of file
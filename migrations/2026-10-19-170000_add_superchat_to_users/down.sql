@@ -0,0 +1,3 @@
+alter table users drop column superchat;
+alter table users drop column start_payload;
+alter table messages drop column tx_chat_id;
\ No newline at end of file
@@ -0,0 +1,3 @@
+alter table users add column superchat bigint;
+alter table users add column start_payload text;
+alter table messages add column tx_chat_id bigint;
\ No newline at end of file
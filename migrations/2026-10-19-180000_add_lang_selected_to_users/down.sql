@@ -0,0 +1 @@
+alter table users drop column lang_selected;
\ No newline at end of file
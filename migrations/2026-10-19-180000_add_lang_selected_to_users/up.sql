@@ -0,0 +1 @@
+alter table users add column lang_selected boolean not null default false;
\ No newline at end of file
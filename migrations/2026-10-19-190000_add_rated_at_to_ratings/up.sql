@@ -0,0 +1 @@
+alter table ratings add column rated_at bigint;
\ No newline at end of file
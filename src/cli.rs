@@ -0,0 +1,56 @@
+use std::path::PathBuf;
+
+pub const USAGE: &str = "Usage: telegram-support-bot [--config <file>] [command]
+
+Commands:
+  run                          start the bot, default
+  check-config                 validate configuration and exit
+  migrate                      apply pending database migrations
+  print-default-config         print config with required keys
+  export-localization <file>   write template of all localization keys, format is picked by extension";
+
+pub enum Command {
+    Run,
+    CheckConfig,
+    Migrate,
+    PrintDefaultConfig,
+    /// Template of all localization keys for translators
+    ExportLocalization(PathBuf),
+    Help,
+}
+
+pub struct Args {
+    /// Config file to read instead of `config.toml` and `/config/config.toml`
+    pub config: Option<PathBuf>,
+    pub command: Command,
+}
+
+impl Args {
+    /// Parses arguments without program name
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Args, String> {
+        let mut config = None;
+        let mut words = vec![];
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--config" | "-c" => config = Some(PathBuf::from(args.next().ok_or("--config needs a file path")?)),
+                "--help" | "-h" => words = vec!["help".to_string()],
+                _ if arg.starts_with("--config=") => config = Some(PathBuf::from(&arg["--config=".len()..])),
+                _ if arg.starts_with('-') => return Err(format!("Unknown option {}", arg)),
+                _ => words.push(arg),
+            }
+        }
+        let command = match words.iter().map(|w| w.as_str()).collect::<Vec<_>>().as_slice() {
+            [] | ["run"] => Command::Run,
+            ["check-config"] => Command::CheckConfig,
+            ["migrate"] => Command::Migrate,
+            ["print-default-config"] => Command::PrintDefaultConfig,
+            ["export-localization", path] => Command::ExportLocalization(PathBuf::from(path)),
+            ["export-localization"] => return Err("export-localization needs a file path, like en.yaml".to_string()),
+            ["help", ..] => Command::Help,
+            [command] => return Err(format!("Unknown command {}", command)),
+            _ => return Err(format!("Unexpected arguments: {}", words.join(" "))),
+        };
+        Ok(Args { config, command })
+    }
+}
\ No newline at end of file
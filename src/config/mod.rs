@@ -0,0 +1,101 @@
+use std::fmt::{Display, Formatter};
+use std::path::Path;
+use config::{Config, ConfigError, Environment, File};
+use serde::Deserialize;
+use tracing::warn;
+use crate::database::DatabaseConfig;
+use crate::localization::LocalizationConfig;
+use crate::metrics::MetricsConfig;
+use crate::telegram::TelegramConfig;
+use crate::translation::TranslationConfig;
+
+#[cfg(test)]
+mod tests;
+
+/// Commented config with required keys, printed by `print-default-config`
+pub const DEFAULT_CONFIG: &str = include_str!("default.toml");
+
+#[derive(Deserialize, Debug)]
+pub struct Configuration {
+    pub metrics: Option<MetricsConfig>,
+    pub telegram: TelegramConfig,
+    pub localization: Option<LocalizationConfig>,
+    pub database: DatabaseConfig,
+    pub translation: Option<TranslationConfig>,
+}
+
+impl Configuration {
+    /// Reads config from `path`, or from `config.toml` and `/config/config.toml` if not set.
+    /// [environment] variables override file values
+    pub fn load(path: Option<&Path>) -> Result<Self, ConfigError> {
+        let builder = match path {
+            Some(path) => Config::builder().add_source(File::from(path)),
+            None => Config::builder()
+                .add_source(File::with_name("config.toml").required(false))
+                .add_source(File::with_name("/config/config.toml").required(false)),
+        };
+        builder.add_source(legacy_environment(std::env::vars()))
+            .add_source(environment())
+            .build()?
+            .try_deserialize()
+    }
+
+    /// Checks values that deserialize fine but can't work, like missing directories or chat ids that aren't superchats
+    pub fn validate(&self) -> ConfigErrors {
+        let mut errors = ConfigErrors::default();
+        if let Some(ref metrics) = self.metrics {
+            metrics.validate(&mut errors);
+        }
+        self.telegram.validate(&mut errors);
+        if let Some(ref localization) = self.localization {
+            localization.validate(&mut errors);
+        }
+        self.database.validate(&mut errors);
+        if let Some(ref translation) = self.translation {
+            translation.validate(&mut errors);
+        }
+        errors
+    }
+}
+
+/// `APP_` variables with `__` between key parts, so keys can contain `_`: `APP_TELEGRAM__STAFF_LANGUAGE`
+fn environment() -> Environment {
+    Environment::with_prefix("app").prefix_separator("_").separator("__")
+}
+
+/// Variables of the old format with single `_` separator, like `APP_TELEGRAM_TOKEN`. Only keys without `_`
+/// could be set this way, so just `APP_SECTION_KEY` ones are read. [environment] variables take precedence
+fn legacy_environment(vars: impl Iterator<Item = (String, String)>) -> Environment {
+    let vars = vars
+        .filter(|(k, _)| k.to_lowercase().starts_with("app_") && !k.contains("__") && k.matches('_').count() == 2)
+        .inspect(|(k, _)| warn!("{} uses deprecated format, rename it to {}{}", k, &k[..4], k[4..].replacen('_', "__", 1)))
+        .collect();
+    Environment::with_prefix("app").separator("_").source(Some(vars))
+}
+
+/// Problems found by [Configuration::validate], each with the key it's about
+#[derive(Debug, Default)]
+pub struct ConfigErrors(Vec<(String, String)>);
+
+impl ConfigErrors {
+    pub fn add(&mut self, key: impl Into<String>, message: impl Display) {
+        self.0.push((key.into(), message.to_string()));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+}
+
+impl Display for ConfigErrors {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for (key, message) in self.0.iter() {
+            writeln!(f, "{}: {}", key, message)?;
+        }
+        Ok(())
+    }
+}
\ No newline at end of file
@@ -0,0 +1,112 @@
+use config::{Config, File, FileFormat};
+use super::{environment, legacy_environment, Configuration, DEFAULT_CONFIG};
+use crate::cli::{Args, Command};
+
+fn parse(toml: &str, overrides: &[(&str, &str)]) -> Configuration {
+    let mut builder = Config::builder().add_source(File::from_str(toml, FileFormat::Toml));
+    for (key, value) in overrides {
+        builder = builder.set_override(*key, *value).unwrap();
+    }
+    builder.build().unwrap().try_deserialize().unwrap()
+}
+
+#[test]
+fn default_config_is_valid() {
+    let db = std::env::temp_dir().join(format!("config-default-{}.sqlite", std::process::id()));
+    let config = parse(DEFAULT_CONFIG, &[("database.path", db.to_str().unwrap())]);
+    let errors = config.validate();
+    assert!(errors.is_empty(), "{errors}");
+    assert!(!db.exists(), "writability probe should not leave database file");
+}
+
+#[test]
+fn validation_reports_every_problem() {
+    let config = parse(r#"
+        [metrics]
+        address = "localhost"
+
+        [database]
+        type = "Sqlite"
+        path = "/nonexistent/dir/db.sqlite"
+
+        [telegram]
+        token = "token"
+        superchat = 12345
+
+        [[telegram.routes]]
+        superchat = -1001234567890
+
+        [[telegram.routes]]
+        superchat = -42
+
+        [telegram.internal]
+        prefixes = ["//", " "]
+
+        [telegram.sla]
+        response = 0
+
+        [localization]
+        paths = ["/nonexistent/localization"]
+
+        [translation]
+        type = "LibreTranslate"
+        url = "localhost:5000"
+    "#, &[]);
+    let errors = config.validate().to_string();
+    for key in [
+        "metrics.address", "database.path", "telegram.token", "telegram.superchat", "telegram.routes[1].superchat",
+        "telegram.internal.prefixes", "telegram.sla.response", "localization.paths", "translation.url",
+    ] {
+        assert!(errors.contains(&format!("{key}: ")), "{key} missing in:\n{errors}");
+    }
+    assert!(!errors.contains("routes[0]"), "{errors}");
+    assert_eq!(config.validate().len(), 9);
+}
+
+#[test]
+fn environment_overrides_nested_keys_with_underscores() {
+    let vars = [
+        ("APP_TELEGRAM__TOKEN", "env-token"),
+        ("APP_TELEGRAM__STAFF_LANGUAGE", "ru"),
+        ("APP_DATABASE__PATH", "/data/db.sqlite"),
+    ];
+    let vars = vars.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+    let config: Configuration = Config::builder()
+        .add_source(File::from_str(DEFAULT_CONFIG, FileFormat::Toml))
+        .add_source(environment().source(Some(vars)))
+        .build().unwrap()
+        .try_deserialize().unwrap();
+    assert_eq!(config.telegram.token, "env-token");
+    assert_eq!(config.telegram.staff_language.as_deref(), Some("ru"));
+    assert!(matches!(config.database, crate::database::DatabaseConfig::Sqlite { ref path } if path == "/data/db.sqlite"));
+}
+
+#[test]
+fn old_environment_format_keeps_working_for_flat_keys() {
+    let vars = |vars: &[(&str, &str)]| vars.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect::<Vec<_>>();
+    let old = vars(&[("APP_TELEGRAM_TOKEN", "old-token"), ("APP_DATABASE_PATH", "/old.sqlite"), ("APP_TELEGRAM_STAFF_LANGUAGE", "ru")]);
+    let new = vars(&[("APP_DATABASE__PATH", "/new.sqlite")]);
+    let config: Configuration = Config::builder()
+        .add_source(File::from_str(DEFAULT_CONFIG, FileFormat::Toml))
+        .add_source(legacy_environment(old.iter().cloned().chain(new.iter().cloned())))
+        .add_source(environment().source(Some(new.into_iter().collect())))
+        .build().unwrap()
+        .try_deserialize().unwrap();
+    assert_eq!(config.telegram.token, "old-token");
+    assert!(config.telegram.staff_language.is_none(), "keys with underscores never worked in old format");
+    assert!(matches!(config.database, crate::database::DatabaseConfig::Sqlite { ref path } if path == "/new.sqlite"));
+}
+
+#[test]
+fn arguments_pick_command_and_config() {
+    let args = |s: &str| Args::parse(s.split_whitespace().map(|a| a.to_string()));
+    let parsed = args("--config /etc/bot.toml check-config").unwrap();
+    assert!(matches!(parsed.command, Command::CheckConfig));
+    assert_eq!(parsed.config.unwrap().to_str(), Some("/etc/bot.toml"));
+    assert!(matches!(args("").unwrap().command, Command::Run));
+    assert!(matches!(args("migrate --config=bot.toml").unwrap().command, Command::Migrate));
+    assert!(matches!(args("export-localization en.po").unwrap().command, Command::ExportLocalization(p) if p.to_str() == Some("en.po")));
+    assert!(args("export-localization").is_err());
+    assert!(args("deploy").is_err());
+    assert!(args("--config").is_err());
+}
\ No newline at end of file
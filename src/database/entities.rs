@@ -1,7 +1,7 @@
 use diesel::{AsChangeset, Identifiable, Insertable, Queryable, Selectable};
 use teloxide::prelude::Message;
 use teloxide::types::MessageId;
-use crate::schema::{users, messages, notes};
+use crate::schema::{users, messages, notes, note_history, conversations, ratings, staff_aliases, user_tags};
 
 #[derive(Queryable, Selectable, AsChangeset, Identifiable, Clone)]
 #[diesel(table_name = users)]
@@ -13,6 +13,16 @@ pub struct UserEntity {
     pub first_name: Option<String>,
     pub last_name: Option<String>,
     pub lang_code: Option<String>,
+    pub username: Option<String>,
+    pub is_premium: bool,
+    pub first_contact: Option<i64>,
+    pub banned: bool,
+    /// Superchat where user topic lives
+    pub superchat: Option<i64>,
+    /// Parameter of `/start` deep link user came with
+    pub start_payload: Option<String>,
+    /// Language was chosen with `/language` and is kept when client language changes
+    pub lang_selected: bool,
 }
 
 #[derive(Insertable)]
@@ -24,6 +34,20 @@ pub struct InsertUserEntity {
     pub first_name: Option<String>,
     pub last_name: Option<String>,
     pub lang_code: Option<String>,
+    pub username: Option<String>,
+    pub is_premium: bool,
+    pub first_contact: Option<i64>,
+    pub superchat: Option<i64>,
+    pub start_payload: Option<String>,
+}
+
+/// Message and conversation counters shown in user info message
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct UserStats {
+    pub incoming: i64,
+    pub outgoing: i64,
+    /// Closed conversations
+    pub conversations: i64,
 }
 
 #[repr(i16)]
@@ -40,6 +64,7 @@ pub struct InsertMessageEntity {
     pub rx_msg_id: i64,
     pub rx_msg: String,
     pub tx_msg_id: i64,
+    pub tx_chat_id: Option<i64>,
 }
 
 impl InsertMessageEntity {
@@ -50,6 +75,7 @@ impl InsertMessageEntity {
             rx_msg_id: rx.id.0 as i64,
             rx_msg: serde_json::to_string(rx).unwrap(),
             tx_msg_id: tx_id.0 as i64,
+            tx_chat_id: user.superchat,
         }
     }
 
@@ -60,6 +86,7 @@ impl InsertMessageEntity {
             rx_msg_id: rx.id.0 as i64,
             rx_msg: serde_json::to_string(rx).unwrap(),
             tx_msg_id: tx_id.0 as i64,
+            tx_chat_id: Some(user.telegram_id),
         }
     }
 }
@@ -73,6 +100,8 @@ pub struct MessageEntity {
     pub rx_msg_id: i64,
     pub rx_msg: String,
     pub tx_msg_id: i64,
+    /// Chat relayed copy was sent to, user topic can move to another superchat later
+    pub tx_chat_id: Option<i64>,
 }
 
 impl MessageEntity {
@@ -87,6 +116,7 @@ pub struct InsertNoteEntity {
     pub user_id: i32,
     pub key: String,
     pub value: String,
+    pub type_: i16,
 }
 
 #[derive(Queryable, Selectable, AsChangeset, Identifiable, Clone)]
@@ -96,4 +126,133 @@ pub struct NoteEntity {
     pub user_id: i32,
     pub key: String,
     pub value: String,
+    pub type_: i16,
+}
+
+#[repr(i16)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NoteType {
+    String,
+    Number,
+    Date,
+    Url,
+    Tag,
+}
+
+impl NoteType {
+    pub const ALL: [NoteType; 5] = [NoteType::String, NoteType::Number, NoteType::Date, NoteType::Url, NoteType::Tag];
+
+    /// Unknown values are read as strings
+    pub fn from_i16(value: i16) -> NoteType {
+        NoteType::ALL.into_iter().find(|t| *t as i16 == value).unwrap_or(NoteType::String)
+    }
+}
+
+/// Note change. Deletions have no value
+#[derive(Insertable)]
+#[diesel(table_name = note_history)]
+pub struct InsertNoteHistoryEntity {
+    pub user_id: i32,
+    pub key: String,
+    pub value: Option<String>,
+    pub type_: i16,
+    pub staff_id: Option<i64>,
+    pub created_at: i64,
+}
+
+#[derive(Queryable, Selectable, Identifiable, Clone)]
+#[diesel(table_name = note_history)]
+pub struct NoteHistoryEntity {
+    pub id: i32,
+    pub user_id: i32,
+    pub key: String,
+    pub value: Option<String>,
+    pub type_: i16,
+    pub staff_id: Option<i64>,
+    pub created_at: i64,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = conversations)]
+pub struct InsertConversationEntity {
+    pub user_id: i32,
+    pub opened_at: i64,
+    pub first_response_at: Option<i64>,
+    pub unanswered_since: Option<i64>,
+    pub closed_at: Option<i64>,
+    pub breached_at: Option<i64>,
+    pub staff_id: Option<i64>,
+}
+
+#[derive(Queryable, Selectable, AsChangeset, Identifiable, Clone)]
+#[diesel(table_name = conversations)]
+#[diesel(treat_none_as_null = true)]
+pub struct ConversationEntity {
+    pub id: i32,
+    pub user_id: i32,
+    pub opened_at: i64,
+    pub first_response_at: Option<i64>,
+    pub unanswered_since: Option<i64>,
+    pub closed_at: Option<i64>,
+    pub breached_at: Option<i64>,
+    pub staff_id: Option<i64>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = ratings)]
+pub struct InsertRatingEntity {
+    pub conversation_id: i32,
+    pub user_id: i32,
+    pub staff_id: Option<i64>,
+    pub prompt_message: i64,
+    pub rating: Option<i16>,
+    pub comment: Option<String>,
+    pub awaiting_comment: bool,
+    /// When user last picked stars, comment is expected shortly after
+    pub rated_at: Option<i64>,
+}
+
+#[derive(Queryable, Selectable, AsChangeset, Identifiable, Clone)]
+#[diesel(table_name = ratings)]
+pub struct RatingEntity {
+    pub id: i32,
+    pub conversation_id: i32,
+    pub user_id: i32,
+    pub staff_id: Option<i64>,
+    pub prompt_message: i64,
+    pub rating: Option<i16>,
+    pub comment: Option<String>,
+    pub awaiting_comment: bool,
+    /// When user last picked stars, comment is expected shortly after
+    pub rated_at: Option<i64>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = staff_aliases)]
+pub struct InsertStaffAliasEntity {
+    pub telegram_id: i64,
+    pub alias: String,
+}
+
+#[derive(Queryable, Selectable, AsChangeset, Identifiable, Clone)]
+#[diesel(table_name = staff_aliases)]
+pub struct StaffAliasEntity {
+    pub id: i32,
+    pub telegram_id: i64,
+    pub alias: String,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = user_tags)]
+pub struct InsertTagEntity {
+    pub user_id: i32,
+    pub tag: String,
+}
+
+#[derive(Queryable, Selectable, Identifiable, Clone)]
+#[diesel(table_name = user_tags)]
+pub struct TagEntity {
+    pub id: i32,
+    pub user_id: i32,
+    pub tag: String,
 }
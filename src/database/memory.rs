@@ -0,0 +1,378 @@
+use async_trait::async_trait;
+use teloxide::prelude::UserId;
+use tokio::sync::Mutex;
+use super::{ConversationEntity, InsertConversationEntity, InsertMessageEntity, InsertNoteEntity, InsertNoteHistoryEntity, InsertRatingEntity, InsertStaffAliasEntity, InsertUserEntity, MessageEntity, MessageType, NoteEntity, NoteHistoryEntity, RatingEntity, StaffAliasEntity, UserEntity, UserStats, InsertTagEntity, TagEntity};
+
+/// Rows of every table with last used id, like sqlite autoincrement
+struct Table<T> {
+    rows: Vec<T>,
+    last_id: i32,
+}
+
+impl<T> Default for Table<T> {
+    fn default() -> Self {
+        Table { rows: vec![], last_id: 0 }
+    }
+}
+
+impl<T> Table<T> {
+    fn next_id(&mut self) -> i32 {
+        self.last_id += 1;
+        self.last_id
+    }
+}
+
+#[derive(Default)]
+struct State {
+    users: Table<UserEntity>,
+    messages: Table<MessageEntity>,
+    notes: Table<NoteEntity>,
+    note_history: Table<NoteHistoryEntity>,
+    conversations: Table<ConversationEntity>,
+    ratings: Table<RatingEntity>,
+    staff_aliases: Table<StaffAliasEntity>,
+    tags: Table<TagEntity>,
+}
+
+/// Database that keeps everything in process memory. Data is lost on restart
+#[derive(Default)]
+pub struct MemoryDatabase {
+    state: Mutex<State>,
+}
+
+impl MemoryDatabase {
+    pub fn new() -> MemoryDatabase {
+        MemoryDatabase::default()
+    }
+}
+
+/// Mirrors diesel changesets, which skip `None` fields unless `treat_none_as_null` is set
+fn set<T>(field: &mut Option<T>, value: Option<T>) {
+    if value.is_some() {
+        *field = value;
+    }
+}
+
+#[async_trait]
+impl super::Database for MemoryDatabase {
+    async fn get_user_by_tg_id(&self, id: UserId) -> super::Result<Option<UserEntity>> {
+        let state = self.state.lock().await;
+        Ok(state.users.rows.iter().find(|u| u.telegram_id == id.0 as i64).cloned())
+    }
+
+    async fn get_user_by_topic(&self, superchat: i64, topic: i64) -> super::Result<Option<UserEntity>> {
+        let state = self.state.lock().await;
+        Ok(state.users.rows.iter().find(|u| u.superchat == Some(superchat) && u.topic == topic).cloned())
+    }
+
+    async fn get_user(&self, id: i32) -> super::Result<Option<UserEntity>> {
+        let state = self.state.lock().await;
+        Ok(state.users.rows.iter().find(|u| u.id == id).cloned())
+    }
+
+    async fn insert_user(&self, entity: InsertUserEntity) -> super::Result<UserEntity> {
+        let mut state = self.state.lock().await;
+        let user = UserEntity {
+            id: state.users.next_id(),
+            telegram_id: entity.telegram_id,
+            topic: entity.topic,
+            info_message: entity.info_message,
+            first_name: entity.first_name,
+            last_name: entity.last_name,
+            lang_code: entity.lang_code,
+            username: entity.username,
+            is_premium: entity.is_premium,
+            first_contact: entity.first_contact,
+            banned: false,
+            superchat: entity.superchat,
+            start_payload: entity.start_payload,
+            lang_selected: false,
+        };
+        state.users.rows.push(user.clone());
+        Ok(user)
+    }
+
+    async fn update_user(&self, user: UserEntity) -> super::Result<()> {
+        let mut state = self.state.lock().await;
+        if let Some(existing) = state.users.rows.iter_mut().find(|u| u.id == user.id) {
+            existing.telegram_id = user.telegram_id;
+            existing.topic = user.topic;
+            set(&mut existing.info_message, user.info_message);
+            set(&mut existing.first_name, user.first_name);
+            set(&mut existing.last_name, user.last_name);
+            set(&mut existing.lang_code, user.lang_code);
+            set(&mut existing.username, user.username);
+            existing.is_premium = user.is_premium;
+            set(&mut existing.first_contact, user.first_contact);
+            existing.banned = user.banned;
+            set(&mut existing.superchat, user.superchat);
+            set(&mut existing.start_payload, user.start_payload);
+            existing.lang_selected = user.lang_selected;
+        }
+        Ok(())
+    }
+
+    async fn update_user_profile(&self, user: &UserEntity) -> super::Result<()> {
+        let mut state = self.state.lock().await;
+        if let Some(existing) = state.users.rows.iter_mut().find(|u| u.id == user.id) {
+            existing.first_name = user.first_name.clone();
+            existing.last_name = user.last_name.clone();
+            existing.lang_code = user.lang_code.clone();
+            existing.username = user.username.clone();
+            existing.is_premium = user.is_premium;
+            existing.lang_selected = user.lang_selected;
+        }
+        Ok(())
+    }
+
+    async fn set_missing_superchat(&self, superchat: i64) -> super::Result<usize> {
+        let mut state = self.state.lock().await;
+        let mut moved = 0;
+        for user in state.users.rows.iter_mut().filter(|u| u.superchat.is_none()) {
+            user.superchat = Some(superchat);
+            moved += 1;
+        }
+        Ok(moved)
+    }
+
+    async fn get_user_stats(&self, user: &UserEntity) -> super::Result<UserStats> {
+        let state = self.state.lock().await;
+        let count = |typ: MessageType| {
+            let typ = typ as i16;
+            state.messages.rows.iter().filter(|m| m.user_id == user.id && m.type_ == typ).count() as i64
+        };
+        Ok(UserStats {
+            incoming: count(MessageType::Incoming),
+            outgoing: count(MessageType::Outgoing),
+            conversations: state.conversations.rows.iter()
+                .filter(|c| c.user_id == user.id && c.closed_at.is_some())
+                .count() as i64,
+        })
+    }
+
+    async fn insert_message(&self, message: InsertMessageEntity) -> super::Result<MessageEntity> {
+        let mut state = self.state.lock().await;
+        let message = MessageEntity {
+            id: state.messages.next_id(),
+            user_id: message.user_id,
+            type_: message.type_,
+            rx_msg_id: message.rx_msg_id,
+            rx_msg: message.rx_msg,
+            tx_msg_id: message.tx_msg_id,
+            tx_chat_id: message.tx_chat_id,
+        };
+        state.messages.rows.push(message.clone());
+        Ok(message)
+    }
+
+    async fn get_message(&self, user: &UserEntity, typ: MessageType, rx_id: i64) -> super::Result<Option<MessageEntity>> {
+        let typ = typ as i16;
+        let state = self.state.lock().await;
+        Ok(state.messages.rows.iter()
+            .find(|m| m.user_id == user.id && m.type_ == typ && m.rx_msg_id == rx_id)
+            .cloned())
+    }
+
+    async fn save_note(&self, note: InsertNoteEntity) -> super::Result<NoteEntity> {
+        let mut state = self.state.lock().await;
+        if let Some(existing) = state.notes.rows.iter_mut().find(|n| n.user_id == note.user_id && n.key == note.key) {
+            existing.value = note.value;
+            existing.type_ = note.type_;
+            return Ok(existing.clone());
+        }
+        let note = NoteEntity {
+            id: state.notes.next_id(),
+            user_id: note.user_id,
+            key: note.key,
+            value: note.value,
+            type_: note.type_,
+        };
+        state.notes.rows.push(note.clone());
+        Ok(note)
+    }
+
+    async fn get_notes(&self, user: &UserEntity) -> super::Result<Vec<NoteEntity>> {
+        let state = self.state.lock().await;
+        Ok(state.notes.rows.iter().filter(|n| n.user_id == user.id).cloned().collect())
+    }
+
+    async fn delete_note(&self, user: &UserEntity, note_key: &str) -> super::Result<()> {
+        let mut state = self.state.lock().await;
+        state.notes.rows.retain(|n| n.user_id != user.id || n.key != note_key);
+        Ok(())
+    }
+
+    async fn insert_note_history(&self, entry: InsertNoteHistoryEntity) -> super::Result<NoteHistoryEntity> {
+        let mut state = self.state.lock().await;
+        let entry = NoteHistoryEntity {
+            id: state.note_history.next_id(),
+            user_id: entry.user_id,
+            key: entry.key,
+            value: entry.value,
+            type_: entry.type_,
+            staff_id: entry.staff_id,
+            created_at: entry.created_at,
+        };
+        state.note_history.rows.push(entry.clone());
+        Ok(entry)
+    }
+
+    async fn get_note_history(&self, user: &UserEntity, note_key: &str) -> super::Result<Vec<NoteHistoryEntity>> {
+        let state = self.state.lock().await;
+        Ok(state.note_history.rows.iter()
+            .filter(|e| e.user_id == user.id && e.key == note_key)
+            .cloned()
+            .collect())
+    }
+
+    async fn get_open_conversation(&self, user: &UserEntity) -> super::Result<Option<ConversationEntity>> {
+        let state = self.state.lock().await;
+        Ok(state.conversations.rows.iter()
+            .find(|c| c.user_id == user.id && c.closed_at.is_none())
+            .cloned())
+    }
+
+    async fn insert_conversation(&self, conversation: InsertConversationEntity) -> super::Result<ConversationEntity> {
+        let mut state = self.state.lock().await;
+        let conversation = ConversationEntity {
+            id: state.conversations.next_id(),
+            user_id: conversation.user_id,
+            opened_at: conversation.opened_at,
+            first_response_at: conversation.first_response_at,
+            unanswered_since: conversation.unanswered_since,
+            closed_at: conversation.closed_at,
+            breached_at: conversation.breached_at,
+            staff_id: conversation.staff_id,
+        };
+        state.conversations.rows.push(conversation.clone());
+        Ok(conversation)
+    }
+
+    async fn update_conversation(&self, conversation: ConversationEntity) -> super::Result<()> {
+        let mut state = self.state.lock().await;
+        if let Some(existing) = state.conversations.rows.iter_mut().find(|c| c.id == conversation.id) {
+            *existing = conversation;
+        }
+        Ok(())
+    }
+
+    async fn get_unanswered_conversations(&self) -> super::Result<Vec<ConversationEntity>> {
+        let state = self.state.lock().await;
+        Ok(state.conversations.rows.iter()
+            .filter(|c| c.closed_at.is_none() && c.unanswered_since.is_some() && c.breached_at.is_none())
+            .cloned()
+            .collect())
+    }
+
+    async fn count_conversations(&self) -> super::Result<(i64, i64)> {
+        let state = self.state.lock().await;
+        let open = state.conversations.rows.iter().filter(|c| c.closed_at.is_none());
+        Ok((
+            open.clone().count() as i64,
+            open.filter(|c| c.unanswered_since.is_some()).count() as i64,
+        ))
+    }
+
+    async fn insert_rating(&self, rating: InsertRatingEntity) -> super::Result<RatingEntity> {
+        let mut state = self.state.lock().await;
+        let rating = RatingEntity {
+            id: state.ratings.next_id(),
+            conversation_id: rating.conversation_id,
+            user_id: rating.user_id,
+            staff_id: rating.staff_id,
+            prompt_message: rating.prompt_message,
+            rating: rating.rating,
+            comment: rating.comment,
+            awaiting_comment: rating.awaiting_comment,
+            rated_at: rating.rated_at,
+        };
+        state.ratings.rows.push(rating.clone());
+        Ok(rating)
+    }
+
+    async fn update_rating(&self, rating: RatingEntity) -> super::Result<()> {
+        let mut state = self.state.lock().await;
+        if let Some(existing) = state.ratings.rows.iter_mut().find(|r| r.id == rating.id) {
+            existing.conversation_id = rating.conversation_id;
+            existing.user_id = rating.user_id;
+            existing.prompt_message = rating.prompt_message;
+            existing.awaiting_comment = rating.awaiting_comment;
+            set(&mut existing.staff_id, rating.staff_id);
+            set(&mut existing.rating, rating.rating);
+            set(&mut existing.comment, rating.comment);
+            set(&mut existing.rated_at, rating.rated_at);
+        }
+        Ok(())
+    }
+
+    async fn get_rating(&self, conversation_id: i32) -> super::Result<Option<RatingEntity>> {
+        let state = self.state.lock().await;
+        Ok(state.ratings.rows.iter().find(|r| r.conversation_id == conversation_id).cloned())
+    }
+
+    async fn get_rating_awaiting_comment(&self, user: &UserEntity) -> super::Result<Option<RatingEntity>> {
+        let state = self.state.lock().await;
+        Ok(state.ratings.rows.iter()
+            .find(|r| r.user_id == user.id && r.awaiting_comment)
+            .cloned())
+    }
+
+    async fn get_staff_alias(&self, staff: UserId) -> super::Result<Option<StaffAliasEntity>> {
+        let state = self.state.lock().await;
+        Ok(state.staff_aliases.rows.iter().find(|a| a.telegram_id == staff.0 as i64).cloned())
+    }
+
+    async fn save_staff_alias(&self, alias: InsertStaffAliasEntity) -> super::Result<StaffAliasEntity> {
+        let mut state = self.state.lock().await;
+        if let Some(existing) = state.staff_aliases.rows.iter_mut().find(|a| a.telegram_id == alias.telegram_id) {
+            existing.alias = alias.alias;
+            return Ok(existing.clone());
+        }
+        let alias = StaffAliasEntity {
+            id: state.staff_aliases.next_id(),
+            telegram_id: alias.telegram_id,
+            alias: alias.alias,
+        };
+        state.staff_aliases.rows.push(alias.clone());
+        Ok(alias)
+    }
+
+    async fn delete_staff_alias(&self, staff: UserId) -> super::Result<()> {
+        let mut state = self.state.lock().await;
+        state.staff_aliases.rows.retain(|a| a.telegram_id != staff.0 as i64);
+        Ok(())
+    }
+
+    async fn add_tag(&self, tag: InsertTagEntity) -> super::Result<TagEntity> {
+        let mut state = self.state.lock().await;
+        if let Some(existing) = state.tags.rows.iter().find(|t| t.user_id == tag.user_id && t.tag == tag.tag) {
+            return Ok(existing.clone());
+        }
+        let tag = TagEntity {
+            id: state.tags.next_id(),
+            user_id: tag.user_id,
+            tag: tag.tag,
+        };
+        state.tags.rows.push(tag.clone());
+        Ok(tag)
+    }
+
+    async fn delete_tag(&self, user: &UserEntity, tag: &str) -> super::Result<()> {
+        let mut state = self.state.lock().await;
+        state.tags.rows.retain(|t| t.user_id != user.id || t.tag != tag);
+        Ok(())
+    }
+
+    async fn get_tags(&self, user: &UserEntity) -> super::Result<Vec<TagEntity>> {
+        let state = self.state.lock().await;
+        Ok(state.tags.rows.iter().filter(|t| t.user_id == user.id).cloned().collect())
+    }
+
+    async fn get_users_by_tag(&self, tag: &str) -> super::Result<Vec<UserEntity>> {
+        let state = self.state.lock().await;
+        Ok(state.users.rows.iter()
+            .filter(|u| state.tags.rows.iter().any(|t| t.user_id == u.id && t.tag == tag))
+            .cloned()
+            .collect())
+    }
+}
@@ -1,12 +1,17 @@
 use std::error::Error;
+use std::path::Path;
 use async_trait::async_trait;
 use serde::Deserialize;
 use teloxide::prelude::UserId;
 use tracing::info;
+use crate::config::ConfigErrors;
 
 mod sqlite;
+mod memory;
 mod entities;
-pub use entities::{UserEntity, InsertUserEntity, InsertMessageEntity, MessageType, MessageEntity, InsertNoteEntity, NoteEntity};
+#[cfg(test)]
+mod tests;
+pub use entities::{UserEntity, InsertUserEntity, InsertMessageEntity, MessageType, MessageEntity, InsertNoteEntity, NoteEntity, NoteType, InsertNoteHistoryEntity, NoteHistoryEntity, InsertConversationEntity, ConversationEntity, InsertRatingEntity, RatingEntity, InsertStaffAliasEntity, StaffAliasEntity, UserStats, InsertTagEntity, TagEntity};
 
 type Result<T> = std::result::Result<T, Box<dyn Error + Send + Sync>>;
 
@@ -14,12 +19,23 @@ type Result<T> = std::result::Result<T, Box<dyn Error + Send + Sync>>;
 pub trait Database: Send + Sync {
     async fn get_user_by_tg_id(&self, id: UserId) -> Result<Option<UserEntity>>;
 
-    async fn get_user_by_topic(&self, topic: i64) -> Result<Option<UserEntity>>;
+    async fn get_user_by_topic(&self, superchat: i64, topic: i64) -> Result<Option<UserEntity>>;
+
+    async fn get_user(&self, id: i32) -> Result<Option<UserEntity>>;
 
     async fn insert_user(&self, entity: InsertUserEntity) -> Result<UserEntity>;
 
     async fn update_user(&self, user: UserEntity) -> Result<()>;
 
+    /// Overwrites user name, username, premium flag, language and whether it was selected by user,
+    /// unlike [Database::update_user] missing values are stored too
+    async fn update_user_profile(&self, user: &UserEntity) -> Result<()>;
+
+    /// Moves users without superchat, created before superchats were stored, to `superchat`. Returns count of moved users
+    async fn set_missing_superchat(&self, superchat: i64) -> Result<usize>;
+
+    async fn get_user_stats(&self, user: &UserEntity) -> Result<UserStats>;
+
     async fn insert_message(&self, message: InsertMessageEntity) -> Result<MessageEntity>;
 
     async fn get_message(&self, user: &UserEntity, typ: MessageType, rx_id: i64) -> Result<Option<MessageEntity>>;
@@ -29,12 +45,82 @@ pub trait Database: Send + Sync {
     async fn get_notes(&self, user: &UserEntity) -> Result<Vec<NoteEntity>>;
 
     async fn delete_note(&self, user: &UserEntity, note_key: &str) -> Result<()>;
+
+    async fn insert_note_history(&self, entry: InsertNoteHistoryEntity) -> Result<NoteHistoryEntity>;
+
+    /// Returns changes of user note, oldest first
+    async fn get_note_history(&self, user: &UserEntity, note_key: &str) -> Result<Vec<NoteHistoryEntity>>;
+
+    async fn get_open_conversation(&self, user: &UserEntity) -> Result<Option<ConversationEntity>>;
+
+    async fn insert_conversation(&self, conversation: InsertConversationEntity) -> Result<ConversationEntity>;
+
+    async fn update_conversation(&self, conversation: ConversationEntity) -> Result<()>;
+
+    /// Returns open conversations waiting for staff reply that have no SLA breach recorded yet
+    async fn get_unanswered_conversations(&self) -> Result<Vec<ConversationEntity>>;
+
+    /// Returns count of open conversations and count of open conversations waiting for staff reply
+    async fn count_conversations(&self) -> Result<(i64, i64)>;
+
+    async fn insert_rating(&self, rating: InsertRatingEntity) -> Result<RatingEntity>;
+
+    async fn update_rating(&self, rating: RatingEntity) -> Result<()>;
+
+    async fn get_rating(&self, conversation_id: i32) -> Result<Option<RatingEntity>>;
+
+    /// Returns rating that waits for user comment
+    async fn get_rating_awaiting_comment(&self, user: &UserEntity) -> Result<Option<RatingEntity>>;
+
+    async fn get_staff_alias(&self, staff: UserId) -> Result<Option<StaffAliasEntity>>;
+
+    async fn save_staff_alias(&self, alias: InsertStaffAliasEntity) -> Result<StaffAliasEntity>;
+
+    async fn delete_staff_alias(&self, staff: UserId) -> Result<()>;
+
+    /// Adds tag to user. Adding existing tag returns it unchanged
+    async fn add_tag(&self, tag: InsertTagEntity) -> Result<TagEntity>;
+
+    async fn delete_tag(&self, user: &UserEntity, tag: &str) -> Result<()>;
+
+    /// Returns user tags in order they were added
+    async fn get_tags(&self, user: &UserEntity) -> Result<Vec<TagEntity>>;
+
+    async fn get_users_by_tag(&self, tag: &str) -> Result<Vec<UserEntity>>;
 }
 
 #[derive(Deserialize, Debug)]
 #[serde(tag = "type")]
 pub enum  DatabaseConfig {
-    Sqlite { path: String }
+    Sqlite { path: String },
+    /// Keeps data in memory only, for tests and dry runs
+    InMemory,
+}
+
+impl DatabaseConfig {
+    /// Checks that sqlite file can be created or written
+    pub fn validate(&self, errors: &mut ConfigErrors) {
+        let DatabaseConfig::Sqlite { path } = self else {
+            return;
+        };
+        let existed = Path::new(path).exists();
+        match std::fs::OpenOptions::new().create(true).append(true).open(path) {
+            Err(e) => errors.add("database.path", format!("{} is not writable: {}", path, e)),
+            // probe shouldn't leave empty file behind
+            Ok(_) if !existed => {
+                let _ = std::fs::remove_file(path);
+            }
+            Ok(_) => {}
+        }
+    }
+}
+
+/// Applies pending migrations. Returns versions of applied migrations
+pub fn migrate(config: &DatabaseConfig) -> anyhow::Result<Vec<String>> {
+    match config {
+        DatabaseConfig::Sqlite { path } => sqlite::SqliteDatabase::migrate(path),
+        DatabaseConfig::InMemory => Ok(vec![]),
+    }
 }
 
 pub async fn connect(config: DatabaseConfig) -> anyhow::Result<Box<dyn Database>> {
@@ -43,5 +129,15 @@ pub async fn connect(config: DatabaseConfig) -> anyhow::Result<Box<dyn Database>
             info!("Connecting to sqlite database at path {path}");
             Ok(Box::new(sqlite::SqliteDatabase::connect(&path)?))
         }
+        DatabaseConfig::InMemory => {
+            info!("Using in-memory database, data will be lost on restart");
+            Ok(Box::new(memory::MemoryDatabase::new()))
+        }
     }
-}
\ No newline at end of file
+}
+
+/// Creates migrated sqlite database in memory
+#[cfg(test)]
+pub fn sqlite_in_memory() -> anyhow::Result<Box<dyn Database>> {
+    Ok(Box::new(sqlite::SqliteDatabase::in_memory()?))
+}
@@ -3,13 +3,21 @@ use diesel::{Connection, OptionalExtension, QueryDsl, RunQueryDsl, SelectableHel
 use diesel::associations::HasTable;
 use teloxide::prelude::UserId;
 use tokio::sync::Mutex;
+use diesel_migrations::{embed_migrations, EmbeddedMigrations, MigrationHarness};
 use diesel::ExpressionMethods;
-use crate::database::entities::{InsertNoteEntity, NoteEntity};
-use super::{InsertMessageEntity, InsertUserEntity, MessageEntity, MessageType, UserEntity};
+use crate::database::entities::{ConversationEntity, InsertConversationEntity, InsertNoteEntity, InsertNoteHistoryEntity, InsertRatingEntity, InsertStaffAliasEntity, NoteEntity, NoteHistoryEntity, RatingEntity, StaffAliasEntity, InsertTagEntity, TagEntity};
+use super::{InsertMessageEntity, InsertUserEntity, MessageEntity, MessageType, UserEntity, UserStats};
 use crate::schema::users::dsl::users;
 use crate::schema::users::{telegram_id, topic};
 use crate::schema::messages::dsl::messages;
 use crate::schema::notes::dsl::notes;
+use crate::schema::note_history::dsl::note_history;
+use crate::schema::conversations::dsl::conversations;
+use crate::schema::ratings::dsl::ratings;
+use crate::schema::staff_aliases::dsl::staff_aliases;
+use crate::schema::user_tags::dsl::user_tags;
+
+const MIGRATIONS: EmbeddedMigrations = embed_migrations!();
 
 pub struct SqliteDatabase {
     conn: Mutex<SqliteConnection>,
@@ -21,6 +29,20 @@ impl SqliteDatabase {
             conn: Mutex::new(SqliteConnection::establish(db)?)
         })
     }
+
+    /// Applies pending migrations to database at path. Returns versions of applied migrations
+    pub fn migrate(db: &str) -> anyhow::Result<Vec<String>> {
+        let mut conn = SqliteConnection::establish(db)?;
+        let applied = conn.run_pending_migrations(MIGRATIONS).map_err(|e| anyhow::anyhow!(e))?;
+        Ok(applied.iter().map(|v| v.to_string()).collect())
+    }
+
+    #[cfg(test)]
+    pub fn in_memory() -> anyhow::Result<SqliteDatabase> {
+        let mut conn = SqliteConnection::establish(":memory:")?;
+        conn.run_pending_migrations(MIGRATIONS).map_err(|e| anyhow::anyhow!(e))?;
+        Ok(SqliteDatabase { conn: Mutex::new(conn) })
+    }
 }
 
 #[async_trait]
@@ -34,15 +56,29 @@ impl super::Database for SqliteDatabase {
             optional()?)
     }
 
-    async fn get_user_by_topic(&self, t: i64) -> super::Result<Option<UserEntity>> {
+    async fn get_user_by_topic(&self, chat: i64, t: i64) -> super::Result<Option<UserEntity>> {
+        use crate::schema::users::superchat;
+
         let mut conn = self.conn.lock().await;
         Ok(users
             .select(UserEntity::as_select())
+            .filter(superchat.eq(chat))
             .filter(topic.eq(t))
             .first(&mut *conn)
             .optional()?)
     }
 
+    async fn get_user(&self, user_id: i32) -> super::Result<Option<UserEntity>> {
+        use crate::schema::users::id;
+
+        let mut conn = self.conn.lock().await;
+        Ok(users
+            .select(UserEntity::as_select())
+            .filter(id.eq(user_id))
+            .first(&mut *conn)
+            .optional()?)
+    }
+
     async fn insert_user(&self, entity: InsertUserEntity) -> super::Result<UserEntity> {
         let mut conn = self.conn.lock().await;
         Ok(diesel::insert_into(users::table())
@@ -61,6 +97,57 @@ impl super::Database for SqliteDatabase {
         Ok(())
     }
 
+    async fn update_user_profile(&self, user: &UserEntity) -> crate::database::Result<()> {
+        use crate::schema::users::{id, first_name, last_name, lang_code, username, is_premium, lang_selected};
+
+        let mut conn = self.conn.lock().await;
+        diesel::update(users::table())
+            .filter(id.eq(user.id))
+            .set((
+                first_name.eq(&user.first_name),
+                last_name.eq(&user.last_name),
+                lang_code.eq(&user.lang_code),
+                username.eq(&user.username),
+                is_premium.eq(user.is_premium),
+                lang_selected.eq(user.lang_selected),
+            ))
+            .execute(&mut *conn)?;
+        Ok(())
+    }
+
+    async fn set_missing_superchat(&self, chat: i64) -> crate::database::Result<usize> {
+        use crate::schema::users::superchat;
+
+        let mut conn = self.conn.lock().await;
+        Ok(diesel::update(users::table())
+            .filter(superchat.is_null())
+            .set(superchat.eq(chat))
+            .execute(&mut *conn)?)
+    }
+
+    async fn get_user_stats(&self, user: &UserEntity) -> crate::database::Result<UserStats> {
+        use crate::schema::messages::{type_, user_id};
+        use crate::schema::conversations::{closed_at, user_id as conversation_user_id};
+
+        let mut conn = self.conn.lock().await;
+        let incoming: i64 = messages
+            .filter(user_id.eq(user.id))
+            .filter(type_.eq(MessageType::Incoming as i16))
+            .count()
+            .get_result(&mut *conn)?;
+        let outgoing: i64 = messages
+            .filter(user_id.eq(user.id))
+            .filter(type_.eq(MessageType::Outgoing as i16))
+            .count()
+            .get_result(&mut *conn)?;
+        let closed: i64 = conversations
+            .filter(conversation_user_id.eq(user.id))
+            .filter(closed_at.is_not_null())
+            .count()
+            .get_result(&mut *conn)?;
+        Ok(UserStats { incoming, outgoing, conversations: closed })
+    }
+
     async fn insert_message(&self, message: InsertMessageEntity) -> crate::database::Result<MessageEntity> {
         let mut conn = self.conn.lock().await;
         Ok(diesel::insert_into(messages::table())
@@ -97,6 +184,7 @@ impl super::Database for SqliteDatabase {
             }
             Some(mut entity) => {
                 entity.value = note.value;
+                entity.type_ = note.type_;
                 diesel::update(notes::table())
                     .filter(id.eq(entity.id))
                     .set(entity.clone())
@@ -125,4 +213,197 @@ impl super::Database for SqliteDatabase {
             .execute(&mut *conn)?;
         Ok(())
     }
+
+    async fn insert_note_history(&self, entry: InsertNoteHistoryEntity) -> crate::database::Result<NoteHistoryEntity> {
+        let mut conn = self.conn.lock().await;
+        Ok(diesel::insert_into(note_history::table())
+            .values(&entry)
+            .get_result(&mut *conn)?)
+    }
+
+    async fn get_note_history(&self, user: &UserEntity, note_key: &str) -> crate::database::Result<Vec<NoteHistoryEntity>> {
+        use crate::schema::note_history::{user_id, key, id};
+
+        let mut conn = self.conn.lock().await;
+        Ok(note_history.select(NoteHistoryEntity::as_select())
+            .filter(user_id.eq(user.id))
+            .filter(key.eq(note_key))
+            .order(id.asc())
+            .get_results(&mut *conn)?)
+    }
+
+    async fn get_open_conversation(&self, user: &UserEntity) -> crate::database::Result<Option<ConversationEntity>> {
+        use crate::schema::conversations::{user_id, closed_at};
+
+        let mut conn = self.conn.lock().await;
+        Ok(conversations.select(ConversationEntity::as_select())
+            .filter(user_id.eq(user.id))
+            .filter(closed_at.is_null())
+            .first(&mut *conn)
+            .optional()?)
+    }
+
+    async fn insert_conversation(&self, conversation: InsertConversationEntity) -> crate::database::Result<ConversationEntity> {
+        let mut conn = self.conn.lock().await;
+        Ok(diesel::insert_into(conversations::table())
+            .values(&conversation)
+            .get_result(&mut *conn)?)
+    }
+
+    async fn update_conversation(&self, conversation: ConversationEntity) -> crate::database::Result<()> {
+        use crate::schema::conversations::id;
+
+        let mut conn = self.conn.lock().await;
+        diesel::update(conversations::table())
+            .filter(id.eq(conversation.id))
+            .set(conversation)
+            .execute(&mut *conn)?;
+        Ok(())
+    }
+
+    async fn get_unanswered_conversations(&self) -> crate::database::Result<Vec<ConversationEntity>> {
+        use crate::schema::conversations::{closed_at, unanswered_since, breached_at};
+
+        let mut conn = self.conn.lock().await;
+        Ok(conversations.select(ConversationEntity::as_select())
+            .filter(closed_at.is_null())
+            .filter(unanswered_since.is_not_null())
+            .filter(breached_at.is_null())
+            .get_results(&mut *conn)?)
+    }
+
+    async fn count_conversations(&self) -> crate::database::Result<(i64, i64)> {
+        use crate::schema::conversations::{closed_at, unanswered_since};
+
+        let mut conn = self.conn.lock().await;
+        let open: i64 = conversations
+            .filter(closed_at.is_null())
+            .count()
+            .get_result(&mut *conn)?;
+        let unanswered: i64 = conversations
+            .filter(closed_at.is_null())
+            .filter(unanswered_since.is_not_null())
+            .count()
+            .get_result(&mut *conn)?;
+        Ok((open, unanswered))
+    }
+
+    async fn insert_rating(&self, rating: InsertRatingEntity) -> crate::database::Result<RatingEntity> {
+        let mut conn = self.conn.lock().await;
+        Ok(diesel::insert_into(ratings::table())
+            .values(&rating)
+            .get_result(&mut *conn)?)
+    }
+
+    async fn update_rating(&self, rating: RatingEntity) -> crate::database::Result<()> {
+        use crate::schema::ratings::id;
+
+        let mut conn = self.conn.lock().await;
+        diesel::update(ratings::table())
+            .filter(id.eq(rating.id))
+            .set(rating)
+            .execute(&mut *conn)?;
+        Ok(())
+    }
+
+    async fn get_rating(&self, conversation: i32) -> crate::database::Result<Option<RatingEntity>> {
+        use crate::schema::ratings::conversation_id;
+
+        let mut conn = self.conn.lock().await;
+        Ok(ratings.select(RatingEntity::as_select())
+            .filter(conversation_id.eq(conversation))
+            .first(&mut *conn)
+            .optional()?)
+    }
+
+    async fn get_rating_awaiting_comment(&self, user: &UserEntity) -> crate::database::Result<Option<RatingEntity>> {
+        use crate::schema::ratings::{user_id, awaiting_comment};
+
+        let mut conn = self.conn.lock().await;
+        Ok(ratings.select(RatingEntity::as_select())
+            .filter(user_id.eq(user.id))
+            .filter(awaiting_comment.eq(true))
+            .first(&mut *conn)
+            .optional()?)
+    }
+
+    async fn get_staff_alias(&self, staff: UserId) -> crate::database::Result<Option<StaffAliasEntity>> {
+        use crate::schema::staff_aliases::telegram_id;
+
+        let mut conn = self.conn.lock().await;
+        Ok(staff_aliases.select(StaffAliasEntity::as_select())
+            .filter(telegram_id.eq(staff.0 as i64))
+            .first(&mut *conn)
+            .optional()?)
+    }
+
+    async fn save_staff_alias(&self, entity: InsertStaffAliasEntity) -> crate::database::Result<StaffAliasEntity> {
+        use crate::schema::staff_aliases::{telegram_id, alias};
+
+        let mut conn = self.conn.lock().await;
+        Ok(diesel::insert_into(staff_aliases::table())
+            .values(&entity)
+            .on_conflict(telegram_id)
+            .do_update()
+            .set(alias.eq(&entity.alias))
+            .get_result(&mut *conn)?)
+    }
+
+    async fn delete_staff_alias(&self, staff: UserId) -> crate::database::Result<()> {
+        use crate::schema::staff_aliases::telegram_id;
+
+        let mut conn = self.conn.lock().await;
+        diesel::delete(staff_aliases::table())
+            .filter(telegram_id.eq(staff.0 as i64))
+            .execute(&mut *conn)?;
+        Ok(())
+    }
+
+    async fn add_tag(&self, entity: InsertTagEntity) -> crate::database::Result<TagEntity> {
+        use crate::schema::user_tags::{user_id, tag};
+
+        let mut conn = self.conn.lock().await;
+        diesel::insert_into(user_tags::table())
+            .values(&entity)
+            .on_conflict((user_id, tag))
+            .do_nothing()
+            .execute(&mut *conn)?;
+        Ok(user_tags.select(TagEntity::as_select())
+            .filter(user_id.eq(entity.user_id))
+            .filter(tag.eq(&entity.tag))
+            .first(&mut *conn)?)
+    }
+
+    async fn delete_tag(&self, user: &UserEntity, user_tag: &str) -> crate::database::Result<()> {
+        use crate::schema::user_tags::{user_id, tag};
+
+        let mut conn = self.conn.lock().await;
+        diesel::delete(user_tags::table())
+            .filter(user_id.eq(user.id))
+            .filter(tag.eq(user_tag))
+            .execute(&mut *conn)?;
+        Ok(())
+    }
+
+    async fn get_tags(&self, user: &UserEntity) -> crate::database::Result<Vec<TagEntity>> {
+        use crate::schema::user_tags::{user_id, id};
+
+        let mut conn = self.conn.lock().await;
+        Ok(user_tags.select(TagEntity::as_select())
+            .filter(user_id.eq(user.id))
+            .order(id.asc())
+            .get_results(&mut *conn)?)
+    }
+
+    async fn get_users_by_tag(&self, user_tag: &str) -> crate::database::Result<Vec<UserEntity>> {
+        use crate::schema::user_tags::tag;
+        use crate::schema::users::id;
+
+        let mut conn = self.conn.lock().await;
+        Ok(users.inner_join(user_tags)
+            .filter(tag.eq(user_tag))
+            .select(UserEntity::as_select())
+            .order(id.asc())
+            .get_results(&mut *conn)?)
+    }
 }
\ No newline at end of file
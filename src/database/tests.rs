@@ -0,0 +1,313 @@
+//! Conformance suite that every [Database] backend must pass
+
+use teloxide::prelude::UserId;
+use super::{Database, InsertConversationEntity, InsertMessageEntity, InsertNoteEntity, InsertNoteHistoryEntity, InsertRatingEntity, InsertStaffAliasEntity, InsertTagEntity, InsertUserEntity, MessageType, NoteType, UserEntity, UserStats};
+
+/// Generates a test per check for each backend
+macro_rules! conformance {
+    ($($check:ident),* $(,)?) => {
+        mod sqlite {
+            $(
+                #[tokio::test]
+                async fn $check() {
+                    super::$check(&crate::database::sqlite::SqliteDatabase::in_memory().unwrap()).await;
+                }
+            )*
+        }
+
+        mod memory {
+            $(
+                #[tokio::test]
+                async fn $check() {
+                    super::$check(&crate::database::memory::MemoryDatabase::new()).await;
+                }
+            )*
+        }
+    };
+}
+
+conformance!(
+    users_are_found_by_telegram_id_topic_and_id,
+    update_user_skips_missing_fields,
+    update_user_profile_stores_missing_fields,
+    topics_are_scoped_to_superchat,
+    messages_are_found_by_user_type_and_rx_id,
+    user_stats_count_messages_and_closed_conversations,
+    save_note_upserts_by_key,
+    delete_note_removes_only_matching_key,
+    note_history_is_kept_per_user_and_key,
+    open_conversation_ignores_closed,
+    unanswered_conversations_and_counts,
+    ratings_are_found_by_conversation_and_comment_state,
+    staff_alias_upserts_and_deletes,
+    tags_are_added_once_and_queried_by_tag,
+);
+
+const SUPERCHAT: i64 = -100;
+
+async fn user(db: &dyn Database, telegram_id: i64, topic: i64) -> UserEntity {
+    db.insert_user(InsertUserEntity {
+        telegram_id,
+        topic,
+        info_message: None,
+        first_name: Some("John".to_string()),
+        last_name: None,
+        lang_code: Some("en".to_string()),
+        username: None,
+        is_premium: false,
+        first_contact: Some(1000),
+        superchat: Some(SUPERCHAT),
+        start_payload: None,
+    }).await.unwrap()
+}
+
+fn conversation(user: &UserEntity, opened_at: i64) -> InsertConversationEntity {
+    InsertConversationEntity {
+        user_id: user.id,
+        opened_at,
+        first_response_at: None,
+        unanswered_since: Some(opened_at),
+        closed_at: None,
+        breached_at: None,
+        staff_id: None,
+    }
+}
+
+async fn users_are_found_by_telegram_id_topic_and_id(db: &dyn Database) {
+    let first = user(db, 10, 100).await;
+    let second = user(db, 20, 200).await;
+    assert_ne!(first.id, second.id);
+
+    assert_eq!(db.get_user_by_tg_id(UserId(20)).await.unwrap().unwrap().id, second.id);
+    assert_eq!(db.get_user_by_topic(SUPERCHAT, 100).await.unwrap().unwrap().id, first.id);
+    assert_eq!(db.get_user(second.id).await.unwrap().unwrap().telegram_id, 20);
+    assert!(db.get_user_by_tg_id(UserId(30)).await.unwrap().is_none());
+    assert!(db.get_user_by_topic(SUPERCHAT, 300).await.unwrap().is_none());
+}
+
+async fn topics_are_scoped_to_superchat(db: &dyn Database) {
+    let first = user(db, 10, 100).await;
+    let second = db.insert_user(InsertUserEntity {
+        telegram_id: 20,
+        topic: 100,
+        info_message: None,
+        first_name: None,
+        last_name: None,
+        lang_code: None,
+        username: None,
+        is_premium: false,
+        first_contact: None,
+        superchat: None,
+        start_payload: Some("promo".to_string()),
+    }).await.unwrap();
+    assert_eq!(db.get_user(second.id).await.unwrap().unwrap().start_payload.as_deref(), Some("promo"));
+    assert!(db.get_user_by_topic(-200, 100).await.unwrap().is_none());
+
+    assert_eq!(db.set_missing_superchat(-200).await.unwrap(), 1);
+    assert_eq!(db.get_user_by_topic(-200, 100).await.unwrap().unwrap().id, second.id);
+    assert_eq!(db.get_user_by_topic(SUPERCHAT, 100).await.unwrap().unwrap().id, first.id);
+    assert_eq!(db.set_missing_superchat(-200).await.unwrap(), 0);
+}
+
+async fn update_user_skips_missing_fields(db: &dyn Database) {
+    let mut entity = user(db, 10, 100).await;
+    entity.info_message = Some(5);
+    entity.first_name = None;
+    db.update_user(entity.clone()).await.unwrap();
+
+    let stored = db.get_user(entity.id).await.unwrap().unwrap();
+    assert_eq!(stored.info_message, Some(5));
+    assert_eq!(stored.first_name.as_deref(), Some("John"));
+}
+
+async fn update_user_profile_stores_missing_fields(db: &dyn Database) {
+    let mut entity = user(db, 10, 100).await;
+    entity.first_name = Some("Jane".to_string());
+    entity.lang_code = None;
+    entity.lang_selected = true;
+    db.update_user_profile(&entity).await.unwrap();
+
+    let stored = db.get_user(entity.id).await.unwrap().unwrap();
+    assert_eq!(stored.first_name.as_deref(), Some("Jane"));
+    assert!(stored.lang_code.is_none());
+    assert!(stored.lang_selected);
+}
+
+async fn messages_are_found_by_user_type_and_rx_id(db: &dyn Database) {
+    let entity = user(db, 10, 100).await;
+    let insert = |type_: MessageType, rx_msg_id: i64, tx_msg_id: i64| InsertMessageEntity {
+        user_id: entity.id,
+        type_: type_ as i16,
+        rx_msg_id,
+        rx_msg: "{}".to_string(),
+        tx_msg_id,
+        tx_chat_id: Some(SUPERCHAT),
+    };
+    db.insert_message(insert(MessageType::Incoming, 1, 11)).await.unwrap();
+    db.insert_message(insert(MessageType::Outgoing, 1, 21)).await.unwrap();
+
+    let incoming = db.get_message(&entity, MessageType::Incoming, 1).await.unwrap().unwrap();
+    assert_eq!(incoming.tx_msg_id, 11);
+    assert_eq!(incoming.tx_chat_id, Some(SUPERCHAT));
+    let outgoing = db.get_message(&entity, MessageType::Outgoing, 1).await.unwrap().unwrap();
+    assert_eq!(outgoing.tx_msg_id, 21);
+    assert!(db.get_message(&entity, MessageType::Incoming, 2).await.unwrap().is_none());
+}
+
+async fn user_stats_count_messages_and_closed_conversations(db: &dyn Database) {
+    let entity = user(db, 10, 100).await;
+    let other = user(db, 20, 200).await;
+    for (owner, type_, rx_msg_id) in [(&entity, MessageType::Incoming, 1), (&entity, MessageType::Incoming, 2), (&entity, MessageType::Outgoing, 3), (&other, MessageType::Incoming, 4)] {
+        db.insert_message(InsertMessageEntity { user_id: owner.id, type_: type_ as i16, rx_msg_id, rx_msg: "{}".to_string(), tx_msg_id: rx_msg_id, tx_chat_id: None }).await.unwrap();
+    }
+    let mut closed = db.insert_conversation(conversation(&entity, 1000)).await.unwrap();
+    closed.closed_at = Some(2000);
+    db.update_conversation(closed).await.unwrap();
+    db.insert_conversation(conversation(&entity, 3000)).await.unwrap();
+
+    assert_eq!(db.get_user_stats(&entity).await.unwrap(), UserStats { incoming: 2, outgoing: 1, conversations: 1 });
+}
+
+async fn save_note_upserts_by_key(db: &dyn Database) {
+    let entity = user(db, 10, 100).await;
+    let note = |key: &str, value: &str, type_: NoteType| InsertNoteEntity { user_id: entity.id, key: key.to_string(), value: value.to_string(), type_: type_ as i16 };
+    let first = db.save_note(note("order", "1", NoteType::String)).await.unwrap();
+    let updated = db.save_note(note("order", "2", NoteType::Number)).await.unwrap();
+    db.save_note(note("plan", "pro", NoteType::String)).await.unwrap();
+
+    assert_eq!(first.id, updated.id);
+    assert_eq!(updated.value, "2");
+    assert_eq!(db.get_notes(&entity).await.unwrap()[0].type_, NoteType::Number as i16);
+    let notes = db.get_notes(&entity).await.unwrap();
+    assert_eq!(notes.iter().map(|n| (n.key.as_str(), n.value.as_str())).collect::<Vec<_>>(), vec![("order", "2"), ("plan", "pro")]);
+}
+
+async fn delete_note_removes_only_matching_key(db: &dyn Database) {
+    let first = user(db, 10, 100).await;
+    let second = user(db, 20, 200).await;
+    for entity in [&first, &second] {
+        db.save_note(InsertNoteEntity { user_id: entity.id, key: "order".to_string(), value: "1".to_string(), type_: 0 }).await.unwrap();
+    }
+    db.save_note(InsertNoteEntity { user_id: first.id, key: "plan".to_string(), value: "pro".to_string(), type_: 0 }).await.unwrap();
+
+    db.delete_note(&first, "order").await.unwrap();
+
+    let notes = db.get_notes(&first).await.unwrap();
+    assert_eq!(notes.len(), 1);
+    assert_eq!(notes[0].key, "plan");
+    assert_eq!(db.get_notes(&second).await.unwrap().len(), 1);
+}
+
+async fn note_history_is_kept_per_user_and_key(db: &dyn Database) {
+    let entity = user(db, 10, 100).await;
+    let other = user(db, 20, 200).await;
+    let entry = |user: &UserEntity, key: &str, value: Option<&str>, created_at: i64| InsertNoteHistoryEntity {
+        user_id: user.id,
+        key: key.to_string(),
+        value: value.map(|v| v.to_string()),
+        type_: NoteType::String as i16,
+        staff_id: Some(7),
+        created_at,
+    };
+    db.insert_note_history(entry(&entity, "order", Some("1"), 1000)).await.unwrap();
+    db.insert_note_history(entry(&entity, "plan", Some("pro"), 1100)).await.unwrap();
+    db.insert_note_history(entry(&other, "order", Some("5"), 1200)).await.unwrap();
+    db.insert_note_history(entry(&entity, "order", None, 1300)).await.unwrap();
+
+    let history = db.get_note_history(&entity, "order").await.unwrap();
+    assert_eq!(history.iter().map(|e| (e.value.as_deref(), e.created_at)).collect::<Vec<_>>(), vec![(Some("1"), 1000), (None, 1300)]);
+    assert!(db.get_note_history(&other, "plan").await.unwrap().is_empty());
+}
+
+async fn open_conversation_ignores_closed(db: &dyn Database) {
+    let entity = user(db, 10, 100).await;
+    assert!(db.get_open_conversation(&entity).await.unwrap().is_none());
+
+    let mut closed = db.insert_conversation(conversation(&entity, 1000)).await.unwrap();
+    closed.closed_at = Some(2000);
+    db.update_conversation(closed).await.unwrap();
+    assert!(db.get_open_conversation(&entity).await.unwrap().is_none());
+
+    let open = db.insert_conversation(conversation(&entity, 3000)).await.unwrap();
+    assert_eq!(db.get_open_conversation(&entity).await.unwrap().unwrap().id, open.id);
+}
+
+async fn unanswered_conversations_and_counts(db: &dyn Database) {
+    let first = user(db, 10, 100).await;
+    let second = user(db, 20, 200).await;
+    let third = user(db, 30, 300).await;
+    let unanswered = db.insert_conversation(conversation(&first, 1000)).await.unwrap();
+    let mut answered = db.insert_conversation(conversation(&second, 1000)).await.unwrap();
+    answered.unanswered_since = None;
+    answered.first_response_at = Some(1100);
+    db.update_conversation(answered).await.unwrap();
+    let mut breached = db.insert_conversation(conversation(&third, 1000)).await.unwrap();
+    breached.breached_at = Some(2000);
+    db.update_conversation(breached).await.unwrap();
+
+    let found = db.get_unanswered_conversations().await.unwrap();
+    assert_eq!(found.iter().map(|c| c.id).collect::<Vec<_>>(), vec![unanswered.id]);
+    assert_eq!(db.count_conversations().await.unwrap(), (3, 2));
+}
+
+async fn ratings_are_found_by_conversation_and_comment_state(db: &dyn Database) {
+    let entity = user(db, 10, 100).await;
+    let open = db.insert_conversation(conversation(&entity, 1000)).await.unwrap();
+    let mut rating = db.insert_rating(InsertRatingEntity {
+        conversation_id: open.id,
+        user_id: entity.id,
+        staff_id: Some(7),
+        prompt_message: 50,
+        rating: None,
+        comment: None,
+        awaiting_comment: false,
+        rated_at: None,
+    }).await.unwrap();
+    assert!(db.get_rating_awaiting_comment(&entity).await.unwrap().is_none());
+
+    rating.rating = Some(5);
+    rating.awaiting_comment = true;
+    rating.rated_at = Some(1700000000);
+    db.update_rating(rating.clone()).await.unwrap();
+    let awaiting = db.get_rating_awaiting_comment(&entity).await.unwrap().unwrap();
+    assert_eq!(awaiting.id, rating.id);
+    assert_eq!(awaiting.rated_at, Some(1700000000));
+    assert_eq!(awaiting.rating, Some(5));
+
+    rating.awaiting_comment = false;
+    rating.comment = Some("Great".to_string());
+    db.update_rating(rating).await.unwrap();
+    let stored = db.get_rating(open.id).await.unwrap().unwrap();
+    assert_eq!(stored.comment.as_deref(), Some("Great"));
+    assert_eq!(stored.staff_id, Some(7));
+    assert!(db.get_rating_awaiting_comment(&entity).await.unwrap().is_none());
+}
+
+async fn staff_alias_upserts_and_deletes(db: &dyn Database) {
+    let first = db.save_staff_alias(InsertStaffAliasEntity { telegram_id: 7, alias: "Anna".to_string() }).await.unwrap();
+    let updated = db.save_staff_alias(InsertStaffAliasEntity { telegram_id: 7, alias: "Ann".to_string() }).await.unwrap();
+    assert_eq!(first.id, updated.id);
+    assert_eq!(db.get_staff_alias(UserId(7)).await.unwrap().unwrap().alias, "Ann");
+
+    db.delete_staff_alias(UserId(7)).await.unwrap();
+    assert!(db.get_staff_alias(UserId(7)).await.unwrap().is_none());
+}
+
+async fn tags_are_added_once_and_queried_by_tag(db: &dyn Database) {
+    let first = user(db, 10, 100).await;
+    let second = user(db, 20, 200).await;
+    let tag = |user: &UserEntity, tag: &str| InsertTagEntity { user_id: user.id, tag: tag.to_string() };
+    let vip = db.add_tag(tag(&first, "vip")).await.unwrap();
+    assert_eq!(db.add_tag(tag(&first, "vip")).await.unwrap().id, vip.id);
+    db.add_tag(tag(&first, "beta")).await.unwrap();
+    db.add_tag(tag(&second, "vip")).await.unwrap();
+
+    let tags = db.get_tags(&first).await.unwrap();
+    assert_eq!(tags.iter().map(|t| t.tag.as_str()).collect::<Vec<_>>(), vec!["vip", "beta"]);
+    let tagged = db.get_users_by_tag("vip").await.unwrap();
+    assert_eq!(tagged.iter().map(|u| u.id).collect::<Vec<_>>(), vec![first.id, second.id]);
+
+    db.delete_tag(&first, "vip").await.unwrap();
+    assert_eq!(db.get_users_by_tag("vip").await.unwrap().len(), 1);
+    assert_eq!(db.get_tags(&first).await.unwrap().len(), 1);
+}
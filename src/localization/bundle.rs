@@ -1,28 +1,50 @@
-use std::collections::HashMap;
-use std::path::Path;
-use crate::localization::file::{FileContents, ParseError};
-use crate::localization::LocKey;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::{Display, Formatter};
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+use crate::localization::file::{is_supported, Entry, FileContents, ParseError, ParseMode};
+use crate::localization::{CommonMessages, LocKey, LocalizationConfig, StaffMessages};
+use crate::localization::format::{format, format_with};
+use crate::localization::html;
 
+/// Messages of all languages. Languages can be replaced while bot is running, see [LocalizationBundle::swap]
 pub struct LocalizationBundle {
-    langs: HashMap<String, FileContents>,
+    langs: RwLock<HashMap<String, FileContents>>,
     default_lang: Option<String>,
+    /// Languages to try when message is missing in language, like `uk → ru`
+    fallbacks: HashMap<String, Vec<String>>,
+    /// Where languages were loaded from, used to reload them
+    pub(super) config: Option<LocalizationConfig>,
 }
 
 impl LocalizationBundle {
     pub fn new() -> Self {
-        LocalizationBundle { langs: HashMap::new(), default_lang: None }
+        LocalizationBundle { langs: RwLock::new(HashMap::new()), default_lang: None, fallbacks: HashMap::new(), config: None }
     }
 
     pub fn set_default_lang(&mut self, lang: String) {
-        self.default_lang = Some(lang);
+        self.default_lang = Some(normalize(&lang));
     }
 
+    pub fn set_fallback(&mut self, lang: &str, fallbacks: &[String]) {
+        self.fallbacks.insert(normalize(lang), fallbacks.iter().map(|l| normalize(l)).collect());
+    }
+
+    /// Loaded languages in alphabetical order
     pub fn languages(&self) -> Vec<String> {
-        self.langs.keys().map(|k| k.to_string()).collect()
+        let mut langs = self.langs.read().unwrap().keys().map(|k| k.to_string()).collect::<Vec<_>>();
+        langs.sort();
+        langs
     }
 
+    /// Adds messages to language. Messages with keys that language already has are replaced
     pub fn add(&mut self, lang: impl Into<String>, contents: FileContents) {
-        self.langs.insert(lang.into(), contents);
+        self.langs.get_mut().unwrap().entry(normalize(&lang.into())).or_default().extend(contents);
+    }
+
+    /// Replaces all languages with languages of `other` at once, so no message is localized with a half-loaded bundle
+    pub fn swap(&self, other: LocalizationBundle) {
+        *self.langs.write().unwrap() = other.langs.into_inner().unwrap();
     }
 
     pub async fn add_file(&mut self, file: &Path) -> Result<(), ParseError> {
@@ -31,29 +53,170 @@ impl LocalizationBundle {
         Ok(())
     }
 
-    pub async fn scan_dir(&mut self, dir: impl AsRef<Path>) -> Result<(), ParseError> {
-        let mut paths = tokio::fs::read_dir(dir).await?;
-        while let Some(entry) = paths.next_entry().await? {
-            if entry.file_type().await?.is_dir() {
-                self.add_file(&entry.path()).await?;
+    /// Adds `lang.json` files from `dir`. With `recursive`, files from subdirectories are added too:
+    /// everything under `lang/`, like `lang/namespace.json`, belongs to `lang`. Other files are skipped
+    pub async fn scan_dir(&mut self, dir: impl AsRef<Path>, recursive: bool) -> Result<(), ParseError> {
+        let (files, dirs) = list_dir(dir.as_ref()).await?;
+        for file in files {
+            self.add_file(&file).await?;
+        }
+        if !recursive {
+            return Ok(());
+        }
+        for lang_dir in dirs {
+            let lang = lang_dir.file_name().and_then(|s| s.to_str()).unwrap_or_default().to_string();
+            let mut pending = vec![lang_dir];
+            while let Some(dir) = pending.pop() {
+                let (files, dirs) = list_dir(&dir).await?;
+                for file in files {
+                    let (_, contents) = super::file::parse(&file).await?;
+                    self.add(lang.clone(), contents);
+                }
+                pending.extend(dirs.into_iter().rev());
             }
         }
         Ok(())
     }
 
-    pub fn localize(&self, lang: Option<String>, key: impl LocKey) -> String {
-        let k = key.key();
-        let mut msg = lang
-            .and_then(|lang| self.langs.get(&lang))
-            .or_else(|| self.default_lang.clone().and_then(|d| self.langs.get(&d) ))
-            .and_then(|l| l.get(&k))
-            .map(|e| e.default_message.to_string())
-            .unwrap_or_else(|| key.default_message());
-        if let Some(args) = key.args() {
-            for (k, v) in args {
-                msg = msg.replace(&format!("{{{k}}}"), &v)
+    /// Compares keys of every language with keys of [CommonMessages] and [StaffMessages].
+    /// Command descriptions depend on bot commands and are not checked
+    pub fn validate(&self) -> ValidationReport {
+        let known = CommonMessages::all().iter().map(|m| m.key())
+            .chain(StaffMessages::all().iter().map(|m| m.key()))
+            .collect::<Vec<_>>();
+        let mut report = ValidationReport::default();
+        for (lang, contents) in self.langs.read().unwrap().iter() {
+            let mut missing = known.iter().filter(|k| !contents.contains_key(*k)).cloned().collect::<Vec<_>>();
+            let mut unknown = contents.keys()
+                .filter(|k| !known.contains(k) && !k.starts_with("commands."))
+                .cloned()
+                .collect::<Vec<_>>();
+            missing.sort();
+            unknown.sort();
+            if !missing.is_empty() {
+                report.missing.insert(lang.clone(), missing);
+            }
+            if !unknown.is_empty() {
+                report.unknown.insert(lang.clone(), unknown);
             }
         }
-        msg
+        report
+    }
+
+    /// Languages to look message up in: the language itself, its parents (`pt` for `pt-br`),
+    /// configured fallbacks of each and then default language
+    pub fn chain(&self, lang: Option<&str>) -> Vec<String> {
+        let mut chain = vec![];
+        if let Some(lang) = lang {
+            self.visit(&normalize(lang), &mut chain);
+        }
+        if let Some(ref default) = self.default_lang {
+            self.visit(default, &mut chain);
+        }
+        chain
+    }
+
+    fn visit(&self, lang: &str, chain: &mut Vec<String>) {
+        if lang.is_empty() || chain.iter().any(|l| l == lang) {
+            return;
+        }
+        chain.push(lang.to_string());
+        if let Some((parent, _)) = lang.rsplit_once('-') {
+            self.visit(parent, chain);
+        }
+        for fallback in self.fallbacks.get(lang).into_iter().flatten() {
+            self.visit(fallback, chain);
+        }
+    }
+
+    /// Localizes message for user language. Every message is looked up along [LocalizationBundle::chain] separately,
+    /// so partially translated languages fall back only for missing messages
+    pub fn localize(&self, lang: Option<String>, key: impl LocKey) -> String {
+        // plural rules and number formats follow language of the message, built-in messages are English
+        let (lang, msg) = self.find(lang.as_deref(), &key.key())
+            .map(|(lang, e)| (lang, e.default_message))
+            .unwrap_or_else(|| ("en".to_string(), key.default_message()));
+        format(&lang, &msg, &key.args().unwrap_or_default())
+    }
+
+    /// Localizes message like [LocalizationBundle::localize], keeping parse mode of its entry.
+    /// Arguments are escaped for that parse mode, built-in messages are plain text
+    pub fn localize_message(&self, lang: Option<String>, key: impl LocKey) -> Localized {
+        let Some((lang, entry)) = self.find(lang.as_deref(), &key.key()) else {
+            return Localized { text: format("en", &key.default_message(), &key.args().unwrap_or_default()), parse_mode: None };
+        };
+        let escape: fn(&str) -> String = match entry.parse_mode {
+            Some(ParseMode::Html) => html::escape,
+            Some(ParseMode::MarkdownV2) => teloxide::utils::markdown::escape,
+            None => |s| s.to_string(),
+        };
+        Localized {
+            text: format_with(&lang, &entry.default_message, &key.args().unwrap_or_default(), escape),
+            parse_mode: entry.parse_mode,
+        }
+    }
+
+    /// First entry of key along [LocalizationBundle::chain] and its language
+    fn find(&self, lang: Option<&str>, key: &str) -> Option<(String, Entry)> {
+        let langs = self.langs.read().unwrap();
+        self.chain(lang)
+            .into_iter()
+            .find_map(|lang| langs.get(&lang).and_then(|l| l.get(key)).map(|e| (lang, e.clone())))
+    }
+}
+
+/// Localized text and parse mode to send it with
+pub struct Localized {
+    pub text: String,
+    pub parse_mode: Option<ParseMode>,
+}
+
+/// Keys that localization files lack or have in excess, per language
+#[derive(Debug, Default, PartialEq)]
+pub struct ValidationReport {
+    /// Keys that fall back to default messages
+    pub missing: BTreeMap<String, Vec<String>>,
+    /// Keys that no message uses, usually typos
+    pub unknown: BTreeMap<String, Vec<String>>,
+}
+
+impl ValidationReport {
+    pub fn is_empty(&self) -> bool {
+        self.missing.is_empty() && self.unknown.is_empty()
+    }
+}
+
+impl Display for ValidationReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for (lang, keys) in self.missing.iter() {
+            writeln!(f, "{}: missing {}", lang, keys.join(", "))?;
+        }
+        for (lang, keys) in self.unknown.iter() {
+            writeln!(f, "{}: unknown {}", lang, keys.join(", "))?;
+        }
+        Ok(())
     }
+}
+
+/// Supported files and subdirectories of `dir`, sorted so later files override earlier ones predictably
+async fn list_dir(dir: &Path) -> Result<(Vec<PathBuf>, Vec<PathBuf>), ParseError> {
+    let mut files = vec![];
+    let mut dirs = vec![];
+    let mut entries = tokio::fs::read_dir(dir).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        let kind = entry.file_type().await?;
+        if kind.is_dir() {
+            dirs.push(entry.path());
+        } else if kind.is_file() && is_supported(&entry.path()) {
+            files.push(entry.path());
+        }
+    }
+    files.sort();
+    dirs.sort();
+    Ok((files, dirs))
+}
+
+/// Lowercase BCP-47 tag with `-` separators, so `pt_BR` and `pt-br` are the same language
+fn normalize(lang: &str) -> String {
+    lang.trim().replace('_', "-").to_lowercase()
 }
\ No newline at end of file
@@ -1,57 +1,204 @@
-use tracing::warn;
-use crate::localization::{LocKey, sanitize};
+use crate::localization::{escape, Arg, LocKey};
 
 #[derive(Clone)]
 pub enum CommonMessages {
-    PollsNotSupported,
     GamesNotSupported,
+    MessageNotSupported,
+    UnsupportedPlaceholder {
+        kind: String,
+    },
     InfoHeader {
         id: i64,
         first_name: Option<String>,
         last_name: Option<String>,
         lang: Option<String>,
+        username: Option<String>,
+        premium: bool,
+        first_contact: Option<i64>,
+        source: Option<String>,
+        incoming: i64,
+        outgoing: i64,
+        tickets: i64,
+        banned: bool,
+        agent: Option<String>,
+        tags: Vec<String>,
     },
     Welcome,
     Faq,
     UserReply,
+    RatingPrompt,
+    RatingCommentPrompt,
+    RatingSkip,
+    RatingThanks,
+    StaffSignature {
+        name: String,
+    },
+    StaffHeader {
+        name: String,
+    },
+    LanguagePrompt,
+    LanguageAuto,
+    LanguageChanged,
+    CommandsHeader,
+}
+
+impl CommonMessages {
+    /// Every message with empty arguments, for checking and exporting localization files
+    pub fn all() -> Vec<CommonMessages> {
+        vec![
+            CommonMessages::GamesNotSupported,
+            CommonMessages::MessageNotSupported,
+            CommonMessages::UnsupportedPlaceholder { kind: String::new() },
+            CommonMessages::InfoHeader {
+                id: 0,
+                first_name: None,
+                last_name: None,
+                lang: None,
+                username: None,
+                premium: false,
+                first_contact: None,
+                source: None,
+                incoming: 0,
+                outgoing: 0,
+                tickets: 0,
+                banned: false,
+                agent: None,
+                tags: vec![],
+            },
+            CommonMessages::Welcome,
+            CommonMessages::Faq,
+            CommonMessages::UserReply,
+            CommonMessages::RatingPrompt,
+            CommonMessages::RatingCommentPrompt,
+            CommonMessages::RatingSkip,
+            CommonMessages::RatingThanks,
+            CommonMessages::StaffSignature { name: String::new() },
+            CommonMessages::StaffHeader { name: String::new() },
+            CommonMessages::LanguagePrompt,
+            CommonMessages::LanguageAuto,
+            CommonMessages::LanguageChanged,
+            CommonMessages::CommandsHeader,
+        ]
+    }
 }
 
 impl LocKey for CommonMessages {
     fn key(&self) -> String {
         match self {
-            CommonMessages::PollsNotSupported => "common.pollsNotSupported",
+            CommonMessages::MessageNotSupported => "common.messageNotSupported",
+            CommonMessages::UnsupportedPlaceholder { .. } => "common.unsupportedPlaceholder",
             CommonMessages::GamesNotSupported => "common.gamesNotSupported",
             CommonMessages::InfoHeader { .. } => "common.infoHeader",
             CommonMessages::Welcome => "common.welcome",
             CommonMessages::Faq => "common.faq",
             CommonMessages::UserReply => "common.userReply",
+            CommonMessages::RatingPrompt => "common.ratingPrompt",
+            CommonMessages::RatingCommentPrompt => "common.ratingCommentPrompt",
+            CommonMessages::RatingSkip => "common.ratingSkip",
+            CommonMessages::RatingThanks => "common.ratingThanks",
+            CommonMessages::StaffSignature { .. } => "common.staffSignature",
+            CommonMessages::StaffHeader { .. } => "common.staffHeader",
+            CommonMessages::LanguagePrompt => "common.languagePrompt",
+            CommonMessages::LanguageAuto => "common.languageAuto",
+            CommonMessages::LanguageChanged => "common.languageChanged",
+            CommonMessages::CommandsHeader => "common.commandsHeader",
         }.to_string()
     }
 
     fn default_message(&self) -> String {
         match self {
             CommonMessages::GamesNotSupported => "Games not supported".to_string(),
-            CommonMessages::PollsNotSupported => "Polls not supported".to_string(),
-            CommonMessages::InfoHeader { .. } => "<b><a href=\"tg://user?id={id}\">{first_name} {last_name}</a></b>\n<b>Language: </b> {lang}\n".to_string(),
+            CommonMessages::MessageNotSupported => "This type of message is not supported and was not delivered".to_string(),
+            CommonMessages::UnsupportedPlaceholder { .. } => "⚠️ User sent a message that can't be shown here ({kind})".to_string(),
+            CommonMessages::InfoHeader { .. } => concat!(
+                "<b><a href=\"tg://user?id={id}\">{first_name} {last_name}</a></b> {username}\n",
+                "<b>Language: </b> {lang}\n",
+                "<b>Premium: </b> {premium, select, true {yes} other {no}}\n",
+                "<b>First contact: </b> {first_contact, date}\n",
+                "<b>Source: </b> {source}\n",
+                "<b>Messages: </b> {incoming} from user, {outgoing} from staff\n",
+                "<b>Past tickets: </b> {tickets}\n",
+                "<b>Banned: </b> {banned, select, true {yes} other {no}}\n",
+                "<b>Agent: </b> {agent}\n",
+                "<b>Tags: </b> {tags}\n",
+            ).to_string(),
             CommonMessages::Welcome => "Welcome to support chat! Ask your questions here".to_string(),
             CommonMessages::Faq => "To contact support, send your message, video or file. You will receive support answer in this chat".to_string(),
             CommonMessages::UserReply => "Thank you for contacting us. We will answer as soon as possible.".to_string(),
+            CommonMessages::RatingPrompt => "Your conversation with support was closed. Please rate our help".to_string(),
+            CommonMessages::RatingCommentPrompt => "Thank you for your rating! You can send a comment about our help or skip it".to_string(),
+            CommonMessages::RatingSkip => "Skip".to_string(),
+            CommonMessages::RatingThanks => "Thank you for your feedback!".to_string(),
+            CommonMessages::StaffSignature { .. } => "— {name}, Support".to_string(),
+            CommonMessages::StaffHeader { .. } => "{name}, Support:".to_string(),
+            CommonMessages::LanguagePrompt => "Choose language of bot messages".to_string(),
+            CommonMessages::LanguageAuto => "Same as Telegram".to_string(),
+            CommonMessages::LanguageChanged => "Language changed".to_string(),
+            CommonMessages::CommandsHeader => "These commands are supported:".to_string(),
         }
     }
 
-    fn args(self) -> Option<Vec<(String, String)>> {
+    fn description(&self) -> Option<String> {
+        let description = match self {
+            CommonMessages::GamesNotSupported => "Reply to user who sent a game",
+            CommonMessages::MessageNotSupported => "Reply to user whose message can't be relayed to staff",
+            CommonMessages::UnsupportedPlaceholder { .. } => "Posted to staff topic instead of message that can't be relayed",
+            CommonMessages::InfoHeader { .. } => "Pinned user card in staff topic, HTML. {premium} and {banned} are true or false",
+            CommonMessages::Welcome => "Reply to /start",
+            CommonMessages::Faq => "Reply to /faq",
+            CommonMessages::UserReply => "Reply to every user message",
+            CommonMessages::RatingPrompt => "Sent to user when staff closes conversation, followed by star buttons",
+            CommonMessages::RatingCommentPrompt => "Shown after user picked rating",
+            CommonMessages::RatingSkip => "Button to skip rating comment",
+            CommonMessages::RatingThanks => "Shown after rating comment is sent or skipped",
+            CommonMessages::StaffSignature { .. } => "Last line of staff reply",
+            CommonMessages::StaffHeader { .. } => "First line of staff reply",
+            CommonMessages::LanguagePrompt => "Reply to /language, followed by language buttons",
+            CommonMessages::LanguageAuto => "Button to use language of Telegram client",
+            CommonMessages::LanguageChanged => "Sent in newly chosen language",
+            CommonMessages::CommandsHeader => "First line of /help",
+        };
+        Some(description.to_string())
+    }
+
+    fn args(self) -> Option<Vec<(String, Arg)>> {
         match self {
-            CommonMessages::PollsNotSupported => None,
+            CommonMessages::MessageNotSupported => None,
+            CommonMessages::UnsupportedPlaceholder { kind } => Some(vec![("kind".to_string(), kind.into())]),
             CommonMessages::GamesNotSupported => None,
             CommonMessages::Welcome => None,
             CommonMessages::Faq => None,
             CommonMessages::UserReply => None,
+            CommonMessages::RatingPrompt => None,
+            CommonMessages::RatingCommentPrompt => None,
+            CommonMessages::RatingSkip => None,
+            CommonMessages::RatingThanks => None,
+            CommonMessages::LanguagePrompt => None,
+            CommonMessages::LanguageAuto => None,
+            CommonMessages::LanguageChanged => None,
+            CommonMessages::CommandsHeader => None,
+            CommonMessages::StaffSignature { name } => Some(vec![("name".to_string(), name.into())]),
+            CommonMessages::StaffHeader { name } => Some(vec![("name".to_string(), name.into())]),
 
-            CommonMessages::InfoHeader { last_name, id, lang, first_name } => Some(vec![
-                ("id".to_string(), id.to_string()),
-                ("first_name".to_string(), sanitize(first_name.unwrap_or_default())),
-                ("last_name".to_string(), sanitize(last_name.unwrap_or_default())),
-                ("lang".to_string(), sanitize(lang.unwrap_or_default())),
+            CommonMessages::InfoHeader { last_name, id, lang, first_name, username, premium, first_contact, source, incoming, outgoing, tickets, banned, agent, tags } => Some(vec![
+                ("id".to_string(), id.to_string().into()),
+                ("first_name".to_string(), escape(&first_name.unwrap_or_default()).into()),
+                ("last_name".to_string(), escape(&last_name.unwrap_or_default()).into()),
+                ("lang".to_string(), escape(&lang.unwrap_or_default()).into()),
+                ("username".to_string(), username.map(|u| format!("@{}", escape(&u))).unwrap_or_default().into()),
+                ("premium".to_string(), premium.to_string().into()),
+                ("first_contact".to_string(), first_contact.map(Arg::Date).unwrap_or("-".into())),
+                ("source".to_string(), source.map(|s| format!("<code>{}</code>", escape(&s))).unwrap_or("-".to_string()).into()),
+                ("incoming".to_string(), incoming.into()),
+                ("outgoing".to_string(), outgoing.into()),
+                ("tickets".to_string(), tickets.into()),
+                ("banned".to_string(), banned.to_string().into()),
+                ("agent".to_string(), agent.map(|a| escape(&a)).unwrap_or("-".to_string()).into()),
+                ("tags".to_string(), if tags.is_empty() {
+                    "-".to_string()
+                } else {
+                    tags.into_iter().map(|t| format!("#{}", escape(&t))).collect::<Vec<_>>().join(" ")
+                }.into()),
             ])
         }
     }
@@ -1,28 +1,127 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
 use serde::Deserialize;
-use tracing::{info, instrument};
-use crate::localization::{LocalizationBundle, ParseError};
+use tracing::{error, info, instrument, warn};
+use crate::config::ConfigErrors;
+use crate::localization::{LocalizationBundle, ParseError, ValidationReport};
 
-#[derive(Deserialize, Debug, Default)]
+#[derive(Deserialize, Debug, Default, Clone)]
 pub struct LocalizationConfig {
     #[serde(default)]
     default_language: Option<String>,
+    /// Languages to try before default language, like `uk = ["ru"]`. Parent languages, like `pt` for `pt-BR`, are tried first
+    #[serde(default)]
+    fallbacks: HashMap<String, Vec<String>>,
     #[serde(default)]
     paths: Vec<String>,
+    /// Also read subdirectories of `paths`, files under `lang/` directory belong to `lang`
+    #[serde(default)]
+    recursive: bool,
+    /// Seconds between checks of `paths` for changed files. Changes are picked up only by `/reloadloc` if not set
+    #[serde(default)]
+    watch_interval: Option<u64>,
+}
+
+impl LocalizationConfig {
+    pub fn validate(&self, errors: &mut ConfigErrors) {
+        for path in self.paths.iter() {
+            if !Path::new(path).is_dir() {
+                errors.add("localization.paths", format!("{} is not a directory", path));
+            }
+        }
+        if self.watch_interval == Some(0) {
+            errors.add("localization.watch_interval", "should be at least 1 second");
+        }
+    }
 }
 
 #[instrument]
 pub async fn from_config(cfg: Option<LocalizationConfig>) -> Result<LocalizationBundle, ParseError> {
+    let mut bundle = load(&cfg).await?;
+    info!("Created localization bundle with languages: [{:?}]", bundle.languages());
+    report(&bundle.validate());
+    bundle.config = cfg;
+    Ok(bundle)
+}
+
+/// Reads configured paths again and swaps bundle languages. Bundle is left unchanged if any file fails to parse
+pub async fn reload(bundle: &LocalizationBundle) -> Result<ValidationReport, ParseError> {
+    let fresh = load(&bundle.config).await?;
+    bundle.swap(fresh);
+    info!("Reloaded localization bundle with languages: [{:?}]", bundle.languages());
+    let validation = bundle.validate();
+    report(&validation);
+    Ok(validation)
+}
+
+/// Periodically checks configured paths and reloads bundle when files change
+pub async fn watch(bundle: Arc<LocalizationBundle>) {
+    let Some(watch_interval) = bundle.config.as_ref().and_then(|c| c.watch_interval) else {
+        return;
+    };
+    info!("Checking localization files for changes every {} seconds", watch_interval);
+    let mut seen = snapshot(&bundle.config).await;
+    let mut interval = tokio::time::interval(Duration::from_secs(watch_interval));
+    loop {
+        interval.tick().await;
+        let current = snapshot(&bundle.config).await;
+        if current == seen {
+            continue;
+        }
+        seen = current;
+        if let Err(e) = reload(&bundle).await {
+            error!("Failed to reload localization, keeping previous messages: {}", e);
+        }
+    }
+}
+
+async fn load(cfg: &Option<LocalizationConfig>) -> Result<LocalizationBundle, ParseError> {
     let mut bundle = LocalizationBundle::new();
     if let Some(cfg) = cfg {
-        if let Some(dlang) = cfg.default_language {
-            info!("Using default language {}", &dlang);
-            bundle.set_default_lang(dlang);
+        if let Some(ref dlang) = cfg.default_language {
+            info!("Using default language {}", dlang);
+            bundle.set_default_lang(dlang.clone());
+        }
+        for (lang, fallbacks) in cfg.fallbacks.iter() {
+            bundle.set_fallback(lang, fallbacks);
         }
-        for path in cfg.paths {
-            info!("Scanning dir {} for localizations", &path);
-            bundle.scan_dir(&path).await?;
+        for path in cfg.paths.iter() {
+            info!("Scanning dir {} for localizations", path);
+            bundle.scan_dir(path, cfg.recursive).await?;
         }
     }
-    info!("Created localization bundle with languages: [{:?}]", bundle.languages());
     Ok(bundle)
+}
+
+fn report(validation: &ValidationReport) {
+    if !validation.is_empty() {
+        warn!("Localization files don't match known messages:\n{}", validation);
+    }
+}
+
+/// Modification times of entries in configured paths. Unreadable paths are skipped, their errors show up on reload
+async fn snapshot(cfg: &Option<LocalizationConfig>) -> Vec<(PathBuf, Option<SystemTime>)> {
+    let mut files = vec![];
+    let Some(cfg) = cfg else {
+        return files;
+    };
+    let mut pending = cfg.paths.iter().map(PathBuf::from).collect::<Vec<_>>();
+    while let Some(dir) = pending.pop() {
+        let Ok(mut entries) = tokio::fs::read_dir(&dir).await else {
+            continue;
+        };
+        while let Ok(Some(entry)) = entries.next_entry().await {
+            let Ok(metadata) = entry.metadata().await else {
+                continue;
+            };
+            if metadata.is_dir() && cfg.recursive {
+                pending.push(entry.path());
+            }
+            files.push((entry.path(), metadata.modified().ok()));
+        }
+    }
+    files.sort();
+    files
 }
\ No newline at end of file
@@ -4,11 +4,14 @@ use std::path::Path;
 use serde::{Deserialize, Serialize};
 use tokio::fs::File;
 use tokio::io::AsyncReadExt;
+use crate::localization::{formats, html, CommonMessages, LocKey, StaffMessages};
 
 #[derive(Debug)]
 pub enum ParseError {
     Io(std::io::Error),
     Serde(serde_json::Error),
+    /// Error of YAML, TOML or gettext file
+    Syntax(String),
     WrongExtension,
 }
 
@@ -17,7 +20,8 @@ impl Display for ParseError {
         match self {
             ParseError::Io(e) => write!(f, "IO Error: {}", e),
             ParseError::Serde(e) => write!(f, "Serde error: {}", e),
-            ParseError::WrongExtension => write!(f, "File extension should be .json"),
+            ParseError::Syntax(e) => write!(f, "Syntax error: {}", e),
+            ParseError::WrongExtension => write!(f, "File extension should be one of: {}", formats::extensions()),
         }
     }
 }
@@ -38,38 +42,96 @@ impl std::error::Error for ParseError {}
 
 pub type FileContents = HashMap<String, Entry>;
 
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub struct Entry {
     #[serde(rename = "defaultMessage")]
     pub default_message: String,
     #[serde(rename = "description")]
     pub description: Option<String>,
+    #[serde(rename = "parseMode", default, skip_serializing_if = "Option::is_none")]
+    pub parse_mode: Option<ParseMode>,
 }
 
-async fn read(path: &Path) -> Result<FileContents, ParseError> {
-    let contents = {
-        let mut reader = File::open(path).await?;
-        let mut str = String::new();
-        reader.read_to_string(&mut str).await?;
-        str
-    };
-    Ok(serde_json::from_str(&contents)?)
+/// Formatting of localized message, messages without it are sent as plain text
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum ParseMode {
+    #[serde(rename = "HTML")]
+    Html,
+    MarkdownV2,
+}
+
+impl From<ParseMode> for teloxide::types::ParseMode {
+    fn from(value: ParseMode) -> Self {
+        match value {
+            ParseMode::Html => teloxide::types::ParseMode::Html,
+            ParseMode::MarkdownV2 => teloxide::types::ParseMode::MarkdownV2,
+        }
+    }
+}
+
+async fn read(path: &Path) -> Result<String, ParseError> {
+    let mut reader = File::open(path).await?;
+    let mut str = String::new();
+    reader.read_to_string(&mut str).await?;
+    Ok(str)
+}
+
+/// Whether file at path has extension of localization file
+pub fn is_supported(path: &Path) -> bool {
+    formats::by_path(path).is_some()
 }
 
 pub async fn parse(path: &Path) -> Result<(String, FileContents), ParseError> {
-    let Some(ext) = path.extension()
-        .and_then(|s| s.to_str())
-        .map(|s| s.to_string())
-        .filter(|e| e == "json") else {
+    let (Some(ext), Some(format)) = (path.extension().and_then(|s| s.to_str()), formats::by_path(path)) else {
         return Err(ParseError::WrongExtension)
     };
     let name = path.file_name()
         .and_then(|s| s.to_str())
         .map(|s| s.to_string())
         .unwrap_or_default()
-        .trim_end_matches(&ext)
+        .trim_end_matches(ext)
         .trim_end_matches(".")
         .to_string();
-    let contents = read(path).await?;
+    let contents = format.parse(&read(path).await?)?;
+    for (key, entry) in contents.iter() {
+        if entry.parse_mode == Some(ParseMode::Html) {
+            html::validate(&entry.default_message).map_err(|e| ParseError::Syntax(format!("{}: {}", key, e)))?;
+        }
+    }
     return Ok((name, contents))
+}
+
+/// Every known key with its default message. Descriptions list placeholders for translators
+pub fn template() -> Vec<(String, Entry)> {
+    let mut entries = CommonMessages::all().into_iter().map(entry)
+        .chain(StaffMessages::all().into_iter().map(entry))
+        .collect::<Vec<_>>();
+    entries.sort_by(|a, b| a.0.cmp(&b.0));
+    entries
+}
+
+fn entry(key: impl LocKey) -> (String, Entry) {
+    let name = key.key();
+    let default_message = key.default_message();
+    let mut description = key.description();
+    let placeholders = key.args().unwrap_or_default()
+        .into_iter()
+        .map(|(k, _)| format!("{{{}}}", k))
+        .collect::<Vec<_>>();
+    if !placeholders.is_empty() {
+        let placeholders = format!("Placeholders: {}", placeholders.join(", "));
+        description = Some(match description {
+            Some(d) => format!("{}. {}", d, placeholders),
+            None => placeholders,
+        });
+    }
+    (name, Entry { default_message, description, parse_mode: None })
+}
+
+/// Writes [template] to file, format is picked by extension. Returns number of keys
+pub async fn export(path: &Path) -> Result<usize, ParseError> {
+    let format = formats::by_path(path).ok_or(ParseError::WrongExtension)?;
+    let entries = template();
+    tokio::fs::write(path, format.write(&entries)?).await?;
+    Ok(entries.len())
 }
\ No newline at end of file
@@ -0,0 +1,298 @@
+//! Subset of ICU MessageFormat: `{name}`, `{n, number}`, `{d, date}`,
+//! `{n, plural, =0 {none} one {# message} other {# messages}}` and `{g, select, female {her} other {their}}`.
+//! Plural categories, numbers and dates follow CLDR data of the language.
+//! In messages with these arguments text in apostrophes is literal and `''` is an apostrophe
+
+use chrono::{DateTime, Datelike};
+use fixed_decimal::FixedDecimal;
+use icu_calendar::{Date, Gregorian};
+use icu_datetime::options::length;
+use icu_datetime::TypedDateFormatter;
+use icu_decimal::FixedDecimalFormatter;
+use icu_locid::Locale;
+use icu_plurals::{PluralCategory, PluralRules};
+
+/// Typed argument of localized message
+#[derive(Debug, Clone, PartialEq)]
+pub enum Arg {
+    Str(String),
+    Number(i64),
+    /// Unix timestamp
+    Date(i64),
+}
+
+impl From<String> for Arg {
+    fn from(value: String) -> Self {
+        Arg::Str(value)
+    }
+}
+
+impl From<&str> for Arg {
+    fn from(value: &str) -> Self {
+        Arg::Str(value.to_string())
+    }
+}
+
+impl From<i64> for Arg {
+    fn from(value: i64) -> Self {
+        Arg::Number(value)
+    }
+}
+
+enum Part {
+    Text(String),
+    Arg(String),
+    Number(String),
+    Date(String),
+    Plural(String, Vec<(String, Vec<Part>)>),
+    Select(String, Vec<(String, Vec<Part>)>),
+    /// `#` inside plural branch
+    Count,
+}
+
+impl Part {
+    /// Whether part needs MessageFormat, plain `{name}` messages are formatted as written
+    fn is_icu(&self) -> bool {
+        !matches!(self, Part::Text(_) | Part::Arg(_))
+    }
+}
+
+/// Formats pattern for language. Patterns without MessageFormat arguments and invalid ones get plain `{name}`
+/// replacement, so their apostrophes and braces are kept
+pub fn format(lang: &str, pattern: &str, args: &[(String, Arg)]) -> String {
+    format_with(lang, pattern, args, |s| s.to_string())
+}
+
+/// Formats pattern like [format], passing every inserted value through `escape`
+pub fn format_with(lang: &str, pattern: &str, args: &[(String, Arg)], escape: fn(&str) -> String) -> String {
+    let chars = pattern.chars().collect::<Vec<_>>();
+    let mut pos = 0;
+    match parse(&chars, &mut pos, false, false) {
+        Some(parts) if pos == chars.len() && parts.iter().any(Part::is_icu) => {
+            let mut out = String::new();
+            render(lang, &parts, args, None, escape, &mut out);
+            out
+        }
+        _ => {
+            let mut msg = pattern.to_string();
+            for (k, v) in args {
+                msg = msg.replace(&format!("{{{k}}}"), &escape(&display(lang, v)));
+            }
+            msg
+        }
+    }
+}
+
+/// ICU locale of language tag like `pt-BR`. Tags ICU can't parse get root locale data
+fn locale(lang: &str) -> Locale {
+    Locale::try_from_bytes(lang.replace('_', "-").as_bytes()).unwrap_or(Locale::UND)
+}
+
+/// CLDR plural category of integer for language
+fn plural_category(lang: &str, n: i64) -> PluralCategory {
+    PluralRules::try_new_cardinal(&locale(lang).into())
+        .map(|rules| rules.category_for(n))
+        .unwrap_or(PluralCategory::Other)
+}
+
+fn parse(chars: &[char], pos: &mut usize, in_branch: bool, in_plural: bool) -> Option<Vec<Part>> {
+    let mut parts = vec![];
+    let mut text = String::new();
+    while *pos < chars.len() {
+        let c = chars[*pos];
+        match c {
+            '\'' if chars.get(*pos + 1) == Some(&'\'') => {
+                text.push('\'');
+                *pos += 2;
+            }
+            '\'' if matches!(chars.get(*pos + 1), Some('{' | '}' | '#')) => {
+                *pos += 1;
+                while *pos < chars.len() && chars[*pos] != '\'' {
+                    text.push(chars[*pos]);
+                    *pos += 1;
+                }
+                *pos += 1;
+            }
+            '{' => {
+                *pos += 1;
+                if !text.is_empty() {
+                    parts.push(Part::Text(std::mem::take(&mut text)));
+                }
+                parts.push(placeholder(chars, pos)?);
+            }
+            '}' if in_branch => break,
+            '#' if in_plural => {
+                if !text.is_empty() {
+                    parts.push(Part::Text(std::mem::take(&mut text)));
+                }
+                parts.push(Part::Count);
+                *pos += 1;
+            }
+            _ => {
+                text.push(c);
+                *pos += 1;
+            }
+        }
+    }
+    if !text.is_empty() {
+        parts.push(Part::Text(text));
+    }
+    Some(parts)
+}
+
+/// Parses placeholder after opening brace, including closing brace
+fn placeholder(chars: &[char], pos: &mut usize) -> Option<Part> {
+    let name = token(chars, pos, &[',', '}'])?;
+    if chars.get(*pos) == Some(&'}') {
+        *pos += 1;
+        return Some(Part::Arg(name));
+    }
+    *pos += 1;
+    let kind = token(chars, pos, &[',', '}'])?;
+    match (kind.as_str(), chars.get(*pos)) {
+        ("number", Some('}')) => {
+            *pos += 1;
+            Some(Part::Number(name))
+        }
+        ("date", Some('}')) => {
+            *pos += 1;
+            Some(Part::Date(name))
+        }
+        ("plural", Some(',')) => {
+            *pos += 1;
+            Some(Part::Plural(name, branches(chars, pos, true)?))
+        }
+        ("select", Some(',')) => {
+            *pos += 1;
+            Some(Part::Select(name, branches(chars, pos, false)?))
+        }
+        _ => None,
+    }
+}
+
+/// Parses `selector {message}` pairs up to closing brace of placeholder
+fn branches(chars: &[char], pos: &mut usize, plural: bool) -> Option<Vec<(String, Vec<Part>)>> {
+    let mut branches = vec![];
+    loop {
+        skip_whitespace(chars, pos);
+        match chars.get(*pos)? {
+            '}' => {
+                *pos += 1;
+                return Some(branches);
+            }
+            _ => {
+                let selector = token(chars, pos, &['{'])?;
+                if selector.is_empty() || chars.get(*pos) != Some(&'{') {
+                    return None;
+                }
+                *pos += 1;
+                let message = parse(chars, pos, true, plural)?;
+                if chars.get(*pos) != Some(&'}') {
+                    return None;
+                }
+                *pos += 1;
+                branches.push((selector, message));
+            }
+        }
+    }
+}
+
+/// Reads trimmed text up to one of `until`, which is not consumed
+fn token(chars: &[char], pos: &mut usize, until: &[char]) -> Option<String> {
+    let start = *pos;
+    while *pos < chars.len() && !until.contains(&chars[*pos]) {
+        if chars[*pos] == '{' {
+            return None;
+        }
+        *pos += 1;
+    }
+    if *pos == chars.len() {
+        return None;
+    }
+    Some(chars[start..*pos].iter().collect::<String>().trim().to_string())
+}
+
+fn skip_whitespace(chars: &[char], pos: &mut usize) {
+    while chars.get(*pos).is_some_and(|c| c.is_whitespace()) {
+        *pos += 1;
+    }
+}
+
+fn render(lang: &str, parts: &[Part], args: &[(String, Arg)], count: Option<i64>, escape: fn(&str) -> String, out: &mut String) {
+    let arg = |name: &str| args.iter().find(|(k, _)| k == name).map(|(_, v)| v);
+    for part in parts {
+        match part {
+            Part::Text(text) => out.push_str(text),
+            Part::Arg(name) => match arg(name) {
+                Some(value) => out.push_str(&escape(&display(lang, value))),
+                // unknown arguments are left as is, like before
+                None => out.push_str(&format!("{{{name}}}")),
+            },
+            Part::Number(name) => match arg(name) {
+                Some(Arg::Number(n)) => out.push_str(&escape(&number(lang, *n))),
+                Some(value) => out.push_str(&escape(&display(lang, value))),
+                None => {}
+            },
+            Part::Date(name) => match arg(name) {
+                Some(Arg::Date(t)) | Some(Arg::Number(t)) => out.push_str(&escape(&date(lang, *t))),
+                Some(value) => out.push_str(&escape(&display(lang, value))),
+                None => {}
+            },
+            Part::Plural(name, branches) => {
+                let n = match arg(name) {
+                    Some(Arg::Number(n)) => *n,
+                    Some(Arg::Str(s)) => s.parse().unwrap_or_default(),
+                    _ => 0,
+                };
+                let category = plural_category(lang, n);
+                let exact = format!("={}", n);
+                let branch = branches.iter().find(|(s, _)| *s == exact)
+                    .or_else(|| branches.iter().find(|(s, _)| PluralCategory::get_for_cldr_string(s) == Some(category)))
+                    .or_else(|| branches.iter().find(|(s, _)| s == "other"));
+                if let Some((_, message)) = branch {
+                    render(lang, message, args, Some(n), escape, out);
+                }
+            }
+            Part::Select(name, branches) => {
+                let value = arg(name).map(|v| display(lang, v)).unwrap_or_default();
+                let branch = branches.iter().find(|(s, _)| *s == value)
+                    .or_else(|| branches.iter().find(|(s, _)| s == "other"));
+                if let Some((_, message)) = branch {
+                    render(lang, message, args, count, escape, out);
+                }
+            }
+            Part::Count => match count {
+                Some(n) => out.push_str(&escape(&number(lang, n))),
+                None => out.push('#'),
+            },
+        }
+    }
+}
+
+fn display(lang: &str, value: &Arg) -> String {
+    match value {
+        Arg::Str(s) => s.clone(),
+        Arg::Number(n) => number(lang, *n),
+        Arg::Date(t) => date(lang, *t),
+    }
+}
+
+/// Integer with language group separator, like `1,234` or `1 234`
+fn number(lang: &str, n: i64) -> String {
+    FixedDecimalFormatter::try_new(&locale(lang).into(), Default::default())
+        .map(|formatter| formatter.format_to_string(&FixedDecimal::from(n)))
+        .unwrap_or_else(|_| n.to_string())
+}
+
+/// Medium date with language month names, like `Nov 14, 2023`. UTC day of timestamp
+fn date(lang: &str, timestamp: i64) -> String {
+    let Some(time) = DateTime::from_timestamp(timestamp, 0) else {
+        return String::new();
+    };
+    let formatter = TypedDateFormatter::<Gregorian>::try_new_with_length(&locale(lang).into(), length::Date::Medium);
+    let date = Date::try_new_gregorian_date(time.year(), time.month() as u8, time.day() as u8);
+    match (formatter, date) {
+        (Ok(formatter), Ok(date)) => formatter.format_to_string(&date),
+        _ => time.format("%Y-%m-%d").to_string(),
+    }
+}
\ No newline at end of file
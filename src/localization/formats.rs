@@ -0,0 +1,313 @@
+use std::path::Path;
+use serde_json::Value;
+use yaml_rust2::{Yaml, YamlEmitter, YamlLoader};
+use crate::localization::file::{Entry, FileContents, ParseError, ParseMode};
+
+/// Localization file format, picked by file extension
+pub trait Format: Sync {
+    fn extensions(&self) -> &'static [&'static str];
+
+    fn parse(&self, text: &str) -> Result<FileContents, ParseError>;
+
+    /// Serializes entries in given order
+    fn write(&self, entries: &[(String, Entry)]) -> Result<String, ParseError>;
+}
+
+const FORMATS: &[&dyn Format] = &[&JsonFormat, &YamlFormat, &TomlFormat, &PoFormat];
+
+/// Format for extension of file at path
+pub fn by_path(path: &Path) -> Option<&'static dyn Format> {
+    let ext = path.extension()?.to_str()?;
+    FORMATS.iter().copied().find(|f| f.extensions().contains(&ext))
+}
+
+/// Extensions of all formats, like `json, yaml`
+pub fn extensions() -> String {
+    FORMATS.iter().flat_map(|f| f.extensions()).copied().collect::<Vec<_>>().join(", ")
+}
+
+/// `{"key": {"defaultMessage": "...", "description": "..."}}`
+struct JsonFormat;
+
+impl Format for JsonFormat {
+    fn extensions(&self) -> &'static [&'static str] {
+        &["json"]
+    }
+
+    fn parse(&self, text: &str) -> Result<FileContents, ParseError> {
+        Ok(serde_json::from_str(text)?)
+    }
+
+    fn write(&self, entries: &[(String, Entry)]) -> Result<String, ParseError> {
+        let map = entries.iter()
+            .map(|(k, e)| (k.clone(), serde_json::to_value(e).unwrap_or_default()))
+            .collect::<serde_json::Map<_, _>>();
+        Ok(serde_json::to_string_pretty(&map)?)
+    }
+}
+
+/// Same entries as JSON. Messages can also be plain strings and keys can be nested, `common: {welcome: Hi}`
+struct YamlFormat;
+
+impl Format for YamlFormat {
+    fn extensions(&self) -> &'static [&'static str] {
+        &["yaml", "yml"]
+    }
+
+    fn parse(&self, text: &str) -> Result<FileContents, ParseError> {
+        let mut contents = FileContents::new();
+        let docs = YamlLoader::load_from_str(text).map_err(|e| ParseError::Syntax(e.to_string()))?;
+        for doc in docs {
+            flatten("", yaml_to_json(doc), &mut contents)?;
+        }
+        Ok(contents)
+    }
+
+    fn write(&self, entries: &[(String, Entry)]) -> Result<String, ParseError> {
+        let mut root = yaml_rust2::yaml::Hash::new();
+        for (key, entry) in entries {
+            let mut fields = yaml_rust2::yaml::Hash::new();
+            fields.insert(Yaml::String("defaultMessage".to_string()), Yaml::String(entry.default_message.clone()));
+            if let Some(ref description) = entry.description {
+                fields.insert(Yaml::String("description".to_string()), Yaml::String(description.clone()));
+            }
+            if let Some(mode) = entry.parse_mode {
+                fields.insert(Yaml::String("parseMode".to_string()), Yaml::String(serde_json::to_value(mode)?.as_str().unwrap_or_default().to_string()));
+            }
+            root.insert(Yaml::String(key.clone()), Yaml::Hash(fields));
+        }
+        let mut out = String::new();
+        YamlEmitter::new(&mut out).dump(&Yaml::Hash(root))
+            .map_err(|e| ParseError::Syntax(e.to_string()))?;
+        Ok(out)
+    }
+}
+
+/// Same entries as YAML, keys with dots have to be quoted: `["common.welcome"]`
+struct TomlFormat;
+
+impl Format for TomlFormat {
+    fn extensions(&self) -> &'static [&'static str] {
+        &["toml"]
+    }
+
+    fn parse(&self, text: &str) -> Result<FileContents, ParseError> {
+        let table = text.parse::<toml::Table>().map_err(|e| ParseError::Syntax(e.to_string()))?;
+        let mut contents = FileContents::new();
+        flatten("", serde_json::to_value(table)?, &mut contents)?;
+        Ok(contents)
+    }
+
+    fn write(&self, entries: &[(String, Entry)]) -> Result<String, ParseError> {
+        let table = entries.iter().cloned().collect::<std::collections::BTreeMap<_, _>>();
+        toml::to_string(&table).map_err(|e| ParseError::Syntax(e.to_string()))
+    }
+}
+
+/// gettext catalog. Key is `msgctxt`, or `msgid` without context. `#.` comments are descriptions,
+/// `html` and `markdownv2` flags set parse mode. Untranslated and fuzzy entries are skipped, so they fall back to other languages
+struct PoFormat;
+
+#[derive(Default)]
+struct PoEntry {
+    description: Vec<String>,
+    fuzzy: bool,
+    parse_mode: Option<ParseMode>,
+    context: Option<String>,
+    id: Option<String>,
+    text: Option<String>,
+}
+
+#[derive(Clone, Copy)]
+enum PoField {
+    Context,
+    Id,
+    Text,
+}
+
+impl PoEntry {
+    fn field(&mut self, field: PoField) -> &mut Option<String> {
+        match field {
+            PoField::Context => &mut self.context,
+            PoField::Id => &mut self.id,
+            PoField::Text => &mut self.text,
+        }
+    }
+
+    fn finish(self, contents: &mut FileContents) {
+        let (Some(id), Some(text)) = (self.id, self.text) else {
+            return;
+        };
+        // empty msgid is catalog header
+        if self.fuzzy || text.is_empty() || (id.is_empty() && self.context.is_none()) {
+            return;
+        }
+        let description = (!self.description.is_empty()).then(|| self.description.join("\n"));
+        contents.insert(self.context.unwrap_or(id), Entry { default_message: text, description, parse_mode: self.parse_mode });
+    }
+}
+
+impl Format for PoFormat {
+    fn extensions(&self) -> &'static [&'static str] {
+        &["po"]
+    }
+
+    fn parse(&self, text: &str) -> Result<FileContents, ParseError> {
+        let mut contents = FileContents::new();
+        let mut entry = PoEntry::default();
+        // comments before keywords belong to the next entry
+        let mut comments = PoEntry::default();
+        // field that continuation strings are appended to
+        let mut current = None;
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            let error = |message: &str| ParseError::Syntax(format!("line {}: {}", number + 1, message));
+            if line.is_empty() {
+                continue;
+            }
+            if line.starts_with('"') {
+                let value = po_string(line).ok_or_else(|| error("bad string"))?;
+                match current {
+                    Some(field) => entry.field(field).get_or_insert_with(String::new).push_str(&value),
+                    None => return Err(error("string outside of entry")),
+                }
+                continue;
+            }
+            // comment or keyword after msgstr starts next entry
+            if entry.text.is_some() && !line.starts_with("msgstr") {
+                current = None;
+                std::mem::take(&mut entry).finish(&mut contents);
+            }
+            if let Some(comment) = line.strip_prefix("#.") {
+                comments.description.push(comment.trim().to_string());
+                continue;
+            }
+            if let Some(flags) = line.strip_prefix("#,") {
+                for flag in flags.split(',').map(str::trim) {
+                    match flag {
+                        "fuzzy" => comments.fuzzy = true,
+                        "html" => comments.parse_mode = Some(ParseMode::Html),
+                        "markdownv2" => comments.parse_mode = Some(ParseMode::MarkdownV2),
+                        _ => {}
+                    }
+                }
+                continue;
+            }
+            if line.starts_with('#') {
+                continue;
+            }
+            let (keyword, value) = line.split_once(char::is_whitespace).ok_or_else(|| error("expected keyword and string"))?;
+            let value = po_string(value.trim()).ok_or_else(|| error("bad string"))?;
+            if entry.context.is_none() && entry.id.is_none() {
+                entry.description = std::mem::take(&mut comments.description);
+                entry.fuzzy = std::mem::take(&mut comments.fuzzy);
+                entry.parse_mode = comments.parse_mode.take();
+            }
+            current = match keyword {
+                "msgctxt" => Some(PoField::Context),
+                "msgid" => Some(PoField::Id),
+                // plural forms are written with ICU plurals, only the first form is used
+                "msgstr" | "msgstr[0]" => Some(PoField::Text),
+                "msgid_plural" => None,
+                _ if keyword.starts_with("msgstr[") => None,
+                _ => return Err(error("unknown keyword")),
+            };
+            if let Some(field) = current {
+                *entry.field(field) = Some(value);
+            }
+        }
+        entry.finish(&mut contents);
+        Ok(contents)
+    }
+
+    fn write(&self, entries: &[(String, Entry)]) -> Result<String, ParseError> {
+        let mut out = String::new();
+        for (key, entry) in entries {
+            for line in entry.description.iter().flat_map(|d| d.lines()) {
+                out.push_str(&format!("#. {}\n", line));
+            }
+            if let Some(mode) = entry.parse_mode {
+                out.push_str(&format!("#, {}\n", po_flag(mode)));
+            }
+            out.push_str(&format!("msgctxt {}\nmsgid {}\nmsgstr \"\"\n\n", po_quote(key), po_quote(&entry.default_message)));
+        }
+        Ok(out)
+    }
+}
+
+fn po_flag(mode: ParseMode) -> &'static str {
+    match mode {
+        ParseMode::Html => "html",
+        ParseMode::MarkdownV2 => "markdownv2",
+    }
+}
+
+/// Unquotes and unescapes gettext string
+fn po_string(s: &str) -> Option<String> {
+    let s = s.strip_prefix('"')?.strip_suffix('"')?;
+    let mut out = String::new();
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next()? {
+            'n' => out.push('\n'),
+            't' => out.push('\t'),
+            c => out.push(c),
+        }
+    }
+    Some(out)
+}
+
+fn po_quote(s: &str) -> String {
+    let escaped = s.replace('\\', "\\\\").replace('"', "\\\"").replace('\t', "\\t");
+    // multiline messages are split after line breaks, like gettext tools do
+    if escaped.contains('\n') {
+        let lines = escaped.split_inclusive('\n').map(|l| format!("\"{}\"", l.replace('\n', "\\n"))).collect::<Vec<_>>();
+        format!("\"\"\n{}", lines.join("\n"))
+    } else {
+        format!("\"{}\"", escaped)
+    }
+}
+
+fn yaml_to_json(yaml: Yaml) -> Value {
+    match yaml {
+        Yaml::String(s) | Yaml::Real(s) => Value::String(s),
+        Yaml::Integer(i) => Value::from(i),
+        Yaml::Boolean(b) => Value::Bool(b),
+        Yaml::Array(items) => Value::Array(items.into_iter().map(yaml_to_json).collect()),
+        Yaml::Hash(hash) => Value::Object(hash.into_iter()
+            .map(|(k, v)| (match yaml_to_json(k) {
+                Value::String(s) => s,
+                other => other.to_string(),
+            }, yaml_to_json(v)))
+            .collect()),
+        _ => Value::Null,
+    }
+}
+
+/// Collects entries of nested maps. Map with `defaultMessage` is an entry, other maps prefix keys inside them
+fn flatten(prefix: &str, value: Value, contents: &mut FileContents) -> Result<(), ParseError> {
+    let Value::Object(map) = value else {
+        return Err(ParseError::Syntax("top level should be a map of messages".to_string()));
+    };
+    for (key, value) in map {
+        let key = if prefix.is_empty() { key } else { format!("{}.{}", prefix, key) };
+        match value {
+            Value::String(s) => {
+                contents.insert(key, Entry { default_message: s, description: None, parse_mode: None });
+            }
+            Value::Number(_) | Value::Bool(_) => {
+                contents.insert(key, Entry { default_message: value.to_string(), description: None, parse_mode: None });
+            }
+            Value::Object(ref entry) if entry.get("defaultMessage").is_some_and(Value::is_string) => {
+                contents.insert(key, serde_json::from_value(value)?);
+            }
+            Value::Object(_) => flatten(&key, value, contents)?,
+            _ => return Err(ParseError::Syntax(format!("{} should be a message or a map", key))),
+        }
+    }
+    Ok(())
+}
\ No newline at end of file
@@ -0,0 +1,157 @@
+//! HTML subset that Telegram accepts, see <https://core.telegram.org/bots/api#html-style>
+
+/// Supported tags with their allowed attributes
+const TAGS: &[(&str, &[&str])] = &[
+    ("b", &[]), ("strong", &[]), ("i", &[]), ("em", &[]), ("u", &[]), ("ins", &[]),
+    ("s", &[]), ("strike", &[]), ("del", &[]), ("span", &["class"]), ("tg-spoiler", &[]),
+    ("a", &["href"]), ("tg-emoji", &["emoji-id"]), ("code", &["class"]), ("pre", &[]),
+    ("blockquote", &["expandable"]),
+];
+
+/// Escapes text so it's shown as is in HTML messages
+pub fn escape(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+/// Checks that Telegram will accept text as HTML: only supported tags and entities, tags are closed in order
+pub fn validate(html: &str) -> Result<(), String> {
+    scan(html, true).map(|_| ())
+}
+
+/// Keeps supported tags and escapes everything else, like stray `<` or unknown and unclosed tags
+pub fn clean(html: &str) -> String {
+    scan(html, false).unwrap_or_default()
+}
+
+/// Walks over text, reporting first problem if `strict`, otherwise escaping it
+fn scan(html: &str, strict: bool) -> Result<String, String> {
+    let mut out = Vec::<String>::new();
+    // open tags and their index in `out`
+    let mut open = Vec::<(String, usize)>::new();
+    let mut rest = html;
+    while let Some(i) = rest.find(['<', '>', '&']) {
+        out.push(rest[..i].to_string());
+        rest = &rest[i..];
+        let (piece, problem) = match rest.as_bytes()[0] {
+            b'&' => {
+                match entity(rest) {
+                    Some(len) => (&rest[..len], None),
+                    None => (&rest[..1], Some("unescaped &".to_string())),
+                }
+            }
+            b'>' => (&rest[..1], Some("unescaped >".to_string())),
+            _ => match rest.find('>') {
+                None => (&rest[..1], Some("unescaped <".to_string())),
+                Some(end) => {
+                    let raw = &rest[..=end];
+                    let problem = match tag(&raw[1..end]) {
+                        Err(e) => Some(e),
+                        Ok((name, true)) => match open.last() {
+                            Some((last, _)) if *last == name => {
+                                open.pop();
+                                None
+                            }
+                            Some((last, _)) => Some(format!("</{}> closes <{}>", name, last)),
+                            None => Some(format!("</{}> without opening tag", name)),
+                        },
+                        Ok((name, false)) => match open.last() {
+                            Some((last, _)) if last == "code" || (last == "pre" && name != "code") => {
+                                Some(format!("<{}> inside <{}>", name, last))
+                            }
+                            _ => {
+                                open.push((name, out.len()));
+                                None
+                            }
+                        },
+                    };
+                    (raw, problem)
+                }
+            },
+        };
+        rest = &rest[piece.len()..];
+        match problem {
+            Some(problem) if strict => return Err(problem),
+            Some(_) => out.push(escape(piece)),
+            None => out.push(piece.to_string()),
+        }
+    }
+    out.push(rest.to_string());
+    match open.last() {
+        Some((name, _)) if strict => return Err(format!("<{}> is not closed", name)),
+        _ => for (_, index) in open {
+            out[index] = escape(&out[index]);
+        },
+    }
+    Ok(out.concat())
+}
+
+/// Length of entity at start of text, if it's one of entities Telegram supports
+fn entity(s: &str) -> Option<usize> {
+    let end = s.find(';').filter(|e| *e <= 10)?;
+    let name = &s[1..end];
+    let valid = match name.strip_prefix('#') {
+        Some(code) => match code.strip_prefix(['x', 'X']) {
+            Some(hex) => !hex.is_empty() && hex.chars().all(|c| c.is_ascii_hexdigit()),
+            None => !code.is_empty() && code.chars().all(|c| c.is_ascii_digit()),
+        },
+        None => matches!(name, "lt" | "gt" | "amp" | "quot"),
+    };
+    valid.then_some(end + 1)
+}
+
+/// Parses contents of `<...>` into tag name and whether it's a closing tag
+fn tag(s: &str) -> Result<(String, bool), String> {
+    if let Some(name) = s.strip_prefix('/') {
+        let name = name.trim().to_lowercase();
+        return match TAGS.iter().any(|(t, _)| *t == name) {
+            true => Ok((name, true)),
+            false => Err(format!("unsupported tag </{}>", name)),
+        };
+    }
+    let (name, mut attrs) = s.split_once(char::is_whitespace).unwrap_or((s, ""));
+    let name = name.to_lowercase();
+    let Some((_, allowed)) = TAGS.iter().find(|(t, _)| *t == name) else {
+        return Err(format!("unsupported tag <{}>", name));
+    };
+    let mut found = vec![];
+    loop {
+        attrs = attrs.trim_start();
+        if attrs.is_empty() {
+            break;
+        }
+        let end = attrs.find(|c: char| c == '=' || c.is_whitespace()).unwrap_or(attrs.len());
+        let attr = attrs[..end].to_lowercase();
+        attrs = attrs[end..].trim_start();
+        let value = match attrs.strip_prefix('=') {
+            Some(value) => {
+                let value = value.trim_start();
+                let (value, rest) = match value.chars().next() {
+                    Some(q @ ('"' | '\'')) => {
+                        let end = value[1..].find(q).ok_or(format!("unclosed quote in <{}>", name))?;
+                        (&value[1..=end], &value[end + 2..])
+                    }
+                    _ => value.split_once(char::is_whitespace).unwrap_or((value, "")),
+                };
+                attrs = rest;
+                Some(value)
+            }
+            None => None,
+        };
+        if !allowed.contains(&attr.as_str()) {
+            return Err(format!("unsupported attribute {} of <{}>", attr, name));
+        }
+        found.push((attr, value));
+    }
+    let attr = |n: &str| found.iter().find(|(a, _)| a == n).and_then(|(_, v)| *v);
+    let valid = match name.as_str() {
+        "a" => attr("href").is_some_and(|h| !h.is_empty()),
+        "tg-emoji" => attr("emoji-id").is_some_and(|id| id.chars().all(|c| c.is_ascii_digit())),
+        "span" => attr("class") == Some("tg-spoiler"),
+        "code" => attr("class").is_none_or(|c| c.starts_with("language-")),
+        _ => true,
+    };
+    match valid {
+        true => Ok((name, false)),
+        false => Err(format!("<{}> has missing or wrong attributes", name)),
+    }
+}
\ No newline at end of file
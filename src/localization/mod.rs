@@ -1,28 +1,31 @@
 mod file;
 mod bundle;
 mod common;
+mod staff;
 mod config;
+mod format;
+mod formats;
+mod html;
+#[cfg(test)]
+mod tests;
 
-use tracing::warn;
-pub use bundle::LocalizationBundle;
-pub use file::{ParseError, FileContents, Entry};
+pub use bundle::{Localized, LocalizationBundle, ValidationReport};
+pub use file::{export, ParseError, FileContents, Entry, ParseMode};
+pub use html::{clean, escape};
 pub use common::CommonMessages;
-pub use config::{LocalizationConfig, from_config};
+pub use staff::{CommandDescription, StaffMessages};
+pub use format::Arg;
+pub use config::{LocalizationConfig, from_config, reload, watch};
 
 pub trait LocKey {
     fn key(&self) -> String;
 
     fn default_message(&self) -> String;
 
-    fn args(self) -> Option<Vec<(String, String)>>;
-}
-
-pub fn sanitize(s: String) -> String {
-    match sanitize_html::sanitize_str(&sanitize_html::rules::predefined::DEFAULT, &s) {
-        Ok(data) => data,
-        Err(e) => {
-            warn!("Failed to sanitize string {}: {:?}", s, e);
-            String::new()
-        }
+    /// Context for translators, exported to templates
+    fn description(&self) -> Option<String> {
+        None
     }
+
+    fn args(self) -> Option<Vec<(String, Arg)>>;
 }
\ No newline at end of file
@@ -0,0 +1,340 @@
+use crate::localization::{Arg, LocKey};
+
+/// Messages for staff in superchats, localized with staff language. Arguments of HTML messages are expected to be escaped
+#[derive(Clone)]
+pub enum StaffMessages {
+    AliasSaved,
+    AliasDeleted,
+    LocalizationReloaded {
+        languages: String,
+    },
+    LocalizationReloadFailed {
+        error: String,
+    },
+    NoOpenConversation,
+    ConversationClosed,
+    UserBanned,
+    UserUnbanned,
+    Notes,
+    NoteSaved,
+    NoteReplaced {
+        old: String,
+        author: String,
+        time: String,
+    },
+    NoteDeleted,
+    NoteInvalidValue {
+        type_name: String,
+    },
+    NoteUnknownType {
+        types: String,
+    },
+    NoteNoHistory {
+        key: String,
+    },
+    NoteHistory {
+        key: String,
+    },
+    NoteHistorySet {
+        value: String,
+        type_name: String,
+    },
+    NoteHistoryDeleted,
+    NoteDeepLink,
+    NoteDate {
+        date: i64,
+    },
+    Tagged {
+        tags: String,
+    },
+    Untagged {
+        tags: String,
+    },
+    NoUsersTagged {
+        tag: String,
+    },
+    UsersTagged {
+        tag: String,
+    },
+    InvalidTags,
+    UserRenamed {
+        old: String,
+        new: String,
+    },
+    UserMovedTo {
+        link: String,
+    },
+    UserMovedFrom {
+        link: String,
+    },
+    UserRated {
+        stars: String,
+    },
+    UserCommented {
+        text: String,
+    },
+    SlaBreach {
+        link: String,
+        name: String,
+        reason: String,
+    },
+    SlaNoFirstResponse {
+        minutes: i64,
+    },
+    SlaUnanswered {
+        minutes: i64,
+    },
+    Translation {
+        source: String,
+        text: String,
+    },
+    TranslationSent {
+        lang: String,
+        text: String,
+    },
+    TranslationFailed {
+        error: String,
+    },
+    TranslationDisabled,
+    TranslationNoLanguage,
+}
+
+impl StaffMessages {
+    /// Every message with empty arguments, for checking and exporting localization files
+    pub fn all() -> Vec<StaffMessages> {
+        vec![
+            StaffMessages::AliasSaved,
+            StaffMessages::AliasDeleted,
+            StaffMessages::LocalizationReloaded { languages: String::new() },
+            StaffMessages::LocalizationReloadFailed { error: String::new() },
+            StaffMessages::NoOpenConversation,
+            StaffMessages::ConversationClosed,
+            StaffMessages::UserBanned,
+            StaffMessages::UserUnbanned,
+            StaffMessages::Notes,
+            StaffMessages::NoteSaved,
+            StaffMessages::NoteReplaced { old: String::new(), author: String::new(), time: String::new() },
+            StaffMessages::NoteDeleted,
+            StaffMessages::NoteInvalidValue { type_name: String::new() },
+            StaffMessages::NoteUnknownType { types: String::new() },
+            StaffMessages::NoteNoHistory { key: String::new() },
+            StaffMessages::NoteHistory { key: String::new() },
+            StaffMessages::NoteHistorySet { value: String::new(), type_name: String::new() },
+            StaffMessages::NoteHistoryDeleted,
+            StaffMessages::NoteDeepLink,
+            StaffMessages::NoteDate { date: 0 },
+            StaffMessages::Tagged { tags: String::new() },
+            StaffMessages::Untagged { tags: String::new() },
+            StaffMessages::NoUsersTagged { tag: String::new() },
+            StaffMessages::UsersTagged { tag: String::new() },
+            StaffMessages::InvalidTags,
+            StaffMessages::UserRenamed { old: String::new(), new: String::new() },
+            StaffMessages::UserMovedTo { link: String::new() },
+            StaffMessages::UserMovedFrom { link: String::new() },
+            StaffMessages::UserRated { stars: String::new() },
+            StaffMessages::UserCommented { text: String::new() },
+            StaffMessages::SlaBreach { link: String::new(), name: String::new(), reason: String::new() },
+            StaffMessages::SlaNoFirstResponse { minutes: 0 },
+            StaffMessages::SlaUnanswered { minutes: 0 },
+            StaffMessages::Translation { source: String::new(), text: String::new() },
+            StaffMessages::TranslationSent { lang: String::new(), text: String::new() },
+            StaffMessages::TranslationFailed { error: String::new() },
+            StaffMessages::TranslationDisabled,
+            StaffMessages::TranslationNoLanguage,
+        ]
+    }
+}
+
+impl LocKey for StaffMessages {
+    fn key(&self) -> String {
+        match self {
+            StaffMessages::AliasSaved => "staff.aliasSaved",
+            StaffMessages::AliasDeleted => "staff.aliasDeleted",
+            StaffMessages::LocalizationReloaded { .. } => "staff.localizationReloaded",
+            StaffMessages::LocalizationReloadFailed { .. } => "staff.localizationReloadFailed",
+            StaffMessages::NoOpenConversation => "staff.noOpenConversation",
+            StaffMessages::ConversationClosed => "staff.conversationClosed",
+            StaffMessages::UserBanned => "staff.userBanned",
+            StaffMessages::UserUnbanned => "staff.userUnbanned",
+            StaffMessages::Notes => "staff.notes",
+            StaffMessages::NoteSaved => "staff.noteSaved",
+            StaffMessages::NoteReplaced { .. } => "staff.noteReplaced",
+            StaffMessages::NoteDeleted => "staff.noteDeleted",
+            StaffMessages::NoteInvalidValue { .. } => "staff.noteInvalidValue",
+            StaffMessages::NoteUnknownType { .. } => "staff.noteUnknownType",
+            StaffMessages::NoteNoHistory { .. } => "staff.noteNoHistory",
+            StaffMessages::NoteHistory { .. } => "staff.noteHistory",
+            StaffMessages::NoteHistorySet { .. } => "staff.noteHistorySet",
+            StaffMessages::NoteHistoryDeleted => "staff.noteHistoryDeleted",
+            StaffMessages::NoteDeepLink => "staff.noteDeepLink",
+            StaffMessages::NoteDate { .. } => "staff.noteDate",
+            StaffMessages::Tagged { .. } => "staff.tagged",
+            StaffMessages::Untagged { .. } => "staff.untagged",
+            StaffMessages::NoUsersTagged { .. } => "staff.noUsersTagged",
+            StaffMessages::UsersTagged { .. } => "staff.usersTagged",
+            StaffMessages::InvalidTags => "staff.invalidTags",
+            StaffMessages::UserRenamed { .. } => "staff.userRenamed",
+            StaffMessages::UserMovedTo { .. } => "staff.userMovedTo",
+            StaffMessages::UserMovedFrom { .. } => "staff.userMovedFrom",
+            StaffMessages::UserRated { .. } => "staff.userRated",
+            StaffMessages::UserCommented { .. } => "staff.userCommented",
+            StaffMessages::SlaBreach { .. } => "staff.slaBreach",
+            StaffMessages::SlaNoFirstResponse { .. } => "staff.slaNoFirstResponse",
+            StaffMessages::SlaUnanswered { .. } => "staff.slaUnanswered",
+            StaffMessages::Translation { .. } => "staff.translation",
+            StaffMessages::TranslationSent { .. } => "staff.translationSent",
+            StaffMessages::TranslationFailed { .. } => "staff.translationFailed",
+            StaffMessages::TranslationDisabled => "staff.translationDisabled",
+            StaffMessages::TranslationNoLanguage => "staff.translationNoLanguage",
+        }.to_string()
+    }
+
+    fn default_message(&self) -> String {
+        match self {
+            StaffMessages::AliasSaved => "Alias saved",
+            StaffMessages::AliasDeleted => "Alias deleted",
+            StaffMessages::LocalizationReloaded { .. } => "Localization reloaded, languages: {languages}",
+            StaffMessages::LocalizationReloadFailed { .. } => "Failed to reload localization, previous messages are kept: {error}",
+            StaffMessages::NoOpenConversation => "No open conversation",
+            StaffMessages::ConversationClosed => "Conversation closed",
+            StaffMessages::UserBanned => "User banned, their messages are ignored",
+            StaffMessages::UserUnbanned => "User unbanned",
+            StaffMessages::Notes => "User notes:",
+            StaffMessages::NoteSaved => "Note saved",
+            StaffMessages::NoteReplaced { .. } => "Note saved. It replaced <code>{old}</code> set by {author} at {time}",
+            StaffMessages::NoteDeleted => "Note deleted",
+            StaffMessages::NoteInvalidValue { .. } => "Invalid {type_name} value",
+            StaffMessages::NoteUnknownType { .. } => "Unknown note type. Known types: {types}",
+            StaffMessages::NoteNoHistory { .. } => "Note <b>{key}</b> has no history",
+            StaffMessages::NoteHistory { .. } => "History of note <b>{key}</b>:",
+            StaffMessages::NoteHistorySet { .. } => "set <code>{value}</code> ({type_name})",
+            StaffMessages::NoteHistoryDeleted => "deleted",
+            StaffMessages::NoteDeepLink => "deep link",
+            StaffMessages::NoteDate { .. } => "{date, date}",
+            StaffMessages::Tagged { .. } => "Tagged: {tags}",
+            StaffMessages::Untagged { .. } => "Untagged: {tags}",
+            StaffMessages::NoUsersTagged { .. } => "No users tagged {tag}",
+            StaffMessages::UsersTagged { .. } => "Users tagged {tag}:",
+            StaffMessages::InvalidTags => "Tags can contain only letters, digits and <code>_</code>, up to 32 characters",
+            StaffMessages::UserRenamed { .. } => "User renamed: {old} → {new}",
+            StaffMessages::UserMovedTo { .. } => "User moved to <a href=\"{link}\">another superchat</a>",
+            StaffMessages::UserMovedFrom { .. } => "User moved from <a href=\"{link}\">another superchat</a>",
+            StaffMessages::UserRated { .. } => "User rated conversation: {stars}",
+            StaffMessages::UserCommented { .. } => "User commented rating: {text}",
+            StaffMessages::SlaBreach { .. } => "⚠️ <b>SLA breach:</b> <a href=\"{link}\">{name}</a> {reason}",
+            StaffMessages::SlaNoFirstResponse { .. } => "no first response for {minutes} min",
+            StaffMessages::SlaUnanswered { .. } => "unanswered for {minutes} min",
+            StaffMessages::Translation { .. } => "🌐 {source}: {text}",
+            StaffMessages::TranslationSent { .. } => "🌐 Sent in {lang}: {text}",
+            StaffMessages::TranslationFailed { .. } => "Failed to translate, message was not sent: {error}",
+            StaffMessages::TranslationDisabled => "Translation is not configured",
+            StaffMessages::TranslationNoLanguage => "User language is unknown, message was not sent",
+        }.to_string()
+    }
+
+    fn description(&self) -> Option<String> {
+        let description = match self {
+            StaffMessages::Notes => "Header of /notes reply",
+            StaffMessages::NoteReplaced { .. } => "Reply to /setnote that overwrote note of another agent, HTML",
+            StaffMessages::NoteNoHistory { .. } | StaffMessages::NoteHistory { .. } => "Reply to /notehistory, HTML",
+            StaffMessages::NoteHistorySet { .. } | StaffMessages::NoteHistoryDeleted => "Line of /notehistory reply, HTML",
+            StaffMessages::NoteDeepLink => "Author of notes created from /start deep links",
+            StaffMessages::NoteDate { .. } => "Value of date note in user info and /notes reply",
+            StaffMessages::InvalidTags => "Reply to /tag, /untag and /tagged, HTML",
+            StaffMessages::UserMovedTo { .. } | StaffMessages::UserMovedFrom { .. } => "Posted to both topics when user is routed to another superchat, HTML",
+            StaffMessages::SlaBreach { .. } => "SLA alert, HTML. {reason} is one of SLA reasons",
+            StaffMessages::SlaNoFirstResponse { .. } | StaffMessages::SlaUnanswered { .. } => "SLA reason",
+            StaffMessages::Translation { .. } => "Machine translation of user message posted to topic",
+            StaffMessages::TranslationSent { .. } => "Reply to /tr with text that was sent to user",
+            _ => return None,
+        };
+        Some(description.to_string())
+    }
+
+    fn args(self) -> Option<Vec<(String, Arg)>> {
+        match self {
+            StaffMessages::AliasSaved => None,
+            StaffMessages::AliasDeleted => None,
+            StaffMessages::LocalizationReloaded { languages } => Some(vec![("languages".to_string(), languages.into())]),
+            StaffMessages::LocalizationReloadFailed { error } => Some(vec![("error".to_string(), error.into())]),
+            StaffMessages::NoOpenConversation => None,
+            StaffMessages::ConversationClosed => None,
+            StaffMessages::UserBanned => None,
+            StaffMessages::UserUnbanned => None,
+            StaffMessages::Notes => None,
+            StaffMessages::NoteSaved => None,
+            StaffMessages::NoteReplaced { old, author, time } => Some(vec![
+                ("old".to_string(), old.into()),
+                ("author".to_string(), author.into()),
+                ("time".to_string(), time.into()),
+            ]),
+            StaffMessages::NoteDeleted => None,
+            StaffMessages::NoteInvalidValue { type_name } => Some(vec![("type_name".to_string(), type_name.into())]),
+            StaffMessages::NoteUnknownType { types } => Some(vec![("types".to_string(), types.into())]),
+            StaffMessages::NoteNoHistory { key } => Some(vec![("key".to_string(), key.into())]),
+            StaffMessages::NoteHistory { key } => Some(vec![("key".to_string(), key.into())]),
+            StaffMessages::NoteHistorySet { value, type_name } => Some(vec![
+                ("value".to_string(), value.into()),
+                ("type_name".to_string(), type_name.into()),
+            ]),
+            StaffMessages::NoteHistoryDeleted => None,
+            StaffMessages::NoteDeepLink => None,
+            StaffMessages::NoteDate { date } => Some(vec![("date".to_string(), Arg::Date(date))]),
+            StaffMessages::Tagged { tags } => Some(vec![("tags".to_string(), tags.into())]),
+            StaffMessages::Untagged { tags } => Some(vec![("tags".to_string(), tags.into())]),
+            StaffMessages::NoUsersTagged { tag } => Some(vec![("tag".to_string(), tag.into())]),
+            StaffMessages::UsersTagged { tag } => Some(vec![("tag".to_string(), tag.into())]),
+            StaffMessages::InvalidTags => None,
+            StaffMessages::UserRenamed { old, new } => Some(vec![
+                ("old".to_string(), old.into()),
+                ("new".to_string(), new.into()),
+            ]),
+            StaffMessages::UserMovedTo { link } => Some(vec![("link".to_string(), link.into())]),
+            StaffMessages::UserMovedFrom { link } => Some(vec![("link".to_string(), link.into())]),
+            StaffMessages::UserRated { stars } => Some(vec![("stars".to_string(), stars.into())]),
+            StaffMessages::UserCommented { text } => Some(vec![("text".to_string(), text.into())]),
+            StaffMessages::SlaBreach { link, name, reason } => Some(vec![
+                ("link".to_string(), link.into()),
+                ("name".to_string(), name.into()),
+                ("reason".to_string(), reason.into()),
+            ]),
+            StaffMessages::SlaNoFirstResponse { minutes } => Some(vec![("minutes".to_string(), minutes.into())]),
+            StaffMessages::SlaUnanswered { minutes } => Some(vec![("minutes".to_string(), minutes.into())]),
+            StaffMessages::Translation { source, text } => Some(vec![
+                ("source".to_string(), source.into()),
+                ("text".to_string(), text.into()),
+            ]),
+            StaffMessages::TranslationSent { lang, text } => Some(vec![
+                ("lang".to_string(), lang.into()),
+                ("text".to_string(), text.into()),
+            ]),
+            StaffMessages::TranslationFailed { error } => Some(vec![("error".to_string(), error.into())]),
+            StaffMessages::TranslationDisabled => None,
+            StaffMessages::TranslationNoLanguage => None,
+        }
+    }
+}
+
+/// Description of bot command, keyed `commands.user.start` or `commands.staff.setnote`.
+/// Default is the `#[command(description)]` text, so these keys aren't checked by validation
+pub struct CommandDescription {
+    /// `user` or `staff`
+    pub scope: &'static str,
+    /// Command without leading `/`
+    pub command: String,
+    pub default: String,
+}
+
+impl LocKey for CommandDescription {
+    fn key(&self) -> String {
+        format!("commands.{}.{}", self.scope, self.command)
+    }
+
+    fn default_message(&self) -> String {
+        self.default.clone()
+    }
+
+    fn args(self) -> Option<Vec<(String, Arg)>> {
+        None
+    }
+}
\ No newline at end of file
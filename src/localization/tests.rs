@@ -0,0 +1,301 @@
+use std::path::PathBuf;
+use tempfile::TempDir;
+use serde_json::json;
+use super::{from_config, Arg, CommonMessages, LocKey, LocalizationBundle, StaffMessages};
+use super::format::format;
+use super::formats::by_path;
+use super::file::template;
+
+/// Creates empty directory in temp dir, removed when dropped
+fn dir() -> TempDir {
+    tempfile::tempdir().unwrap()
+}
+
+fn write(path: PathBuf, messages: &[(&str, &str)]) {
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    let contents = messages.iter()
+        .map(|(k, v)| (k.to_string(), json!({ "defaultMessage": v })))
+        .collect::<serde_json::Map<_, _>>();
+    std::fs::write(path, serde_json::to_string(&contents).unwrap()).unwrap();
+}
+
+fn layout() -> TempDir {
+    let tmp = dir();
+    let root = tmp.path();
+    write(root.join("en.json"), &[("common.welcome", "Hello")]);
+    write(root.join("ru").join("common.json"), &[("common.welcome", "Привет")]);
+    write(root.join("ru").join("rating").join("prompt.json"), &[("common.ratingPrompt", "Оцените")]);
+    std::fs::write(root.join("README.md"), "not a localization").unwrap();
+    tmp
+}
+
+#[tokio::test]
+async fn scan_dir_reads_json_files_only() {
+    let tmp = layout();
+    let root = tmp.path();
+    let mut bundle = LocalizationBundle::new();
+    bundle.scan_dir(&root, false).await.unwrap();
+
+    assert_eq!(bundle.languages(), vec!["en"]);
+    assert_eq!(bundle.localize(Some("en".to_string()), CommonMessages::Welcome), "Hello");
+}
+
+#[tokio::test]
+async fn scan_dir_merges_language_directories_when_recursive() {
+    let tmp = layout();
+    let root = tmp.path();
+    let mut bundle = LocalizationBundle::new();
+    bundle.scan_dir(&root, true).await.unwrap();
+
+    assert_eq!(bundle.languages(), vec!["en", "ru"]);
+    assert_eq!(bundle.localize(Some("ru".to_string()), CommonMessages::Welcome), "Привет");
+    assert_eq!(bundle.localize(Some("ru".to_string()), CommonMessages::RatingPrompt), "Оцените");
+}
+
+#[tokio::test]
+async fn validation_lists_missing_and_unknown_keys() {
+    let tmp = dir();
+    let root = tmp.path();
+    let all = CommonMessages::all().iter().map(|m| m.key())
+        .chain(StaffMessages::all().iter().map(|m| m.key()))
+        .collect::<Vec<_>>();
+    let complete = all.iter().map(|k| (k.as_str(), "text")).collect::<Vec<_>>();
+    write(root.join("en.json"), &complete);
+    write(root.join("ru.json"), &[("common.welcome", "Привет"), ("common.welcom", "Опечатка"), ("commands.user.faq", "вопросы")]);
+    let cfg = serde_json::from_value(json!({ "paths": [root.to_str().unwrap()] })).unwrap();
+    let bundle = from_config(Some(cfg)).await.unwrap();
+
+    let report = bundle.validate();
+    assert!(!report.missing.contains_key("en"));
+    let missing = &report.missing["ru"];
+    assert_eq!(missing.len(), all.len() - 1);
+    assert!(missing.contains(&"common.faq".to_string()));
+    assert_eq!(report.unknown["ru"], vec!["common.welcom"]);
+    assert!(report.to_string().contains("ru: unknown common.welcom"));
+}
+
+fn args(args: &[(&str, Arg)]) -> Vec<(String, Arg)> {
+    args.iter().map(|(k, v)| (k.to_string(), v.clone())).collect()
+}
+
+#[test]
+fn plurals_follow_language_rules() {
+    let pattern = "{n, plural, =0 {no messages} one {# message} few {# сообщения} many {# сообщений} other {# messages}}";
+    let ru = [(1, "1 message"), (3, "3 сообщения"), (5, "5 сообщений"), (11, "11 сообщений"), (22, "22 сообщения"), (0, "no messages")];
+    for (n, expected) in ru {
+        assert_eq!(format("ru", pattern, &args(&[("n", Arg::Number(n))])), expected);
+    }
+    assert_eq!(format("pl", pattern, &args(&[("n", Arg::Number(21))])), "21 сообщений");
+    assert_eq!(format("pt-BR", pattern, &args(&[("n", Arg::Number(2))])), "2 messages");
+    assert_eq!(format("pt", pattern, &args(&[("n", Arg::Number(0))])), "no messages");
+    let one = "{n, plural, one {one} other {other}}";
+    assert_eq!(format("pt", one, &args(&[("n", Arg::Number(0))])), "one", "CLDR puts 0 into one for Portuguese");
+    assert_eq!(format("lv", one, &args(&[("n", Arg::Number(21))])), "one", "languages are not limited to built-in tables");
+    assert_eq!(format("hr", pattern, &args(&[("n", Arg::Number(3))])), "3 сообщения");
+    assert_eq!(format("en", pattern, &args(&[("n", Arg::Number(12345))])), "12,345 messages");
+    assert_eq!(format("ru", pattern, &args(&[("n", Arg::Number(12345))])), "12\u{a0}345 сообщений");
+}
+
+#[test]
+fn select_dates_and_quotes_are_formatted() {
+    let pattern = "{g, select, female {She} other {They}} wrote on {d, date}, it''s '{'quoted'}'";
+    let values = args(&[("g", Arg::from("female")), ("d", Arg::Date(1700000000))]);
+    assert_eq!(format("en", pattern, &values), "She wrote on Nov 14, 2023, it's {quoted}");
+    assert_eq!(format("ru", pattern, &values), "She wrote on 14 нояб. 2023\u{202f}г., it's {quoted}");
+    assert_eq!(format("de", "{d, date}", &values), "14.11.2023");
+}
+
+#[test]
+fn plain_strings_keep_working() {
+    let values = args(&[("name", Arg::from("Anna"))]);
+    assert_eq!(format("en", "Staff can''t see '{name}' yet", &values), "Staff can''t see 'Anna' yet");
+    assert_eq!(format("en", "— {name}, Support", &values), "— Anna, Support");
+    assert_eq!(format("en", "{name} has {unknown}", &values), "Anna has {unknown}");
+    assert_eq!(format("en", "broken {name, plural, one", &values), "broken {name, plural, one");
+    assert_eq!(format("en", "{ not closed {name}", &values), "{ not closed Anna");
+}
+
+fn contents(messages: &[(&str, &str)]) -> super::FileContents {
+    messages.iter()
+        .map(|(k, v)| (k.to_string(), super::Entry { default_message: v.to_string(), description: None, parse_mode: None }))
+        .collect()
+}
+
+#[test]
+fn languages_fall_back_through_parents_and_chains() {
+    let mut bundle = LocalizationBundle::new();
+    bundle.set_default_lang("en".to_string());
+    bundle.set_fallback("uk", &["ru".to_string()]);
+    bundle.set_fallback("ru", &["uk".to_string()]);
+    bundle.add("en", contents(&[("common.welcome", "Hello"), ("common.faq", "FAQ")]));
+    bundle.add("pt", contents(&[("common.welcome", "Olá")]));
+    bundle.add("ru", contents(&[("common.welcome", "Привет"), ("common.faq", "Вопросы")]));
+    bundle.add("uk", contents(&[("common.welcome", "Вітаю")]));
+
+    assert_eq!(bundle.chain(Some("uk-UA")), vec!["uk-ua", "uk", "ru", "en"]);
+    assert_eq!(bundle.localize(Some("pt_BR".to_string()), CommonMessages::Welcome), "Olá");
+    assert_eq!(bundle.localize(Some("pt-BR".to_string()), CommonMessages::Faq), "FAQ");
+    assert_eq!(bundle.localize(Some("uk".to_string()), CommonMessages::Welcome), "Вітаю");
+    assert_eq!(bundle.localize(Some("uk".to_string()), CommonMessages::Faq), "Вопросы");
+    assert_eq!(bundle.localize(None, CommonMessages::RatingSkip), "Skip");
+}
+
+
+#[test]
+fn info_flags_are_localized_with_select() {
+    let header = |banned| CommonMessages::InfoHeader {
+        id: 42,
+        first_name: Some("John".to_string()),
+        last_name: None,
+        lang: None,
+        username: None,
+        premium: false,
+        first_contact: None,
+        source: None,
+        incoming: 0,
+        outgoing: 0,
+        tickets: 0,
+        banned,
+        agent: None,
+        tags: vec![],
+    };
+    let mut bundle = LocalizationBundle::new();
+    bundle.add("ru", contents(&[("common.infoHeader", "Премиум: {premium, select, true {да} other {нет}}, бан: {banned, select, true {да} other {нет}}")]));
+
+    assert_eq!(bundle.localize(Some("ru".to_string()), header(true)), "Премиум: нет, бан: да");
+    let en = bundle.localize(None, header(false));
+    assert!(en.contains("<b>Premium: </b> no\n") && en.contains("<b>Banned: </b> no\n"), "{en}");
+}
+
+#[tokio::test]
+async fn yaml_toml_and_po_files_are_parsed() {
+    let tmp = dir();
+    let root = tmp.path();
+    std::fs::write(root.join("de.yaml"), concat!(
+        "common:\n",
+        "  welcome: Willkommen\n",
+        "  faq:\n",
+        "    defaultMessage: Fragen\n",
+        "    description: Reply to /faq\n",
+    )).unwrap();
+    std::fs::write(root.join("fr.toml"), concat!(
+        "[\"common.welcome\"]\n",
+        "defaultMessage = \"Bienvenue\"\n",
+        "description = \"Reply to /start\"\n",
+    )).unwrap();
+    std::fs::write(root.join("tr.po"), concat!(
+        "msgid \"\"\n",
+        "msgstr \"\"\n",
+        "\"Language: tr\\n\"\n",
+        "\n",
+        "#. Reply to /start\n",
+        "msgctxt \"common.welcome\"\n",
+        "msgid \"Welcome\"\n",
+        "msgstr \"\"\n",
+        "\"Hoş \"\n",
+        "\"geldiniz\"\n",
+        "\n",
+        "#, fuzzy\n",
+        "msgctxt \"common.faq\"\n",
+        "msgid \"FAQ\"\n",
+        "msgstr \"SSS\"\n",
+        "\n",
+        "msgid \"common.ratingSkip\"\n",
+        "msgstr \"Geç\"\n",
+    )).unwrap();
+    let mut bundle = LocalizationBundle::new();
+    bundle.scan_dir(&root, false).await.unwrap();
+
+    assert_eq!(bundle.languages(), vec!["de", "fr", "tr"]);
+    assert_eq!(bundle.localize(Some("de".to_string()), CommonMessages::Welcome), "Willkommen");
+    assert_eq!(bundle.localize(Some("de".to_string()), CommonMessages::Faq), "Fragen");
+    assert_eq!(bundle.localize(Some("fr".to_string()), CommonMessages::Welcome), "Bienvenue");
+    assert_eq!(bundle.localize(Some("tr".to_string()), CommonMessages::Welcome), "Hoş geldiniz");
+    assert_eq!(bundle.localize(Some("tr".to_string()), CommonMessages::RatingSkip), "Geç");
+    assert_eq!(bundle.localize(Some("tr".to_string()), CommonMessages::Faq), CommonMessages::Faq.default_message(), "fuzzy entries are skipped");
+
+    let (_, po) = super::file::parse(&root.join("tr.po")).await.unwrap();
+    assert_eq!(po["common.welcome"].description.as_deref(), Some("Reply to /start"));
+    let (_, yaml) = super::file::parse(&root.join("de.yaml")).await.unwrap();
+    assert_eq!(yaml["common.faq"].description.as_deref(), Some("Reply to /faq"));
+}
+
+#[tokio::test]
+async fn exported_templates_parse_back() {
+    let tmp = dir();
+    let root = tmp.path();
+    let entries = template();
+    let placeholder = entries.iter().find(|(k, _)| k == "common.staffSignature").unwrap();
+    assert!(placeholder.1.description.as_deref().unwrap().contains("{name}"));
+
+    for name in ["en.json", "en.yaml", "en.toml"] {
+        let path = root.join(name);
+        assert_eq!(super::export(&path).await.unwrap(), entries.len());
+        let (lang, contents) = super::file::parse(&path).await.unwrap();
+        assert_eq!(lang, "en");
+        assert_eq!(contents.len(), entries.len(), "{name}");
+        for (key, entry) in entries.iter() {
+            assert_eq!(&contents[key], entry, "{name} {key}");
+        }
+    }
+
+    // gettext templates have empty translations, so keys and descriptions are checked in text
+    let path = root.join("en.po");
+    super::export(&path).await.unwrap();
+    let text = std::fs::read_to_string(&path).unwrap();
+    assert!(text.contains("msgctxt \"common.infoHeader\"\nmsgid \"\"\n\"<b><a href=\\\"tg://user?id={id}\\\">"), "{text}");
+    assert!(text.contains("#. Reply to /start\nmsgctxt \"common.welcome\""));
+    assert!(by_path(&root.join("en.txt")).is_none());
+}
+
+#[test]
+fn telegram_html_is_validated_and_cleaned() {
+    use super::html::{clean, escape, validate};
+    assert_eq!(validate(r#"<b>bold <i>both</i></b> <a href="https://t.me">link</a> &lt;3 &#128512;"#), Ok(()));
+    assert_eq!(validate(r#"<pre><code class="language-rust">let a = 1;</code></pre><span class="tg-spoiler">x</span>"#), Ok(()));
+    assert_eq!(validate("<blockquote expandable>quote</blockquote>"), Ok(()));
+    assert!(validate("<div>block</div>").is_err());
+    assert!(validate("<b>bold <i>both</b></i>").is_err());
+    assert!(validate("<b>unclosed").is_err());
+    assert!(validate("1 < 2").is_err());
+    assert!(validate("&nbsp;").is_err());
+    assert!(validate(r#"<a onclick="x">link</a>"#).is_err());
+    assert!(validate("<code><b>bold</b></code>").is_err());
+
+    assert_eq!(escape(r#"<b> & "q""#), "&lt;b&gt; &amp; &quot;q&quot;");
+    assert_eq!(clean("<b>kept</b> <script>x</script> 1 < 2 & 3"), "<b>kept</b> &lt;script&gt;x&lt;/script&gt; 1 &lt; 2 &amp; 3");
+    assert_eq!(clean("<i>open <b>bold</b>"), "&lt;i&gt;open <b>bold</b>");
+    assert_eq!(clean("&amp; </b>"), "&amp; &lt;/b&gt;");
+}
+
+#[tokio::test]
+async fn parse_mode_entries_escape_arguments() {
+    let tmp = dir();
+    let root = tmp.path();
+    std::fs::write(root.join("en.json"), json!({
+        "common.staffSignature": { "defaultMessage": "<i>{name}</i>", "parseMode": "HTML" },
+        "common.welcome": { "defaultMessage": "Hello" },
+    }).to_string()).unwrap();
+    std::fs::write(root.join("de.yaml"), "common.staffHeader:\n  defaultMessage: \"*{name}*\"\n  parseMode: MarkdownV2\n").unwrap();
+    std::fs::write(root.join("tr.po"), "#, html\nmsgctxt \"common.welcome\"\nmsgid \"Welcome\"\nmsgstr \"<b>Hoş geldiniz</b>\"\n").unwrap();
+    let mut bundle = LocalizationBundle::new();
+    bundle.scan_dir(&root, false).await.unwrap();
+
+    let signature = bundle.localize_message(Some("en".to_string()), CommonMessages::StaffSignature { name: "<Bob & Co>".to_string() });
+    assert_eq!(signature.text, "<i>&lt;Bob &amp; Co&gt;</i>");
+    assert_eq!(signature.parse_mode, Some(super::ParseMode::Html));
+    assert_eq!(bundle.localize_message(Some("en".to_string()), CommonMessages::Welcome).parse_mode, None);
+    let header = bundle.localize_message(Some("de".to_string()), CommonMessages::StaffHeader { name: "J.R.".to_string() });
+    assert_eq!(header.text, "*J\\.R\\.*");
+    assert_eq!(header.parse_mode, Some(super::ParseMode::MarkdownV2));
+    assert_eq!(bundle.localize_message(Some("tr".to_string()), CommonMessages::Welcome).parse_mode, Some(super::ParseMode::Html));
+    // built-in messages are plain text
+    let default = bundle.localize_message(Some("fr".to_string()), CommonMessages::StaffHeader { name: "<Bob>".to_string() });
+    assert!(default.text.contains("<Bob>") && default.parse_mode.is_none());
+
+    std::fs::write(root.join("en.json"), json!({
+        "common.welcome": { "defaultMessage": "<b>Hello</i>", "parseMode": "HTML" },
+    }).to_string()).unwrap();
+    let error = super::file::parse(&root.join("en.json")).await.unwrap_err();
+    assert!(error.to_string().contains("common.welcome"), "{error}");
+}
\ No newline at end of file
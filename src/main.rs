@@ -1,4 +1,8 @@
+use std::path::Path;
+use std::sync::Arc;
+use tracing::info;
 use tracing_subscriber::EnvFilter;
+use crate::cli::{Args, Command};
 use crate::config::Configuration;
 
 mod metrics;
@@ -7,14 +11,73 @@ mod config;
 mod telegram;
 mod database;
 mod localization;
+mod translation;
+mod cli;
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     tracing_subscriber::fmt().json().with_env_filter(EnvFilter::from_default_env()).init();
-    let config = Configuration::new()?;
+    let args = match Args::parse(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, cli::USAGE);
+            std::process::exit(2);
+        }
+    };
+    match args.command {
+        Command::Help => println!("{}", cli::USAGE),
+        Command::PrintDefaultConfig => println!("{}", config::DEFAULT_CONFIG),
+        Command::ExportLocalization(path) => {
+            let count = localization::export(&path).await?;
+            println!("Exported {} localization keys to {}", count, path.display());
+        }
+        Command::CheckConfig => {
+            load_config(args.config.as_deref());
+            println!("Configuration is valid");
+        }
+        Command::Migrate => {
+            let config = load_config(args.config.as_deref());
+            let applied = database::migrate(&config.database)?;
+            if applied.is_empty() {
+                println!("Database is up to date");
+            } else {
+                println!("Applied migrations: {}", applied.join(", "));
+            }
+        }
+        Command::Run => run(load_config(args.config.as_deref())).await?,
+    }
+    Ok(())
+}
+
+/// Reads config and checks it, so every problem is reported before anything starts. Exits if config is invalid
+fn load_config(path: Option<&Path>) -> Configuration {
+    let config = match Configuration::load(path) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("Failed to read configuration: {}", e);
+            std::process::exit(1);
+        }
+    };
+    let errors = config.validate();
+    if !errors.is_empty() {
+        eprint!("Configuration has {} problems:\n{}", errors.len(), errors);
+        std::process::exit(1);
+    }
+    config
+}
+
+async fn run(config: Configuration) -> anyhow::Result<()> {
     metrics::install(&config.metrics)?;
-    let bundle = localization::from_config(config.localization).await?;
+    let bundle = Arc::new(localization::from_config(config.localization).await?);
+    tokio::spawn(localization::watch(bundle.clone()));
+    let applied = database::migrate(&config.database)?;
+    if !applied.is_empty() {
+        info!("Applied database migrations: {}", applied.join(", "));
+    }
     let db = database::connect(config.database).await?;
-    telegram::run(config.telegram, db, bundle).await?;
+    let translation = config.translation.map(translation::Translation::new).transpose()
+        .map_err(|e| anyhow::anyhow!("Failed to set up translation: {e}"))?
+        .map(Arc::new);
+    telegram::run(config.telegram, db, bundle, translation).await?;
     Ok(())
 }
\ No newline at end of file
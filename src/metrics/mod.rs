@@ -0,0 +1,108 @@
+use std::net::SocketAddr;
+use metrics_exporter_prometheus::{Matcher, PrometheusBuilder};
+use anyhow::Result;
+use metrics::{counter, describe_counter, describe_gauge, describe_histogram, gauge, histogram, Unit};
+use serde::Deserialize;
+use tracing::{debug, info};
+use crate::config::ConfigErrors;
+
+#[cfg(test)]
+mod tests;
+
+const MESSAGES_INCOMING: &str = "support_messages_incoming_total";
+const MESSAGES_OUTGOING: &str = "support_messages_outgoing_total";
+const NEW_USERS: &str = "support_new_users_total";
+const HANDLER_ERRORS: &str = "support_handler_errors_total";
+const FIRST_RESPONSE: &str = "support_first_response_seconds";
+const RESPONSE: &str = "support_response_seconds";
+const OPEN_CONVERSATIONS: &str = "support_open_conversations";
+const UNANSWERED_CONVERSATIONS: &str = "support_unanswered_conversations";
+const SLA_BREACHES: &str = "support_sla_breaches_total";
+const RATINGS: &str = "support_ratings_total";
+
+/// Response time buckets, from a minute up to two days
+const RESPONSE_BUCKETS: &[f64] = &[60.0, 300.0, 900.0, 1800.0, 3600.0, 7200.0, 14400.0, 28800.0, 86400.0, 172800.0];
+
+#[derive(Deserialize, Debug)]
+pub struct MetricsConfig {
+    /// Listen address of prometheus endpoint, like `0.0.0.0:9000`
+    pub address: String,
+}
+
+impl MetricsConfig {
+    pub fn validate(&self, errors: &mut ConfigErrors) {
+        if self.address.parse::<SocketAddr>().is_err() {
+            errors.add("metrics.address", format!("{} is not an address like 0.0.0.0:9000", self.address));
+        }
+    }
+}
+
+pub fn install(config: &Option<MetricsConfig>) -> Result<()> {
+    let mut builder = builder()?;
+    if let Some(ref metrics) = config {
+        info!("Will create prometheus metrics endpoint at {}", &metrics.address);
+        builder = builder.with_http_listener(metrics.address.parse::<SocketAddr>()?);
+    }
+    builder.install()?;
+    describe();
+
+    debug!("Metrics set up");
+    Ok(())
+}
+
+/// Exporter with buckets of response time histograms
+pub(crate) fn builder() -> Result<PrometheusBuilder> {
+    Ok(PrometheusBuilder::new()
+        .set_buckets_for_metric(Matcher::Suffix("response_seconds".to_string()), RESPONSE_BUCKETS)?)
+}
+
+fn describe() {
+    describe_counter!(MESSAGES_INCOMING, "Messages received from users");
+    describe_counter!(MESSAGES_OUTGOING, "Messages sent to users by staff");
+    describe_counter!(NEW_USERS, "Users that contacted support for the first time");
+    describe_counter!(HANDLER_ERRORS, "Errors returned by update handlers");
+    describe_histogram!(FIRST_RESPONSE, Unit::Seconds, "Time from conversation start to the first staff reply");
+    describe_histogram!(RESPONSE, Unit::Seconds, "Time from the oldest unanswered user message to staff reply");
+    describe_gauge!(OPEN_CONVERSATIONS, "Conversations that are not closed");
+    describe_gauge!(UNANSWERED_CONVERSATIONS, "Conversations waiting for staff reply");
+    describe_counter!(SLA_BREACHES, "Conversations that breached SLA targets");
+    describe_counter!(RATINGS, "Satisfaction ratings left by users");
+}
+
+pub fn message_incoming(kind: &'static str) {
+    counter!(MESSAGES_INCOMING, "kind" => kind).increment(1);
+}
+
+pub fn message_outgoing(kind: &'static str) {
+    counter!(MESSAGES_OUTGOING, "kind" => kind).increment(1);
+}
+
+pub fn new_user() {
+    counter!(NEW_USERS).increment(1);
+}
+
+pub fn handler_error(endpoint: &'static str) {
+    counter!(HANDLER_ERRORS, "endpoint" => endpoint).increment(1);
+}
+
+pub fn first_response(seconds: i64) {
+    histogram!(FIRST_RESPONSE).record(seconds.max(0) as f64);
+}
+
+pub fn response(seconds: i64) {
+    histogram!(RESPONSE).record(seconds.max(0) as f64);
+}
+
+pub fn conversations(open: i64, unanswered: i64) {
+    gauge!(OPEN_CONVERSATIONS).set(open as f64);
+    gauge!(UNANSWERED_CONVERSATIONS).set(unanswered as f64);
+}
+
+pub fn sla_breach(kind: &'static str) {
+    counter!(SLA_BREACHES, "kind" => kind).increment(1);
+}
+
+/// Ratings are not labeled by agent to keep cardinality bounded, per-agent data is in the stored ratings
+pub fn rating(rating: i16) {
+    counter!(RATINGS, "rating" => rating.to_string()).increment(1);
+}
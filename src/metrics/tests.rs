@@ -0,0 +1,19 @@
+use super::{builder, conversations, describe, first_response, message_incoming};
+
+#[test]
+fn recorded_metrics_are_rendered() {
+    let recorder = builder().unwrap().build_recorder();
+    let handle = recorder.handle();
+    metrics::with_local_recorder(&recorder, || {
+        describe();
+        message_incoming("text");
+        first_response(120);
+        conversations(3, 1);
+    });
+
+    let rendered = handle.render();
+    assert!(rendered.contains("support_messages_incoming_total{kind=\"text\"} 1"), "{rendered}");
+    assert!(rendered.contains("support_first_response_seconds_bucket{le=\"300\"} 1"), "{rendered}");
+    assert!(rendered.contains("support_open_conversations 3"), "{rendered}");
+    assert!(rendered.contains("support_unanswered_conversations 1"), "{rendered}");
+}
\ No newline at end of file
@@ -1,5 +1,18 @@
 // @generated automatically by Diesel CLI.
 
+diesel::table! {
+    conversations (id) {
+        id -> Integer,
+        user_id -> Integer,
+        opened_at -> BigInt,
+        first_response_at -> Nullable<BigInt>,
+        unanswered_since -> Nullable<BigInt>,
+        closed_at -> Nullable<BigInt>,
+        breached_at -> Nullable<BigInt>,
+        staff_id -> Nullable<BigInt>,
+    }
+}
+
 diesel::table! {
     messages (id) {
         id -> Integer,
@@ -9,6 +22,19 @@ diesel::table! {
         rx_msg_id -> BigInt,
         rx_msg -> Text,
         tx_msg_id -> BigInt,
+        tx_chat_id -> Nullable<BigInt>,
+    }
+}
+
+diesel::table! {
+    note_history (id) {
+        id -> Integer,
+        user_id -> Integer,
+        key -> Text,
+        value -> Nullable<Text>,
+        type_ -> SmallInt,
+        staff_id -> Nullable<BigInt>,
+        created_at -> BigInt,
     }
 }
 
@@ -18,6 +44,37 @@ diesel::table! {
         user_id -> Integer,
         key -> Text,
         value -> Text,
+        type_ -> SmallInt,
+    }
+}
+
+diesel::table! {
+    ratings (id) {
+        id -> Integer,
+        conversation_id -> Integer,
+        user_id -> Integer,
+        staff_id -> Nullable<BigInt>,
+        prompt_message -> BigInt,
+        rating -> Nullable<SmallInt>,
+        comment -> Nullable<Text>,
+        awaiting_comment -> Bool,
+        rated_at -> Nullable<BigInt>,
+    }
+}
+
+diesel::table! {
+    staff_aliases (id) {
+        id -> Integer,
+        telegram_id -> BigInt,
+        alias -> Text,
+    }
+}
+
+diesel::table! {
+    user_tags (id) {
+        id -> Integer,
+        user_id -> Integer,
+        tag -> Text,
     }
 }
 
@@ -30,14 +87,31 @@ diesel::table! {
         first_name -> Nullable<Text>,
         last_name -> Nullable<Text>,
         lang_code -> Nullable<Text>,
+        username -> Nullable<Text>,
+        is_premium -> Bool,
+        first_contact -> Nullable<BigInt>,
+        banned -> Bool,
+        superchat -> Nullable<BigInt>,
+        start_payload -> Nullable<Text>,
+        lang_selected -> Bool,
     }
 }
 
+diesel::joinable!(conversations -> users (user_id));
 diesel::joinable!(messages -> users (user_id));
+diesel::joinable!(note_history -> users (user_id));
 diesel::joinable!(notes -> users (user_id));
+diesel::joinable!(ratings -> conversations (conversation_id));
+diesel::joinable!(ratings -> users (user_id));
+diesel::joinable!(user_tags -> users (user_id));
 
 diesel::allow_tables_to_appear_in_same_query!(
+    conversations,
     messages,
+    note_history,
     notes,
+    ratings,
+    staff_aliases,
+    user_tags,
     users,
 );
@@ -0,0 +1,50 @@
+use teloxide::prelude::*;
+use teloxide::types::{BotCommand, BotCommandScope, Recipient};
+use teloxide::utils::command::BotCommands;
+use crate::localization::{CommandDescription, CommonMessages, LocalizationBundle};
+use crate::telegram::{SupportCommand, TelegramConfig, UserCommand};
+
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+/// Commands of `C` with descriptions in `lang`, `scope` is `user` or `staff`
+fn localized<C: BotCommands>(loc: &LocalizationBundle, lang: Option<String>, scope: &'static str) -> Vec<BotCommand> {
+    C::bot_commands().into_iter()
+        .map(|c| {
+            let description = loc.localize(lang.clone(), CommandDescription {
+                scope,
+                command: c.command.trim_start_matches('/').to_string(),
+                default: c.description,
+            });
+            BotCommand::new(c.command, description)
+        })
+        .collect()
+}
+
+/// `/help` text in user language, formatted like [BotCommands::descriptions]
+pub fn help(loc: &LocalizationBundle, lang: Option<String>) -> String {
+    let mut text = format!("{}\n", loc.localize(lang.clone(), CommonMessages::CommandsHeader));
+    for command in localized::<UserCommand>(loc, lang, "user") {
+        text = format!("{}\n{} — {}", text, command.command, command.description);
+    }
+    text
+}
+
+/// Sets user commands in every loaded language and staff commands in staff language of each superchat.
+/// Telegram accepts only two-letter language codes, so regional variants get commands of the base language
+pub async fn register(bot: &Bot, cfg: &TelegramConfig, loc: &LocalizationBundle) -> Result<()> {
+    bot.set_my_commands(localized::<UserCommand>(loc, None, "user"))
+        .scope(BotCommandScope::AllPrivateChats)
+        .await?;
+    for lang in loc.languages().into_iter().filter(|l| l.len() == 2) {
+        bot.set_my_commands(localized::<UserCommand>(loc, Some(lang.clone()), "user"))
+            .scope(BotCommandScope::AllPrivateChats)
+            .language_code(lang)
+            .await?;
+    }
+    for superchat in cfg.superchats() {
+        bot.set_my_commands(localized::<SupportCommand>(loc, cfg.staff_lang(superchat), "staff"))
+            .scope(BotCommandScope::Chat { chat_id: Recipient::Id(superchat) })
+            .await?;
+    }
+    Ok(())
+}
\ No newline at end of file
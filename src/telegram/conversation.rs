@@ -0,0 +1,84 @@
+use teloxide::prelude::Message;
+use crate::database::{ConversationEntity, Database, InsertConversationEntity, UserEntity};
+use crate::metrics;
+
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+/// Staff reply effects on conversation
+pub struct Reply {
+    /// Reply resolved SLA breach
+    pub breached: bool,
+    /// Reply changed staff member assigned to conversation
+    pub assigned: bool,
+}
+
+/// Opens a conversation if user has none and marks it as waiting for staff reply.
+/// Returns true if new conversation was opened
+pub async fn incoming(db: &dyn Database, user: &UserEntity, msg: &Message) -> Result<bool> {
+    let at = msg.date.timestamp();
+    let opened = match db.get_open_conversation(user).await? {
+        Some(mut conversation) => {
+            if conversation.unanswered_since.is_none() {
+                conversation.unanswered_since = Some(at);
+                db.update_conversation(conversation).await?;
+            }
+            false
+        }
+        None => {
+            // rating comment of previous conversation isn't coming anymore
+            if let Some(mut rating) = db.get_rating_awaiting_comment(user).await? {
+                rating.awaiting_comment = false;
+                db.update_rating(rating).await?;
+            }
+            db.insert_conversation(InsertConversationEntity {
+                user_id: user.id,
+                opened_at: at,
+                first_response_at: None,
+                unanswered_since: Some(at),
+                closed_at: None,
+                breached_at: None,
+                staff_id: None,
+            }).await?;
+            true
+        }
+    };
+    update_gauges(db).await?;
+    Ok(opened)
+}
+
+/// Records response times for staff reply and marks conversation as answered
+pub async fn outgoing(db: &dyn Database, user: &UserEntity, msg: &Message) -> Result<Reply> {
+    let Some(mut conversation) = db.get_open_conversation(user).await? else {
+        return Ok(Reply { breached: false, assigned: false });
+    };
+    let at = msg.date.timestamp();
+    if conversation.first_response_at.is_none() {
+        metrics::first_response(at - conversation.opened_at);
+        conversation.first_response_at = Some(at);
+    }
+    if let Some(since) = conversation.unanswered_since.take() {
+        metrics::response(at - since);
+    }
+    let staff_id = msg.from().map(|u| u.id.0 as i64);
+    let assigned = conversation.staff_id != staff_id;
+    conversation.staff_id = staff_id;
+    let breached = conversation.breached_at.take().is_some();
+    db.update_conversation(conversation).await?;
+    update_gauges(db).await?;
+    Ok(Reply { breached, assigned })
+}
+
+/// Closes conversation. Returns true if conversation had SLA breach
+pub async fn close(db: &dyn Database, mut conversation: ConversationEntity, at: i64) -> Result<bool> {
+    conversation.closed_at = Some(at);
+    let breached = conversation.breached_at.take().is_some();
+    db.update_conversation(conversation).await?;
+    update_gauges(db).await?;
+    Ok(breached)
+}
+
+pub async fn update_gauges(db: &dyn Database) -> Result<()> {
+    let (open, unanswered) = db.count_conversations().await?;
+    metrics::conversations(open, unanswered);
+    Ok(())
+}
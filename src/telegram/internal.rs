@@ -0,0 +1,44 @@
+use serde::Deserialize;
+use teloxide::prelude::*;
+use teloxide::types::{ReactionEmoji, ReactionType};
+use crate::config::ConfigErrors;
+use crate::database::UserEntity;
+
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+/// Rules for staff messages in user topic that should not be sent to user.
+/// `/internal` command is always available
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct InternalConfig {
+    /// Text or caption prefixes, like `//` or `#internal`
+    #[serde(default)]
+    pub prefixes: Vec<String>,
+    /// Treat replies to pinned user info message as internal
+    #[serde(default)]
+    pub reply_to_info: bool,
+}
+
+impl InternalConfig {
+    pub fn validate(&self, errors: &mut ConfigErrors) {
+        if self.prefixes.iter().any(|p| p.trim().is_empty()) {
+            errors.add("telegram.internal.prefixes", "should not contain empty prefixes, every staff message starts with one");
+        }
+    }
+}
+
+pub fn is_internal(cfg: &InternalConfig, user: &UserEntity, msg: &Message) -> bool {
+    if let Some(text) = msg.text().or_else(|| msg.caption()) {
+        if cfg.prefixes.iter().any(|p| text.starts_with(p.as_str())) {
+            return true;
+        }
+    }
+    cfg.reply_to_info && user.info_message.is_some_and(|info| {
+        msg.reply_to_message().is_some_and(|r| r.id.0 as i64 == info)
+    })
+}
+
+/// Marks message as internal so staff can see that it wasn't sent
+pub async fn mark(bot: &Bot, msg: &Message) -> Result<()> {
+    bot.set_message_reaction(msg.chat.id, msg.id, vec![ReactionType::emoji(ReactionEmoji::Eyes)]).await?;
+    Ok(())
+}
@@ -0,0 +1,74 @@
+use std::sync::Arc;
+use teloxide::prelude::*;
+use teloxide::types::{InlineKeyboardButton, InlineKeyboardMarkup, User};
+use crate::database::{Database, UserEntity};
+use crate::localization::{CommonMessages, LocalizationBundle};
+use crate::telegram::utils::send_localized;
+use crate::telegram::{update_user_info_msg, HandlerResult, TelegramConfig};
+
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+/// Button of `/language` keyboard. No language means language reported by Telegram client
+#[derive(Clone)]
+pub struct LanguageCallback {
+    lang: Option<String>,
+}
+
+impl LanguageCallback {
+    pub fn parse(data: &str) -> Option<LanguageCallback> {
+        let lang = data.strip_prefix("lang:")?;
+        Some(LanguageCallback { lang: Some(lang.to_string()).filter(|l| !l.is_empty()) })
+    }
+
+    fn data(&self) -> String {
+        format!("lang:{}", self.lang.as_deref().unwrap_or(""))
+    }
+}
+
+/// Language for messages to user: chosen with `/language`, last known or reported by client of current message
+pub fn user_lang(user: Option<&UserEntity>, from: Option<&User>) -> Option<String> {
+    user.and_then(|u| u.lang_code.clone())
+        .or_else(|| from.and_then(|u| u.language_code.clone()))
+}
+
+/// Sends keyboard with languages of localization bundle
+pub async fn prompt(bot: &Bot, loc: &LocalizationBundle, user: &UserEntity) -> Result<()> {
+    let mut rows = loc.languages()
+        .chunks(4)
+        .map(|langs| langs.iter()
+            .map(|lang| {
+                let label = if user.lang_selected && user.lang_code.as_deref() == Some(lang.as_str()) {
+                    format!("✓ {}", lang)
+                } else {
+                    lang.clone()
+                };
+                InlineKeyboardButton::callback(label, LanguageCallback { lang: Some(lang.clone()) }.data())
+            })
+            .collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+    rows.push(vec![InlineKeyboardButton::callback(
+        loc.localize(user.lang_code.clone(), CommonMessages::LanguageAuto),
+        LanguageCallback { lang: None }.data(),
+    )]);
+    send_localized(bot, UserId(user.telegram_id as u64), loc.localize_message(user.lang_code.clone(), CommonMessages::LanguagePrompt))
+        .reply_markup(InlineKeyboardMarkup::new(rows))
+        .await?;
+    Ok(())
+}
+
+pub async fn callback(bot: Bot, q: CallbackQuery, cb: LanguageCallback, cfg: TelegramConfig, db: Arc<Box<dyn Database>>, loc: Arc<LocalizationBundle>) -> HandlerResult {
+    bot.answer_callback_query(q.id.clone()).await?;
+    let Some(user) = db.get_user_by_tg_id(q.from.id).await? else {
+        return Ok(());
+    };
+    let user = match cb.lang {
+        // keyboard may be older than last localization reload
+        Some(lang) if !loc.languages().contains(&lang) => return Ok(()),
+        Some(lang) => UserEntity { lang_code: Some(lang), lang_selected: true, ..user },
+        None => UserEntity { lang_code: q.from.language_code.clone(), lang_selected: false, ..user },
+    };
+    db.update_user_profile(&user).await?;
+    send_localized(&bot, q.from.id, loc.localize_message(user.lang_code.clone(), CommonMessages::LanguageChanged)).await?;
+    update_user_info_msg(&bot, user, cfg, db.clone(), loc.clone()).await?;
+    Ok(())
+}
\ No newline at end of file
@@ -1,22 +1,112 @@
 mod utils;
+mod conversation;
+mod sla;
+mod survey;
+mod signature;
+mod internal;
+mod relay;
+mod profile;
+mod notes;
+mod tags;
+mod routing;
+mod start;
+mod language;
+mod commands;
+mod translate;
+#[cfg(test)]
+mod tests;
 
+use std::ops::ControlFlow;
 use std::sync::Arc;
 use serde::Deserialize;
+use teloxide::dispatching::UpdateHandler;
 use teloxide::macros::BotCommands;
 use teloxide::prelude::*;
-use teloxide::types::{BotCommandScope, InputFile, MediaKind, MessageId, MessageKind, ParseMode, ReactionEmoji, ReactionType, Recipient, ThreadId};
-use crate::database::{Database, InsertMessageEntity, InsertNoteEntity, InsertUserEntity, MessageType, UserEntity};
-use crate::localization::{CommonMessages, LocalizationBundle, sanitize};
-use crate::telegram::utils::MessageBuilder;
+use teloxide::{ApiError, RequestError};
+use teloxide::types::{MessageId, ParseMode, ReactionEmoji, ReactionType, ThreadId};
+use crate::config::ConfigErrors;
+use crate::database::{Database, InsertMessageEntity, InsertStaffAliasEntity, InsertUserEntity, MessageType, UserEntity};
+use crate::localization::{self, CommonMessages, LocalizationBundle, StaffMessages};
+use crate::metrics;
+use crate::translation::Translation;
+use crate::telegram::relay::Destination;
+use crate::telegram::signature::Signature;
+use crate::telegram::utils::{media_kind_name, send_localized, MessageBuilder};
 
 #[derive(Deserialize, Debug, Clone)]
 pub struct TelegramConfig {
     pub token: String,
+    /// Superchat for users that match no route
     pub superchat: i64,
+    /// Language of staff messages and commands in superchats, default localization language if not set
+    #[serde(default)]
+    pub staff_language: Option<String>,
+    /// Checked in order when user topic is created
+    #[serde(default)]
+    pub routes: Vec<routing::Route>,
+    #[serde(default)]
+    pub sla: Option<sla::SlaConfig>,
+    #[serde(default)]
+    pub signature: signature::SignatureConfig,
+    #[serde(default)]
+    pub internal: internal::InternalConfig,
+    #[serde(default)]
+    pub start: start::StartConfig,
+    #[serde(default)]
+    pub survey: survey::SurveyConfig,
+}
+
+impl TelegramConfig {
+    /// Default superchat followed by superchats of routes
+    fn superchats(&self) -> Vec<ChatId> {
+        let mut chats = vec![ChatId(self.superchat)];
+        for route in self.routes.iter() {
+            if !chats.contains(&ChatId(route.superchat)) {
+                chats.push(ChatId(route.superchat));
+            }
+        }
+        chats
+    }
+
+    fn superchat_of(&self, user: &UserEntity) -> ChatId {
+        ChatId(user.superchat.unwrap_or(self.superchat))
+    }
+
+    /// Staff language of superchat: of the first route to it that sets one, otherwise the common one
+    fn staff_lang(&self, superchat: ChatId) -> Option<String> {
+        self.routes.iter()
+            .filter(|r| ChatId(r.superchat) == superchat)
+            .find_map(|r| r.staff_language.clone())
+            .or(self.staff_language.clone())
+    }
+
+    pub fn validate(&self, errors: &mut ConfigErrors) {
+        let token = self.token.split_once(':')
+            .is_some_and(|(id, secret)| !id.is_empty() && id.chars().all(|c| c.is_ascii_digit()) && !secret.is_empty());
+        if !token {
+            errors.add("telegram.token", "should look like 123456789:secret, as issued by @BotFather");
+        }
+        validate_superchat("telegram.superchat", self.superchat, errors);
+        for (i, route) in self.routes.iter().enumerate() {
+            validate_superchat(&format!("telegram.routes[{}].superchat", i), route.superchat, errors);
+        }
+        self.internal.validate(errors);
+        if let Some(ref sla) = self.sla {
+            sla.validate(errors);
+        }
+    }
+}
+
+/// Topics exist only in supergroups, and their ids start with -100
+fn validate_superchat(key: &str, id: i64, errors: &mut ConfigErrors) {
+    if id > -1_000_000_000_000 {
+        errors.add(key, format!("{} is not a supergroup id, those look like -1001234567890", id));
+    }
 }
 
 type HandlerResult = Result<(), Box<dyn std::error::Error + Send + Sync>>;
 
+const TOPIC_COLOR: u32 = 16766590;
 
 #[derive(BotCommands, Clone)]
 #[command(rename_rule = "lowercase", description = "These commands are supported:")]
@@ -24,9 +114,11 @@ enum UserCommand {
     #[command(description = "display this text.")]
     Help,
     #[command(description = "print welcome message.")]
-    Start,
+    Start(String),
     #[command(description = "show FAQ.")]
     Faq,
+    #[command(description = "choose language of bot messages.")]
+    Language,
 }
 
 #[derive(BotCommands, Clone)]
@@ -38,41 +130,46 @@ enum SupportCommand {
     Notes,
     #[command(description = "Delete note")]
     Delnote { key: String },
+    #[command(description = "Show who changed note and when")]
+    Notehistory { key: String },
+    #[command(description = "Add space separated tags to user")]
+    Tag { tags: String },
+    #[command(description = "Remove space separated tags from user")]
+    Untag { tags: String },
+    #[command(description = "List users with tag")]
+    Tagged { tag: String },
+    #[command(description = "Close conversation and ask user for rating")]
+    Close,
+    #[command(description = "Ignore all messages from user")]
+    Ban,
+    #[command(description = "Accept messages from banned user again")]
+    Unban,
+    #[command(description = "Set your alias shown to users. Empty alias deletes it")]
+    Alias { alias: String },
+    #[command(description = "Leave internal comment that is not sent to user")]
+    Internal { text: String },
+    #[command(description = "Reload localization files")]
+    Reloadloc,
+    #[command(description = "Translate text into user language and send it")]
+    Tr { text: String },
 }
 
-pub async fn run(config: TelegramConfig, db: Box<dyn Database + 'static>, loc: LocalizationBundle) -> anyhow::Result<()> {
-    use teloxide::utils::command::BotCommands;
-
+pub async fn run(config: TelegramConfig, db: Box<dyn Database + 'static>, loc: Arc<LocalizationBundle>, tr: Option<Arc<Translation>>) -> anyhow::Result<()> {
     let bot = Bot::new(config.token.clone());
 
-    let superchat = ChatId(config.superchat);
+    let superchats = config.superchats();
 
-    bot.set_my_commands(UserCommand::bot_commands())
-        .scope(BotCommandScope::AllPrivateChats)
-        .await?;
-    bot.set_my_commands(SupportCommand::bot_commands())
-        .scope(BotCommandScope::Chat { chat_id: Recipient::Id(superchat) })
-        .await?;
+    commands::register(&bot, &config, &loc).await
+        .map_err(|e| anyhow::anyhow!("Failed to set bot commands: {e}"))?;
+    db.set_missing_superchat(config.superchat).await
+        .map_err(|e| anyhow::anyhow!("Failed to set superchat of existing users: {e}"))?;
+    conversation::update_gauges(db.as_ref()).await
+        .map_err(|e| anyhow::anyhow!("Failed to count conversations: {e}"))?;
+    let db = Arc::new(db);
+    tokio::spawn(sla::watch(bot.clone(), config.clone(), db.clone(), loc.clone()));
 
-    Dispatcher::builder(
-        bot,
-        dptree::entry()
-            .branch(Update::filter_message()
-                .branch(dptree::filter(|m: Message| { m.chat.is_private() })
-                    .branch(Update::filter_message().filter_command::<UserCommand>().endpoint(user_cmd))
-                    .branch(Update::filter_message()).endpoint(user_msg))
-                .branch(dptree::filter(move |m: Message| { m.chat.id == superchat })
-                    .branch(Update::filter_message().filter_command::<SupportCommand>().endpoint(superchat_cmd))
-                    .branch(Update::filter_message()).endpoint(superchat_msg)))
-            .branch(Update::filter_edited_message()
-                .branch(dptree::filter(|m: Message| { m.chat.is_private() })
-                    .branch(Update::filter_message().filter_command::<UserCommand>().endpoint(noop))
-                    .branch(Update::filter_message()).endpoint(user_update))
-                .branch(dptree::filter(move |m: Message| { m.chat.id == superchat })
-                    .branch(Update::filter_message().filter_command::<SupportCommand>().endpoint(noop))
-                    .branch(Update::filter_message()).endpoint(superchat_update)))
-    )
-        .dependencies(dptree::deps![config, Arc::new(db), Arc::new(loc)])
+    Dispatcher::builder(bot, schema(superchats))
+        .dependencies(dptree::deps![config, db, loc, tr])
         .enable_ctrlc_handler()
         .build()
         .dispatch()
@@ -80,77 +177,329 @@ pub async fn run(config: TelegramConfig, db: Box<dyn Database + 'static>, loc: L
     Ok(())
 }
 
+fn schema(superchats: Vec<ChatId>) -> UpdateHandler<Box<dyn std::error::Error + Send + Sync + 'static>> {
+    let edited_superchats = superchats.clone();
+    dptree::entry()
+        .branch(Update::filter_message()
+            .branch(dptree::filter(|m: Message| { m.chat.is_private() })
+                .branch(Update::filter_message().filter_command::<UserCommand>().chain(tracked("user_cmd", dptree::endpoint(user_cmd))))
+                .branch(Update::filter_message()).chain(tracked("user_msg", dptree::endpoint(user_msg))))
+            .branch(dptree::filter(move |m: Message| { superchats.contains(&m.chat.id) })
+                .branch(Update::filter_message().filter_command::<SupportCommand>().chain(tracked("superchat_cmd", dptree::endpoint(superchat_cmd))))
+                .branch(Update::filter_message()).chain(tracked("superchat_msg", dptree::endpoint(superchat_msg)))))
+        .branch(Update::filter_edited_message()
+            .branch(dptree::filter(|m: Message| { m.chat.is_private() })
+                .branch(Update::filter_message().filter_command::<UserCommand>().endpoint(noop))
+                .branch(Update::filter_message()).chain(tracked("user_update", dptree::endpoint(user_update))))
+            .branch(dptree::filter(move |m: Message| { edited_superchats.contains(&m.chat.id) })
+                .branch(Update::filter_message().filter_command::<SupportCommand>().endpoint(noop))
+                .branch(Update::filter_message()).chain(tracked("superchat_update", dptree::endpoint(superchat_update)))))
+        .branch(Update::filter_callback_query()
+            .branch(dptree::filter_map(|q: CallbackQuery| q.data.and_then(|d| survey::SurveyCallback::parse(&d)))
+                .chain(tracked("survey_callback", dptree::endpoint(survey::callback))))
+            .branch(dptree::filter_map(|q: CallbackQuery| q.data.and_then(|d| language::LanguageCallback::parse(&d)))
+                .chain(tracked("language_callback", dptree::endpoint(language::callback)))))
+}
+
 async fn noop() -> HandlerResult {
     Ok(())
 }
+
+/// Topic title with user tags and SLA breach mark
+async fn topic_name(cfg: &TelegramConfig, db: &dyn Database, user: &UserEntity) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+    let mut name = format!("#T{:#06} {} {}", user.id, user.first_name.as_deref().unwrap_or(""), user.last_name.as_deref().unwrap_or(""));
+    let tags = db.get_tags(user).await?;
+    if !tags.is_empty() {
+        name = format!("[{}] {}", tags.iter().map(|t| t.tag.as_str()).collect::<Vec<_>>().join(", "), name);
+    }
+    if let Some(ref sla) = cfg.sla {
+        if db.get_open_conversation(user).await?.is_some_and(|c| c.breached_at.is_some()) {
+            name = format!("{}{}", sla.breach_prefix, name);
+        }
+    }
+    // topic names are limited to 128 characters
+    Ok(name.chars().take(128).collect())
+}
+
+fn topic_link(cfg: &TelegramConfig, user: &UserEntity) -> String {
+    format!("https://t.me/c/{}/{}", -(cfg.superchat_of(user).0 + 1_000_000_000_000), user.topic)
+}
+
+async fn rename_topic(bot: &Bot, cfg: &TelegramConfig, db: &dyn Database, user: &UserEntity) -> HandlerResult {
+    bot.edit_forum_topic(cfg.superchat_of(user), ThreadId(MessageId(user.topic as i32)))
+        .name(topic_name(cfg, db, user).await?)
+        .await?;
+    Ok(())
+}
+
+/// Endpoint that counts errors of `handler` under `endpoint` name. Handlers calling each other are counted once
+fn tracked(endpoint: &'static str, handler: UpdateHandler<Box<dyn std::error::Error + Send + Sync + 'static>>) -> UpdateHandler<Box<dyn std::error::Error + Send + Sync + 'static>> {
+    dptree::from_fn(move |deps, cont| {
+        let handler = handler.clone();
+        async move {
+            let result = handler.execute(deps, cont).await;
+            if let ControlFlow::Break(Err(_)) = result {
+                metrics::handler_error(endpoint);
+            }
+            result
+        }
+    })
+}
+
 async fn update_user_info_msg(bot: &Bot, mut entity: UserEntity, cfg: TelegramConfig, db: Arc<Box<dyn Database>>, loc: Arc<LocalizationBundle>) -> Result<UserEntity, Box<dyn std::error::Error + Send + Sync>> {
     let bot = bot.parse_mode(ParseMode::Html);
-    let mut msg = loc.localize(None, CommonMessages::InfoHeader {
+    let stats = db.get_user_stats(&entity).await?;
+    let agent = match db.get_open_conversation(&entity).await?.and_then(|c| c.staff_id) {
+        Some(staff) => Some(signature::staff_name(&**db, staff).await?),
+        None => None,
+    };
+    let mut msg = loc.localize(cfg.staff_lang(cfg.superchat_of(&entity)), CommonMessages::InfoHeader {
         lang: entity.lang_code.clone(),
         last_name: entity.last_name.clone().map(|s| s.to_string()),
         first_name: entity.first_name.clone().map(|s| s.to_string()),
         id: entity.telegram_id,
+        username: entity.username.clone(),
+        premium: entity.is_premium,
+        first_contact: entity.first_contact,
+        source: entity.start_payload.clone(),
+        incoming: stats.incoming,
+        outgoing: stats.outgoing,
+        tickets: stats.conversations,
+        banned: entity.banned,
+        agent,
+        tags: db.get_tags(&entity).await?.into_iter().map(|t| t.tag).collect(),
     });
     for note in db.get_notes(&entity).await? {
-        msg = format!("{}<b>{}: </b><code>{}</code>\n", msg, sanitize(note.key), sanitize(note.value));
+        msg.push_str(&notes::render(&loc, cfg.staff_lang(cfg.superchat_of(&entity)), &note));
     }
 
     if let Some(id) = entity.info_message {
-        bot.edit_message_text(ChatId(cfg.superchat), MessageId(id as i32), &msg)
-            .await?;
+        match bot.edit_message_text(cfg.superchat_of(&entity), MessageId(id as i32), &msg).await {
+            // info is refreshed on events that don't always change it
+            Err(RequestError::Api(ApiError::MessageNotModified)) => {}
+            result => { result?; }
+        }
         Ok(entity)
     } else {
-        let msg = bot.send_message(ChatId(cfg.superchat), &msg).message_thread_id(ThreadId(MessageId(entity.topic as i32))).await?;
-        bot.pin_chat_message(ChatId(cfg.superchat), msg.id).await?;
+        let msg = bot.send_message(cfg.superchat_of(&entity), &msg).message_thread_id(ThreadId(MessageId(entity.topic as i32))).await?;
+        bot.pin_chat_message(cfg.superchat_of(&entity), msg.id).await?;
         entity.info_message = Some(msg.id.0 as i64);
         db.update_user(entity.clone()).await?;
         Ok(entity)
     }
 }
 
-async fn user_cmd(bot: Bot, msg: Message, loc: Arc<LocalizationBundle>, cmd: UserCommand) -> HandlerResult {
-    use teloxide::utils::command::BotCommands;
+/// Creates user with topic in superchat picked by routes
+async fn create_user(bot: &Bot, cfg: &TelegramConfig, db: &Arc<Box<dyn Database>>, loc: &Arc<LocalizationBundle>, msg: &Message, start: Option<String>) -> Result<UserEntity, Box<dyn std::error::Error + Send + Sync>> {
+    let lang_code = msg.from().and_then(|l| l.language_code.clone());
+    let superchat = routing::superchat(cfg, lang_code.as_deref(), start.as_deref(), &[]);
+    // real name needs user id, topic is renamed after insert
+    let name = format!("#T {} {}", msg.chat.first_name().unwrap_or(""), msg.chat.last_name().unwrap_or(""));
+    let topic = bot.create_forum_topic(superchat, name, TOPIC_COLOR, "").await?;
+    let entity = InsertUserEntity {
+        telegram_id: msg.chat.id.0,
+        topic: topic.thread_id.0.0 as i64,
+        info_message: None,
+        first_name: msg.chat.first_name().map(|s| s.to_string()),
+        last_name: msg.chat.last_name().map(|s| s.to_string()),
+        lang_code,
+        username: msg.from().and_then(|u| u.username.clone()),
+        is_premium: msg.from().is_some_and(|u| u.is_premium),
+        first_contact: Some(msg.date.timestamp()),
+        superchat: Some(superchat.0),
+        start_payload: start,
+    };
+    let en = db.insert_user(entity).await?;
+    metrics::new_user();
+    rename_topic(bot, cfg, &***db, &en).await?;
+    profile::post_photo(bot, cfg, &en).await?;
+    update_user_info_msg(bot, en, cfg.clone(), db.clone(), loc.clone()).await
+}
 
-    let user_lang = msg.from().and_then(|u| u.language_code.clone());
+async fn user_cmd(bot: Bot, msg: Message, cfg: TelegramConfig, db: Arc<Box<dyn Database>>, loc: Arc<LocalizationBundle>, cmd: UserCommand) -> HandlerResult {
+    let user = db.get_user_by_tg_id(UserId(msg.chat.id.0 as u64)).await?;
+    let user_lang = language::user_lang(user.as_ref(), msg.from());
     match cmd {
-        UserCommand::Help => bot.send_message(msg.chat.id, UserCommand::descriptions().to_string()).await?,
-        UserCommand::Start => bot.send_message(msg.chat.id, loc.localize(user_lang, CommonMessages::Welcome)).await?,
-        UserCommand::Faq => bot.send_message(msg.chat.id, loc.localize(user_lang, CommonMessages::Faq)).await?,
+        UserCommand::Help => bot.send_message(msg.chat.id, commands::help(&loc, user_lang)).await?,
+        UserCommand::Start(payload) => {
+            let payload = payload.trim();
+            if !payload.is_empty() {
+                // deep link payload can pick superchat, so topic is created right away
+                let user = match user {
+                    Some(user) => user,
+                    None => create_user(&bot, &cfg, &db, &loc, &msg, Some(payload.to_string())).await?,
+                };
+                start::apply(&cfg.start, &**db, &user, payload, msg.date.timestamp()).await?;
+                let user = UserEntity { start_payload: Some(payload.to_string()), ..user };
+                update_user_info_msg(&bot, user, cfg.clone(), db.clone(), loc.clone()).await?;
+            }
+            send_localized(&bot, msg.chat.id, loc.localize_message(user_lang, CommonMessages::Welcome)).await?
+        }
+        UserCommand::Faq => send_localized(&bot, msg.chat.id, loc.localize_message(user_lang, CommonMessages::Faq)).await?,
+        UserCommand::Language => {
+            // choice is stored on user, so topic is created right away
+            let user = match user {
+                Some(user) => user,
+                None => create_user(&bot, &cfg, &db, &loc, &msg, None).await?,
+            };
+            language::prompt(&bot, &loc, &user).await?;
+            return Ok(());
+        }
     };
     Ok(())
 }
 
-async fn superchat_cmd(bot: Bot, msg: Message, loc: Arc<LocalizationBundle>, cfg: TelegramConfig, db: Arc<Box<dyn Database>>, cmd: SupportCommand) -> HandlerResult {
+async fn superchat_cmd(bot: Bot, msg: Message, loc: Arc<LocalizationBundle>, cfg: TelegramConfig, db: Arc<Box<dyn Database>>, tr: Option<Arc<Translation>>, cmd: SupportCommand) -> HandlerResult {
+    let staff_lang = cfg.staff_lang(msg.chat.id);
+    if let SupportCommand::Alias { ref alias } = cmd {
+        let Some(staff) = msg.from() else {
+            return Ok(());
+        };
+        let reply = if alias.trim().is_empty() {
+            db.delete_staff_alias(staff.id).await?;
+            StaffMessages::AliasDeleted
+        } else {
+            db.save_staff_alias(InsertStaffAliasEntity { telegram_id: staff.id.0 as i64, alias: alias.trim().to_string() }).await?;
+            StaffMessages::AliasSaved
+        };
+        let reply = loc.localize(staff_lang, reply);
+        MessageBuilder::new(bot.send_message(msg.chat.id, reply))
+            .with(msg.thread_id, |t, v| v.message_thread_id(t))
+            .build()
+            .await?;
+        return Ok(());
+    }
+    if let SupportCommand::Tagged { ref tag } = cmd {
+        MessageBuilder::new(bot.parse_mode(ParseMode::Html).send_message(msg.chat.id, tags::tagged(&cfg, &**db, &loc, staff_lang, tag).await?))
+            .with(msg.thread_id, |t, v| v.message_thread_id(t))
+            .build()
+            .await?;
+        return Ok(());
+    }
+    if let SupportCommand::Reloadloc = cmd {
+        let reply = match localization::reload(&loc).await {
+            Ok(report) => {
+                commands::register(&bot, &cfg, &loc).await?;
+                let reply = loc.localize(staff_lang, StaffMessages::LocalizationReloaded { languages: loc.languages().join(", ") });
+                if report.is_empty() { reply } else { format!("{}\n\n{}", reply, report) }
+            }
+            Err(e) => loc.localize(staff_lang, StaffMessages::LocalizationReloadFailed { error: e.to_string() }),
+        };
+        MessageBuilder::new(bot.send_message(msg.chat.id, reply))
+            .with(msg.thread_id, |t, v| v.message_thread_id(t))
+            .build()
+            .await?;
+        return Ok(());
+    }
     let Some(topic) = msg.thread_id else {
         return Ok(());
     };
-    let Some(user) = db.get_user_by_topic(topic.0.0 as i64).await? else {
+    let Some(user) = db.get_user_by_topic(msg.chat.id.0, topic.0.0 as i64).await? else {
         return Ok(());
     };
 
     match cmd {
         SupportCommand::Setnote { key, value } => {
-            db.save_note(InsertNoteEntity { user_id: user.id, key: key.trim().to_string(), value: value.trim().to_string() }).await?;
+            let reply = notes::set(&**db, &loc, staff_lang, &user, &msg, key.trim(), value.trim()).await?;
             update_user_info_msg(&bot, user, cfg.clone(), db.clone(), loc.clone()).await?;
-            bot.send_message(ChatId(cfg.superchat), "Note saved")
+            bot.parse_mode(ParseMode::Html)
+                .send_message(msg.chat.id, reply)
                 .message_thread_id(topic)
                 .await?;
         }
         SupportCommand::Delnote { key } => {
-            db.delete_note(&user, key.trim()).await?;
+            let reply = notes::delete(&**db, &loc, staff_lang, &user, &msg, key.trim()).await?;
             update_user_info_msg(&bot, user, cfg.clone(), db.clone(), loc.clone()).await?;
             bot.parse_mode(ParseMode::Html)
-                .send_message(ChatId(cfg.superchat), "Note deleted")
+                .send_message(msg.chat.id, reply)
+                .message_thread_id(topic)
+                .await?;
+        }
+        SupportCommand::Notehistory { key } => {
+            bot.parse_mode(ParseMode::Html)
+                .send_message(msg.chat.id, notes::history(&**db, &loc, staff_lang, &user, key.trim()).await?)
+                .message_thread_id(topic)
+                .await?;
+        }
+        SupportCommand::Close => {
+            let Some(open) = db.get_open_conversation(&user).await? else {
+                bot.send_message(msg.chat.id, loc.localize(staff_lang, StaffMessages::NoOpenConversation))
+                    .message_thread_id(topic)
+                    .await?;
+                return Ok(());
+            };
+            if conversation::close(&**db, open.clone(), msg.date.timestamp()).await? {
+                sla::resolve(&bot, &cfg, &**db, &user).await?;
+            }
+            survey::send_prompt(&bot, &**db, &loc, &user, &open).await?;
+            bot.send_message(msg.chat.id, loc.localize(staff_lang, StaffMessages::ConversationClosed))
+                .message_thread_id(topic)
+                .await?;
+            update_user_info_msg(&bot, user, cfg.clone(), db.clone(), loc.clone()).await?;
+        }
+        SupportCommand::Ban | SupportCommand::Unban => {
+            let banned = matches!(cmd, SupportCommand::Ban);
+            let reply = if banned { StaffMessages::UserBanned } else { StaffMessages::UserUnbanned };
+            let user = UserEntity { banned, ..user };
+            db.update_user(user.clone()).await?;
+            bot.send_message(msg.chat.id, loc.localize(staff_lang, reply))
+                .message_thread_id(topic)
+                .await?;
+            update_user_info_msg(&bot, user, cfg.clone(), db.clone(), loc.clone()).await?;
+        }
+        SupportCommand::Tag { tags } => {
+            let reply = tags::add(&**db, &loc, staff_lang, &user, &tags).await?;
+            if !routing::reroute(&bot, &cfg, &db, &loc, user.clone()).await? {
+                rename_topic(&bot, &cfg, &**db, &user).await?;
+                update_user_info_msg(&bot, user, cfg.clone(), db.clone(), loc.clone()).await?;
+            }
+            bot.parse_mode(ParseMode::Html)
+                .send_message(msg.chat.id, reply)
                 .message_thread_id(topic)
                 .await?;
         }
+        SupportCommand::Untag { tags } => {
+            let reply = tags::remove(&**db, &loc, staff_lang, &user, &tags).await?;
+            if !routing::reroute(&bot, &cfg, &db, &loc, user.clone()).await? {
+                rename_topic(&bot, &cfg, &**db, &user).await?;
+                update_user_info_msg(&bot, user, cfg.clone(), db.clone(), loc.clone()).await?;
+            }
+            bot.parse_mode(ParseMode::Html)
+                .send_message(msg.chat.id, reply)
+                .message_thread_id(topic)
+                .await?;
+        }
+        // handled before looking up topic user
+        SupportCommand::Alias { .. } | SupportCommand::Tagged { .. } | SupportCommand::Reloadloc => {}
+        SupportCommand::Tr { text } => {
+            if text.trim().is_empty() {
+                return Ok(());
+            }
+            let reply = match translate::for_user(&loc, tr.as_deref(), &user, text.trim(), staff_lang.clone()).await {
+                Ok(translated) => {
+                    let lang = user.lang_code.clone().unwrap_or_default();
+                    let tx = translate::send(&bot, &cfg, &**db, &loc, &user, &msg, &translated).await?;
+                    record_outgoing(&bot, &cfg, &db, &loc, user, &msg, tx).await?;
+                    loc.localize(staff_lang, StaffMessages::TranslationSent { lang, text: translated })
+                }
+                Err(reply) => reply,
+            };
+            bot.send_message(msg.chat.id, reply)
+                .message_thread_id(topic)
+                .await?;
+        }
+        SupportCommand::Internal { text } => {
+            if !text.trim().is_empty() {
+                internal::mark(&bot, &msg).await?;
+            }
+        }
         SupportCommand::Notes => {
-            let mut msg = "User notes:\n".to_string();
+            let mut reply = format!("{}\n\n", loc.localize(staff_lang.clone(), StaffMessages::Notes));
             for note in db.get_notes(&user).await? {
-                msg = format!("{}\n<b>{}:</b> <code>{}</code>", msg, note.key, note.value);
+                reply.push_str(&notes::render(&loc, staff_lang.clone(), &note));
             }
             bot.parse_mode(ParseMode::Html)
-                .send_message(ChatId(cfg.superchat), msg)
+                .send_message(msg.chat.id, reply)
                 .message_thread_id(topic)
                 .await?;
         }
@@ -158,323 +507,109 @@ async fn superchat_cmd(bot: Bot, msg: Message, loc: Arc<LocalizationBundle>, cfg
     Ok(())
 }
 
-async fn user_msg(bot: Bot, msg: Message, cfg: TelegramConfig, db: Arc<Box<dyn Database>>, loc: Arc<LocalizationBundle>) -> HandlerResult {
+async fn user_msg(bot: Bot, msg: Message, cfg: TelegramConfig, db: Arc<Box<dyn Database>>, loc: Arc<LocalizationBundle>, tr: Option<Arc<Translation>>) -> HandlerResult {
     let user = match db.get_user_by_tg_id(UserId(msg.chat.id.0 as u64)).await? {
-        None => {
-            let name = format!("#T {} {}", msg.chat.first_name().unwrap_or(""), msg.chat.last_name().unwrap_or(""));
-            let topic = bot.create_forum_topic(ChatId(cfg.superchat), name, 16766590, "").await?;
-            let entity = InsertUserEntity {
-                telegram_id: msg.chat.id.0,
-                topic: topic.thread_id.0.0 as i64,
-                info_message: None,
-                first_name: msg.chat.first_name().map(|s| s.to_string()),
-                last_name: msg.chat.last_name().map(|s| s.to_string()),
-                lang_code: msg.from().and_then(|l| l.language_code.clone()),
-            };
-            let en = db.insert_user(entity).await?;
-            bot.edit_forum_topic(ChatId(cfg.superchat), topic.thread_id)
-                .name(format!("#T{:#06} {} {}", en.id, msg.chat.first_name().unwrap_or(""), msg.chat.last_name().unwrap_or("")))
-                .await?;
-            update_user_info_msg(&bot, en, cfg.clone(), db.clone(), loc.clone()).await?
-        }
+        None => create_user(&bot, &cfg, &db, &loc, &msg, None).await?,
+        // banned users can't cause any activity in topic, profile changes included
+        Some(user) if user.banned => return Ok(()),
         Some(user) => if user.info_message.is_none() {
             update_user_info_msg(&bot, user, cfg.clone(), db.clone(), loc.clone()).await?
         } else {
-            user
+            profile::sync(&bot, &cfg, &db, &loc, user, &msg).await?
         },
     };
-    let tx = match msg.kind {
-        MessageKind::Common(ref a) => match a.media_kind.clone() {
-            MediaKind::Animation(obj) => {
-                MessageBuilder::new(bot.send_animation(ChatId(cfg.superchat), InputFile::file_id(obj.animation.file.id)))
-                    .with(obj.caption, |o, v| { v.caption(o) })
-                    .build()
-                    .caption_entities(obj.caption_entities)
-                    .has_spoiler(obj.has_media_spoiler)
-                    .message_thread_id(ThreadId(MessageId(user.topic as i32)))
-                    .await?
-            }
-            MediaKind::Audio(audio) => {
-                MessageBuilder::new(bot.send_audio(ChatId(cfg.superchat), InputFile::file_id(audio.audio.file.id)))
-                    .with(audio.caption, |o, v| v.caption(o))
-                    .build()
-                    .caption_entities(audio.caption_entities)
-                    .message_thread_id(ThreadId(MessageId(user.topic as i32)))
-                    .await?
-            }
-            MediaKind::Contact(contact) => {
-                MessageBuilder::new(bot.send_contact(ChatId(cfg.superchat), contact.contact.phone_number, contact.contact.first_name))
-                    .with(contact.contact.last_name, |o, v| v.last_name(o))
-                    .with(contact.contact.vcard, |o, v| v.vcard(o))
-                    .build()
-                    .message_thread_id(ThreadId(MessageId(user.topic as i32)))
-                    .await?
-            }
-            MediaKind::Document(doc) => {
-                MessageBuilder::new(bot.send_document(ChatId(cfg.superchat), InputFile::file_id(doc.document.file.id)))
-                    .with(doc.caption, |o, v| v.caption(o))
-                    .build()
-                    .caption_entities(doc.caption_entities)
-                    .message_thread_id(ThreadId(MessageId(user.topic as i32)))
-                    .await?
-            }
-            MediaKind::Venue(v) => {
-                MessageBuilder::new(bot.send_venue(ChatId(cfg.superchat), v.venue.location.latitude, v.venue.location.longitude, v.venue.title, v.venue.address))
-                    .with(v.venue.foursquare_id, |o, v| v.foursquare_id(o))
-                    .with(v.venue.foursquare_type, |o, v| v.foursquare_type(o))
-                    .with(v.venue.google_place_id, |o, v| v.google_place_id(o))
-                    .with(v.venue.google_place_type, |o, v| v.google_place_type(o))
-                    .build()
-                    .message_thread_id(ThreadId(MessageId(user.topic as i32)))
-                    .await?
-            }
-            MediaKind::Location(loc) => {
-                MessageBuilder::new(bot.send_location(ChatId(cfg.superchat), loc.location.latitude, loc.location.longitude))
-                    .with(loc.location.horizontal_accuracy, |o, v| v.horizontal_accuracy(o))
-                    .with(loc.location.live_period, |o, v| v.live_period(o.seconds()))
-                    .with(loc.location.heading, |o, v| v.heading(o))
-                    .with(loc.location.proximity_alert_radius, |o, v| v.proximity_alert_radius(o))
-                    .build()
-                    .message_thread_id(ThreadId(MessageId(user.topic as i32)))
-                    .await?
-            }
-            MediaKind::Photo(p) => {
-                let Some(photo) = p.photo.iter().max_by_key(|a| a.width * a.height) else {
-                    return Ok(())
-                };
-                MessageBuilder::new(bot.send_photo(ChatId(cfg.superchat), InputFile::file_id(photo.file.id.clone())))
-                    .with(p.caption, |o, v| v.caption(o))
-                    .build()
-                    .caption_entities(p.caption_entities)
-                    .message_thread_id(ThreadId(MessageId(user.topic as i32)))
-                    .await?
-            }
-            MediaKind::Sticker(s) => {
-                bot.send_sticker(ChatId(cfg.superchat), InputFile::file_id(s.sticker.file.id))
-                    .message_thread_id(ThreadId(MessageId(user.topic as i32)))
-                    .await?
-            }
-            MediaKind::Text(t) => {
-                bot.send_message(ChatId(cfg.superchat), t.text)
-                    .entities(t.entities)
-                    .message_thread_id(ThreadId(MessageId(user.topic as i32)))
-                    .await?
-            }
-            MediaKind::Video(v) => {
-                MessageBuilder::new(bot.send_video(ChatId(cfg.superchat), InputFile::file_id(v.video.file.id)))
-                    .with(v.caption, |o, v| v.caption(o))
-                    .build()
-                    .caption_entities(v.caption_entities)
-                    .has_spoiler(v.has_media_spoiler)
-                    .message_thread_id(ThreadId(MessageId(user.topic as i32)))
-                    .await?
-            }
-            MediaKind::VideoNote(v) => {
-                bot.send_video_note(ChatId(cfg.superchat), InputFile::file_id(v.video_note.file.id))
-                    .length(v.video_note.length)
-                    .duration(v.video_note.duration.seconds())
-                    .message_thread_id(ThreadId(MessageId(user.topic as i32)))
-                    .await?
-            }
-            MediaKind::Voice(v) => {
-                MessageBuilder::new(bot.send_voice(ChatId(cfg.superchat), InputFile::file_id(v.voice.file.id)))
-                    .with(v.caption, |c, v| v.caption(c))
-                    .build()
-                    .caption_entities(v.caption_entities)
-                    .message_thread_id(ThreadId(MessageId(user.topic as i32)))
-                    .await?
-            }
-
-            MediaKind::Game(_) => {
-                bot.send_message(msg.chat.id, loc.localize(msg.from().and_then(|u| u.language_code.clone()), CommonMessages::GamesNotSupported)).await?;
-                return Ok(());
-            }
-            MediaKind::Poll(_) => {
-                bot.send_message(msg.chat.id, loc.localize(msg.from().and_then(|u| u.language_code.clone()), CommonMessages::PollsNotSupported)).await?;
+    if survey::comment(&bot, &cfg, &**db, &loc, &user, &msg).await? {
+        return Ok(());
+    }
+    let topic = Destination::topic(cfg.superchat_of(&user), user.topic);
+    // user already got unsupported notice, auto reply would be a second answer
+    let (tx, notified) = match relay::send(&bot, &msg, topic, None).await? {
+        Some(tx) => (tx, false),
+        None => {
+            relay::unsupported(&bot, &loc, &msg, user.lang_code.clone()).await?;
+            let Some(tx) = relay::placeholder(&bot, &loc, &msg, topic, cfg.staff_lang(topic.chat)).await? else {
                 return Ok(());
-            }
-            _ => return Ok(())
+            };
+            (tx, true)
         }
-        _ => return Ok(())
     };
-    db.insert_message(InsertMessageEntity::incoming(&user, &msg, tx.id)).await?;
-    bot.send_message(msg.chat.id, loc.localize(msg.from().and_then(|l| l.language_code.clone()), CommonMessages::UserReply)).await?;
+    db.insert_message(InsertMessageEntity::incoming(&user, &msg, tx)).await?;
+    metrics::message_incoming(media_kind_name(&msg));
+    if conversation::incoming(&**db, &user, &msg).await? {
+        update_user_info_msg(&bot, user.clone(), cfg.clone(), db.clone(), loc.clone()).await?;
+    }
+    if !notified {
+        send_localized(&bot, msg.chat.id, loc.localize_message(user.lang_code.clone(), CommonMessages::UserReply)).await?;
+    }
+    // translation is the slowest step, user gets auto reply without waiting for translator
+    if let Some(ref tr) = tr {
+        translate::incoming(&bot, &cfg, &loc, tr, &user, &msg, topic).await;
+    }
     Ok(())
 }
 
-async fn superchat_msg(bot: Bot, msg: Message, db: Arc<Box<dyn Database>>, loc: Arc<LocalizationBundle>) -> HandlerResult {
+async fn superchat_msg(bot: Bot, msg: Message, cfg: TelegramConfig, db: Arc<Box<dyn Database>>, loc: Arc<LocalizationBundle>) -> HandlerResult {
     let Some(topic) = msg.thread_id else {
         return Ok(());
     };
-    let Some(user) = db.get_user_by_topic(topic.0.0 as i64).await? else {
+    let Some(user) = db.get_user_by_topic(msg.chat.id.0, topic.0.0 as i64).await? else {
         return Ok(());
     };
-    let uid = UserId(user.telegram_id as u64);
-    let tx = match msg.kind {
-        MessageKind::Common(ref a) => match a.media_kind.clone() {
-            MediaKind::Animation(obj) => {
-                MessageBuilder::new(bot.send_animation(uid, InputFile::file_id(obj.animation.file.id)))
-                    .with(obj.caption, |o, v| { v.caption(o) })
-                    .build()
-                    .caption_entities(obj.caption_entities)
-                    .has_spoiler(obj.has_media_spoiler)
-                    .await?
-            }
-            MediaKind::Audio(audio) => {
-                MessageBuilder::new(bot.send_audio(uid, InputFile::file_id(audio.audio.file.id)))
-                    .with(audio.caption, |o, v| v.caption(o))
-                    .build()
-                    .caption_entities(audio.caption_entities)
-                    .await?
-            }
-            MediaKind::Contact(contact) => {
-                MessageBuilder::new(bot.send_contact(uid, contact.contact.phone_number, contact.contact.first_name))
-                    .with(contact.contact.last_name, |o, v| v.last_name(o))
-                    .with(contact.contact.vcard, |o, v| v.vcard(o))
-                    .build()
-                    .await?
-            }
-            MediaKind::Document(doc) => {
-                MessageBuilder::new(bot.send_document(uid, InputFile::file_id(doc.document.file.id)))
-                    .with(doc.caption, |o, v| v.caption(o))
-                    .build()
-                    .caption_entities(doc.caption_entities)
-                    .await?
-            }
-            MediaKind::Venue(v) => {
-                MessageBuilder::new(bot.send_venue(uid, v.venue.location.latitude, v.venue.location.longitude, v.venue.title, v.venue.address))
-                    .with(v.venue.foursquare_id, |o, v| v.foursquare_id(o))
-                    .with(v.venue.foursquare_type, |o, v| v.foursquare_type(o))
-                    .with(v.venue.google_place_id, |o, v| v.google_place_id(o))
-                    .with(v.venue.google_place_type, |o, v| v.google_place_type(o))
-                    .build()
-                    .await?
-            }
-            MediaKind::Location(loc) => {
-                MessageBuilder::new(bot.send_location(uid, loc.location.latitude, loc.location.longitude))
-                    .with(loc.location.horizontal_accuracy, |o, v| v.horizontal_accuracy(o))
-                    .with(loc.location.live_period, |o, v| v.live_period(o.seconds()))
-                    .with(loc.location.heading, |o, v| v.heading(o))
-                    .with(loc.location.proximity_alert_radius, |o, v| v.proximity_alert_radius(o))
-                    .build()
-                    .await?
-            }
-            MediaKind::Photo(p) => {
-                let Some(photo) = p.photo.iter().max_by_key(|a| a.width * a.height) else {
-                    return Ok(())
-                };
-                MessageBuilder::new(bot.send_photo(uid, InputFile::file_id(photo.file.id.clone())))
-                    .with(p.caption, |o, v| v.caption(o))
-                    .build()
-                    .caption_entities(p.caption_entities)
-                    .await?
-            }
-            MediaKind::Sticker(s) => {
-                bot.send_sticker(uid, InputFile::file_id(s.sticker.file.id))
-                    .await?
-            }
-            MediaKind::Text(t) => {
-                bot.send_message(uid, t.text)
-                    .entities(t.entities)
-                    .await?
-            }
-            MediaKind::Video(v) => {
-                MessageBuilder::new(bot.send_video(uid, InputFile::file_id(v.video.file.id)))
-                    .with(v.caption, |o, v| v.caption(o))
-                    .build()
-                    .caption_entities(v.caption_entities)
-                    .has_spoiler(v.has_media_spoiler)
-                    .await?
-            }
-            MediaKind::VideoNote(v) => {
-                bot.send_video_note(uid, InputFile::file_id(v.video_note.file.id))
-                    .length(v.video_note.length)
-                    .duration(v.video_note.duration.seconds())
-                    .await?
-            }
-            MediaKind::Voice(v) => {
-                MessageBuilder::new(bot.send_voice(uid, InputFile::file_id(v.voice.file.id)))
-                    .with(v.caption, |c, v| v.caption(c))
-                    .build()
-                    .caption_entities(v.caption_entities)
-                    .await?
-            }
-
-            MediaKind::Game(_) => {
-                bot.send_message(msg.chat.id, loc.localize(msg.from().and_then(|u| u.language_code.clone()), CommonMessages::GamesNotSupported)).await?;
-                return Ok(());
-            }
-            MediaKind::Poll(_) => {
-                bot.send_message(msg.chat.id, loc.localize(msg.from().and_then(|u| u.language_code.clone()), CommonMessages::PollsNotSupported)).await?;
-                return Ok(());
-            }
-            _ => return Ok(())
-        }
-        _ => return Ok(())
+    if internal::is_internal(&cfg.internal, &user, &msg) {
+        internal::mark(&bot, &msg).await?;
+        return Ok(());
+    }
+    let signature = Signature::of(&cfg.signature, &**db, &loc, &user, &msg).await?;
+    let Some(tx) = relay::send(&bot, &msg, Destination::chat(ChatId(user.telegram_id)), signature.as_ref()).await? else {
+        relay::unsupported(&bot, &loc, &msg, cfg.staff_lang(msg.chat.id)).await?;
+        return Ok(());
     };
-    db.insert_message(InsertMessageEntity::outgoing(&user, &msg, tx.id)).await?;
+    record_outgoing(&bot, &cfg, &db, &loc, user, &msg, tx).await
+}
+
+/// Records staff message delivered to user as `tx`, updates conversation, SLA mark and info message
+async fn record_outgoing(bot: &Bot, cfg: &TelegramConfig, db: &Arc<Box<dyn Database>>, loc: &Arc<LocalizationBundle>, user: UserEntity, msg: &Message, tx: MessageId) -> HandlerResult {
+    db.insert_message(InsertMessageEntity::outgoing(&user, msg, tx)).await?;
+    metrics::message_outgoing(media_kind_name(msg));
+    let reply = conversation::outgoing(&***db, &user, msg).await?;
+    if reply.breached {
+        sla::resolve(bot, cfg, &***db, &user).await?;
+    }
+    if reply.assigned {
+        update_user_info_msg(bot, user, cfg.clone(), db.clone(), loc.clone()).await?;
+    }
     bot.set_message_reaction(msg.chat.id, msg.id, vec![ReactionType::emoji(ReactionEmoji::Lightning)]).await?;
     Ok(())
 }
 
-async fn user_update(bot: Bot, edited: Message, cfg: TelegramConfig, db: Arc<Box<dyn Database>>, loc: Arc<LocalizationBundle>) -> HandlerResult {
+async fn user_update(bot: Bot, edited: Message, cfg: TelegramConfig, db: Arc<Box<dyn Database>>, loc: Arc<LocalizationBundle>, tr: Option<Arc<Translation>>) -> HandlerResult {
     let Some(user) = db.get_user_by_tg_id(UserId(edited.chat.id.0 as u64)).await? else {
         return Ok(())
     };
     let Some(msg) = db.get_message(&user, MessageType::Incoming, edited.id.0 as i64).await? else {
-        return user_msg(bot, edited, cfg, db, loc).await;
+        return user_msg(bot, edited, cfg, db, loc, tr).await;
     };
-    let mid = MessageId(msg.tx_msg_id as i32);
     let original = msg.rx_message()?;
-    if original.caption() != edited.caption() || original.caption_entities() != edited.caption_entities() {
-        MessageBuilder::new(bot.edit_message_caption(ChatId(cfg.superchat), mid))
-            .with(edited.caption(), |c, v| v.caption(c))
-            .with(edited.caption_entities(), |c, v| v.caption_entities(c.iter().cloned().collect::<Vec<_>>()))
-            .build()
-            .await?;
-    }
-    if original.text() != edited.text() {
-        if let Some(text) = edited.text() {
-            bot.edit_message_text(ChatId(cfg.superchat), mid, text).await?;
-        }
-    }
-    if original.location() != edited.location() {
-        if let Some(location) = edited.location() {
-            bot.edit_message_live_location(ChatId(cfg.superchat), mid, location.latitude, location.longitude).await?;
-        }
-    }
+    let chat = msg.tx_chat_id.map(ChatId).unwrap_or(cfg.superchat_of(&user));
+    relay::edit(&bot, &original, &edited, chat, MessageId(msg.tx_msg_id as i32), None).await?;
     Ok(())
 }
 
-async fn superchat_update(bot: Bot, edited: Message, db: Arc<Box<dyn Database>>, loc: Arc<LocalizationBundle>) -> HandlerResult {
+async fn superchat_update(bot: Bot, edited: Message, cfg: TelegramConfig, db: Arc<Box<dyn Database>>, loc: Arc<LocalizationBundle>) -> HandlerResult {
     let Some(topic) = edited.thread_id else {
         return Ok(());
     };
-    let Some(user) = db.get_user_by_topic(topic.0.0 as i64).await? else {
+    let Some(user) = db.get_user_by_topic(edited.chat.id.0, topic.0.0 as i64).await? else {
         return Ok(());
     };
+    if internal::is_internal(&cfg.internal, &user, &edited) {
+        return Ok(());
+    }
     let Some(msg) = db.get_message(&user, MessageType::Outgoing, edited.id.0 as i64).await? else {
-        return superchat_msg(bot, edited, db, loc).await;
+        return superchat_msg(bot, edited, cfg, db, loc).await;
     };
-    let mid = MessageId(msg.tx_msg_id as i32);
-    let uid = UserId(user.telegram_id as u64);
     let original = msg.rx_message()?;
-    if original.caption() != edited.caption() || original.caption_entities() != edited.caption_entities() {
-        MessageBuilder::new(bot.edit_message_caption(uid, mid))
-            .with(edited.caption(), |c, v| v.caption(c))
-            .with(edited.caption_entities(), |c, v| v.caption_entities(c.iter().cloned().collect::<Vec<_>>()))
-            .build()
-            .await?;
-    }
-    if original.text() != edited.text() {
-        if let Some(text) = edited.text() {
-            bot.edit_message_text(uid, mid, text).await?;
-        }
-    }
-    if original.location() != edited.location() {
-        if let Some(location) = edited.location() {
-            bot.edit_message_live_location(uid, mid, location.latitude, location.longitude).await?;
-        }
-    }
+    let signature = Signature::of(&cfg.signature, &**db, &loc, &user, &edited).await?;
+    relay::edit(&bot, &original, &edited, ChatId(user.telegram_id), MessageId(msg.tx_msg_id as i32), signature.as_ref()).await?;
     Ok(())
 }
\ No newline at end of file
@@ -0,0 +1,149 @@
+use chrono::{DateTime, NaiveDate, NaiveTime};
+use teloxide::prelude::Message;
+use crate::database::{Database, InsertNoteEntity, InsertNoteHistoryEntity, NoteEntity, NoteHistoryEntity, NoteType, UserEntity};
+use crate::localization::{escape, LocalizationBundle, StaffMessages};
+use crate::telegram::signature::staff_name;
+
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+const DATE_FORMAT: &str = "%Y-%m-%d";
+
+/// Handles `/setnote key value` and `/setnote key:type value`. Returns HTML reply for staff
+pub async fn set(db: &dyn Database, loc: &LocalizationBundle, lang: Option<String>, user: &UserEntity, msg: &Message, key: &str, value: &str) -> Result<String> {
+    let (key, type_) = match key.split_once(':') {
+        Some((key, name)) => match parse_type(name) {
+            Some(type_) if is_valid(type_, value) => (key, type_),
+            Some(type_) => return Ok(loc.localize(lang, StaffMessages::NoteInvalidValue { type_name: type_name(type_).to_string() })),
+            None => return Ok(loc.localize(lang, StaffMessages::NoteUnknownType {
+                types: NoteType::ALL.iter().map(|t| type_name(*t)).collect::<Vec<_>>().join(", "),
+            })),
+        },
+        None => (key, infer(value)),
+    };
+    let staff = msg.from().map(|u| u.id.0 as i64);
+    let previous = db.get_note_history(user, key).await?.pop();
+    db.save_note(InsertNoteEntity { user_id: user.id, key: key.to_string(), value: value.to_string(), type_: type_ as i16 }).await?;
+    db.insert_note_history(InsertNoteHistoryEntity {
+        user_id: user.id,
+        key: key.to_string(),
+        value: Some(value.to_string()),
+        type_: type_ as i16,
+        staff_id: staff,
+        created_at: msg.date.timestamp(),
+    }).await?;
+    Ok(match previous {
+        // let staff know they replaced someone else's note
+        Some(NoteHistoryEntity { value: Some(old), staff_id: Some(author), created_at, .. }) if Some(author) != staff => loc.localize(lang, StaffMessages::NoteReplaced {
+            old: escape(&old),
+            author: escape(&staff_name(db, author).await?),
+            time: format_time(created_at),
+        }),
+        _ => loc.localize(lang, StaffMessages::NoteSaved),
+    })
+}
+
+/// Saves note from `/start` payload, type is inferred from value
+pub async fn auto(db: &dyn Database, user: &UserEntity, key: &str, value: &str, date: i64) -> Result<()> {
+    let type_ = infer(value);
+    db.save_note(InsertNoteEntity { user_id: user.id, key: key.to_string(), value: value.to_string(), type_: type_ as i16 }).await?;
+    db.insert_note_history(InsertNoteHistoryEntity {
+        user_id: user.id,
+        key: key.to_string(),
+        value: Some(value.to_string()),
+        type_: type_ as i16,
+        staff_id: None,
+        created_at: date,
+    }).await?;
+    Ok(())
+}
+
+/// Handles `/delnote key`. Returns HTML reply for staff
+pub async fn delete(db: &dyn Database, loc: &LocalizationBundle, lang: Option<String>, user: &UserEntity, msg: &Message, key: &str) -> Result<String> {
+    db.delete_note(user, key).await?;
+    db.insert_note_history(InsertNoteHistoryEntity {
+        user_id: user.id,
+        key: key.to_string(),
+        value: None,
+        type_: NoteType::String as i16,
+        staff_id: msg.from().map(|u| u.id.0 as i64),
+        created_at: msg.date.timestamp(),
+    }).await?;
+    Ok(loc.localize(lang, StaffMessages::NoteDeleted))
+}
+
+/// Handles `/notehistory key`. Returns HTML reply for staff
+pub async fn history(db: &dyn Database, loc: &LocalizationBundle, lang: Option<String>, user: &UserEntity, key: &str) -> Result<String> {
+    let entries = db.get_note_history(user, key).await?;
+    if entries.is_empty() {
+        return Ok(loc.localize(lang, StaffMessages::NoteNoHistory { key: escape(key) }));
+    }
+    let mut reply = format!("{}\n", loc.localize(lang.clone(), StaffMessages::NoteHistory { key: escape(key) }));
+    for entry in entries {
+        let author = match entry.staff_id {
+            Some(staff) => staff_name(db, staff).await?,
+            // notes without author come from deep links
+            None => loc.localize(lang.clone(), StaffMessages::NoteDeepLink),
+        };
+        let change = match entry.value {
+            Some(value) => loc.localize(lang.clone(), StaffMessages::NoteHistorySet {
+                value: escape(&value),
+                type_name: type_name(NoteType::from_i16(entry.type_)).to_string(),
+            }),
+            None => loc.localize(lang.clone(), StaffMessages::NoteHistoryDeleted),
+        };
+        reply = format!("{}\n{} <b>{}</b>: {}", reply, format_time(entry.created_at), escape(&author), change);
+    }
+    Ok(reply)
+}
+
+/// Renders note as HTML line of user info message, dates are in staff language
+pub fn render(loc: &LocalizationBundle, lang: Option<String>, note: &NoteEntity) -> String {
+    let value = escape(&note.value);
+    let value = match NoteType::from_i16(note.type_) {
+        NoteType::String => format!("<code>{}</code>", value),
+        NoteType::Number | NoteType::Tag => value,
+        NoteType::Date => NaiveDate::parse_from_str(&note.value, DATE_FORMAT)
+            .map(|d| loc.localize(lang, StaffMessages::NoteDate { date: d.and_time(NaiveTime::MIN).and_utc().timestamp() }))
+            .unwrap_or(value),
+        NoteType::Url => format!("<a href=\"{}\">{}</a>", value, value),
+    };
+    format!("<b>{}: </b>{}\n", escape(&note.key), value)
+}
+
+fn type_name(type_: NoteType) -> &'static str {
+    match type_ {
+        NoteType::String => "string",
+        NoteType::Number => "number",
+        NoteType::Date => "date",
+        NoteType::Url => "url",
+        NoteType::Tag => "tag",
+    }
+}
+
+fn parse_type(name: &str) -> Option<NoteType> {
+    NoteType::ALL.into_iter().find(|t| type_name(*t) == name.to_lowercase())
+}
+
+fn is_valid(type_: NoteType, value: &str) -> bool {
+    match type_ {
+        NoteType::String => true,
+        NoteType::Number => value.parse::<f64>().is_ok_and(|n| n.is_finite()),
+        NoteType::Date => NaiveDate::parse_from_str(value, DATE_FORMAT).is_ok(),
+        NoteType::Url => (value.starts_with("https://") || value.starts_with("http://"))
+            && !value.contains(|c: char| c.is_whitespace() || c == '"' || c == '<' || c == '>'),
+        NoteType::Tag => value.len() > 1 && value.starts_with('#') && !value.contains(char::is_whitespace),
+    }
+}
+
+/// Picks the most specific type that value is valid for
+fn infer(value: &str) -> NoteType {
+    [NoteType::Number, NoteType::Date, NoteType::Url, NoteType::Tag].into_iter()
+        .find(|t| is_valid(*t, value))
+        .unwrap_or(NoteType::String)
+}
+
+fn format_time(timestamp: i64) -> String {
+    DateTime::from_timestamp(timestamp, 0)
+        .map(|t| t.format("%Y-%m-%d %H:%M UTC").to_string())
+        .unwrap_or_default()
+}
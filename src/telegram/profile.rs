@@ -0,0 +1,57 @@
+use std::sync::Arc;
+use teloxide::prelude::*;
+use teloxide::types::{InputFile, MessageId, ThreadId};
+use crate::database::{Database, UserEntity};
+use crate::localization::{LocalizationBundle, StaffMessages};
+use crate::telegram::{rename_topic, update_user_info_msg, TelegramConfig};
+
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+/// Compares stored profile with sender of incoming message and applies changes to DB, topic and info message
+pub async fn sync(bot: &Bot, cfg: &TelegramConfig, db: &Arc<Box<dyn Database>>, loc: &Arc<LocalizationBundle>, user: UserEntity, msg: &Message) -> Result<UserEntity> {
+    let first_name = msg.chat.first_name().map(|s| s.to_string());
+    let last_name = msg.chat.last_name().map(|s| s.to_string());
+    // clients don't always send language, it's not a change. Language chosen with `/language` is kept
+    let lang_code = msg.from().and_then(|u| u.language_code.clone())
+        .filter(|_| !user.lang_selected)
+        .or(user.lang_code.clone());
+    let username = msg.from().and_then(|u| u.username.clone());
+    let is_premium = msg.from().is_some_and(|u| u.is_premium);
+    if first_name == user.first_name && last_name == user.last_name && lang_code == user.lang_code
+        && username == user.username && is_premium == user.is_premium {
+        return Ok(user);
+    }
+    let renamed = first_name != user.first_name || last_name != user.last_name;
+    let old_name = full_name(&user);
+    let user = UserEntity { first_name, last_name, lang_code, username, is_premium, ..user };
+    db.update_user_profile(&user).await?;
+
+    if renamed {
+        rename_topic(bot, cfg, &***db, &user).await?;
+        let text = loc.localize(cfg.staff_lang(cfg.superchat_of(&user)), StaffMessages::UserRenamed { old: old_name, new: full_name(&user) });
+        bot.send_message(cfg.superchat_of(&user), text)
+            .message_thread_id(ThreadId(MessageId(user.topic as i32)))
+            .disable_notification(true)
+            .await?;
+    }
+    update_user_info_msg(bot, user, cfg.clone(), db.clone(), loc.clone()).await
+}
+
+/// Posts current profile photo of new user to the topic
+pub async fn post_photo(bot: &Bot, cfg: &TelegramConfig, user: &UserEntity) -> Result<()> {
+    let photos = bot.get_user_profile_photos(UserId(user.telegram_id as u64))
+        .limit(1)
+        .await?;
+    let Some(photo) = photos.photos.first().and_then(|sizes| sizes.iter().max_by_key(|p| p.width * p.height)) else {
+        return Ok(());
+    };
+    bot.send_photo(cfg.superchat_of(user), InputFile::file_id(photo.file.id.clone()))
+        .message_thread_id(ThreadId(MessageId(user.topic as i32)))
+        .disable_notification(true)
+        .await?;
+    Ok(())
+}
+
+fn full_name(user: &UserEntity) -> String {
+    format!("{} {}", user.first_name.as_deref().unwrap_or(""), user.last_name.as_deref().unwrap_or("")).trim().to_string()
+}
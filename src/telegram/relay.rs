@@ -0,0 +1,171 @@
+use teloxide::prelude::*;
+use teloxide::types::{MediaKind, MessageCommon, MessageEntity, MessageId, MessageKind, PollType, ThreadId};
+use crate::localization::{CommonMessages, LocalizationBundle};
+use crate::telegram::signature::{Signature, CAPTION_LIMIT, TEXT_LIMIT};
+use crate::telegram::utils::{media_kind_name, send_localized, MessageBuilder};
+
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+/// Chat and optional forum topic that relayed message goes to
+#[derive(Debug, Clone, Copy)]
+pub struct Destination {
+    pub chat: ChatId,
+    pub thread: Option<ThreadId>,
+}
+
+impl Destination {
+    pub fn chat(chat: ChatId) -> Destination {
+        Destination { chat, thread: None }
+    }
+
+    pub fn topic(chat: ChatId, topic: i64) -> Destination {
+        Destination { chat, thread: Some(ThreadId(MessageId(topic as i32))) }
+    }
+}
+
+/// Sends message to destination. Messages are copied unless they have to be changed on the way.
+/// Returns `None` when message kind can't be relayed
+pub async fn send(bot: &Bot, msg: &Message, to: Destination, signature: Option<&Signature>) -> Result<Option<MessageId>> {
+    let common = match msg.kind {
+        MessageKind::Common(ref common) => common,
+        MessageKind::Dice(_) => return Ok(Some(copy(bot, msg, to).await?)),
+        _ => return Ok(None),
+    };
+    let id = match common.media_kind {
+        MediaKind::Text(ref t) if signature.is_some() => {
+            let (text, entities) = sign(signature, Some(&t.text), Some(&t.entities), TEXT_LIMIT);
+            MessageBuilder::new(bot.send_message(to.chat, text.unwrap_or_default()))
+                .with(to.thread, |t, v| v.message_thread_id(t))
+                .build()
+                .entities(entities)
+                .await?
+                .id
+        }
+        // copies of live locations can't be updated, send a new one
+        MediaKind::Location(ref l) if l.location.live_period.is_some() => {
+            MessageBuilder::new(bot.send_location(to.chat, l.location.latitude, l.location.longitude))
+                .with(l.location.horizontal_accuracy, |o, v| v.horizontal_accuracy(o))
+                .with(l.location.live_period, |o, v| v.live_period(o.seconds()))
+                .with(l.location.heading, |o, v| v.heading(o))
+                .with(l.location.proximity_alert_radius, |o, v| v.proximity_alert_radius(o))
+                .with(to.thread, |t, v| v.message_thread_id(t))
+                .build()
+                .await?
+                .id
+        }
+        MediaKind::Poll(ref p) => {
+            let poll = &p.poll;
+            // quiz can be recreated only if the bot knows the right answer
+            let quiz = poll.correct_option_id.filter(|_| matches!(poll.poll_type, PollType::Quiz));
+            MessageBuilder::new(bot.send_poll(to.chat, poll.question.clone(), poll.options.iter().map(|o| o.text.clone())))
+                .with(quiz, |c, v| v.type_(PollType::Quiz).correct_option_id(c))
+                .with(quiz.and(poll.explanation.clone()), |e, v| v.explanation(e))
+                .with(quiz.and(poll.explanation_entities.clone()), |e, v| v.explanation_entities(e))
+                .with(to.thread, |t, v| v.message_thread_id(t))
+                .build()
+                .is_anonymous(poll.is_anonymous)
+                .allows_multiple_answers(poll.allows_multiple_answers && quiz.is_none())
+                .await?
+                .id
+        }
+        // stories can't be copied
+        MediaKind::Story(_) => {
+            MessageBuilder::new(bot.forward_message(to.chat, msg.chat.id, msg.id))
+                .with(to.thread, |t, v| v.message_thread_id(t))
+                .build()
+                .await?
+                .id
+        }
+        MediaKind::Animation(_) | MediaKind::Audio(_) | MediaKind::Document(_)
+        | MediaKind::Photo(_) | MediaKind::Video(_) | MediaKind::Voice(_) if signature.is_some() => {
+            let (caption, entities) = sign(signature, msg.caption(), msg.caption_entities(), CAPTION_LIMIT);
+            MessageBuilder::new(bot.copy_message(to.chat, msg.chat.id, msg.id))
+                .with(caption, |c, v| v.caption(c))
+                .with(to.thread, |t, v| v.message_thread_id(t))
+                .build()
+                .caption_entities(entities)
+                .await?
+        }
+        MediaKind::Animation(_) | MediaKind::Audio(_) | MediaKind::Contact(_) | MediaKind::Document(_)
+        | MediaKind::Location(_) | MediaKind::Photo(_) | MediaKind::Sticker(_) | MediaKind::Text(_)
+        | MediaKind::Venue(_) | MediaKind::Video(_) | MediaKind::VideoNote(_) | MediaKind::Voice(_) => copy(bot, msg, to).await?,
+        _ => return Ok(None),
+    };
+    Ok(Some(id))
+}
+
+/// Applies changes between `original` and `edited` to message that was relayed to `chat`
+pub async fn edit(bot: &Bot, original: &Message, edited: &Message, chat: ChatId, id: MessageId, signature: Option<&Signature>) -> Result<()> {
+    if original.caption() != edited.caption() || original.caption_entities() != edited.caption_entities() {
+        let (caption, entities) = sign(signature, edited.caption(), edited.caption_entities(), CAPTION_LIMIT);
+        MessageBuilder::new(bot.edit_message_caption(chat, id))
+            .with(caption, |c, v| v.caption(c))
+            .build()
+            .caption_entities(entities)
+            .await?;
+    }
+    if edited.text().is_some() && (original.text() != edited.text() || original.entities() != edited.entities()) {
+        let (text, entities) = sign(signature, edited.text(), edited.entities(), TEXT_LIMIT);
+        bot.edit_message_text(chat, id, text.unwrap_or_default())
+            .entities(entities)
+            .await?;
+    }
+    if original.location() != edited.location() {
+        if let Some(location) = edited.location() {
+            bot.edit_message_live_location(chat, id, location.latitude, location.longitude).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Tells sender in `lang` that message wasn't relayed. Service messages are ignored
+pub async fn unsupported(bot: &Bot, loc: &LocalizationBundle, msg: &Message, lang: Option<String>) -> Result<()> {
+    if !is_content(msg) {
+        return Ok(());
+    }
+    let text = match msg.kind {
+        MessageKind::Common(MessageCommon { media_kind: MediaKind::Game(_), .. }) => CommonMessages::GamesNotSupported,
+        _ => CommonMessages::MessageNotSupported,
+    };
+    MessageBuilder::new(send_localized(bot, msg.chat.id, loc.localize_message(lang, text)))
+        .with(msg.thread_id, |t, v| v.message_thread_id(t))
+        .build()
+        .await?;
+    Ok(())
+}
+
+/// Sends a note in `lang` in place of message that can't be relayed, so it doesn't go unnoticed
+pub async fn placeholder(bot: &Bot, loc: &LocalizationBundle, msg: &Message, to: Destination, lang: Option<String>) -> Result<Option<MessageId>> {
+    if !is_content(msg) {
+        return Ok(None);
+    }
+    let text = loc.localize(lang, CommonMessages::UnsupportedPlaceholder { kind: media_kind_name(msg).to_string() });
+    let sent = MessageBuilder::new(bot.send_message(to.chat, text))
+        .with(to.thread, |t, v| v.message_thread_id(t))
+        .build()
+        .await?;
+    Ok(Some(sent.id))
+}
+
+/// Whether message was sent by someone, unlike service messages about chat changes
+fn is_content(msg: &Message) -> bool {
+    matches!(msg.kind, MessageKind::Common(_) | MessageKind::Dice(_) | MessageKind::Invoice(_)
+        | MessageKind::Giveaway(_) | MessageKind::GiveawayWinners(_))
+}
+
+async fn copy(bot: &Bot, msg: &Message, to: Destination) -> Result<MessageId> {
+    let id = MessageBuilder::new(bot.copy_message(to.chat, msg.chat.id, msg.id))
+        .with(to.thread, |t, v| v.message_thread_id(t))
+        .build()
+        .await?;
+    Ok(id)
+}
+
+fn sign(signature: Option<&Signature>, text: Option<&str>, entities: Option<&[MessageEntity]>, limit: usize) -> (Option<String>, Vec<MessageEntity>) {
+    let text = text.map(|t| t.to_string());
+    let entities = entities.map(|e| e.to_vec()).unwrap_or_default();
+    match signature {
+        Some(signature) => signature.apply(text, entities, limit),
+        None => (text, entities),
+    }
+}
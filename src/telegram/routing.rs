@@ -0,0 +1,73 @@
+use std::sync::Arc;
+use serde::Deserialize;
+use teloxide::prelude::*;
+use teloxide::types::{MessageId, ParseMode, ThreadId};
+use crate::database::{Database, UserEntity};
+use crate::localization::{LocalizationBundle, StaffMessages};
+use crate::telegram::{tags, topic_link, topic_name, update_user_info_msg, TelegramConfig, TOPIC_COLOR};
+
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+/// Sends matching users to another superchat. Every set condition has to match,
+/// route without conditions matches everyone
+#[derive(Deserialize, Debug, Clone)]
+pub struct Route {
+    pub superchat: i64,
+    /// User language codes, `pt` also matches `pt-br`
+    #[serde(default)]
+    pub lang: Vec<String>,
+    /// Prefixes of `/start` deep-link parameter
+    #[serde(default)]
+    pub start: Vec<String>,
+    /// User has any of these tags
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Staff language of the superchat, overrides common one
+    #[serde(default)]
+    pub staff_language: Option<String>,
+}
+
+impl Route {
+    fn matches(&self, lang: Option<&str>, start: Option<&str>, tags: &[String]) -> bool {
+        let lang = lang.map(|l| l.to_lowercase());
+        (self.lang.is_empty() || lang.is_some_and(|l| self.lang.iter().any(|r| {
+            let r = r.to_lowercase();
+            l == r || l.starts_with(&format!("{}-", r))
+        })))
+            && (self.start.is_empty() || start.is_some_and(|s| self.start.iter().any(|r| s.starts_with(r.as_str()))))
+            && (self.tags.is_empty() || self.tags.iter().any(|r| tags::normalize(r).is_some_and(|r| tags.contains(&r))))
+    }
+}
+
+/// Superchat of the first matching route, default superchat if none match
+pub fn superchat(cfg: &TelegramConfig, lang: Option<&str>, start: Option<&str>, tags: &[String]) -> ChatId {
+    let chat = cfg.routes.iter()
+        .find(|r| r.matches(lang, start, tags))
+        .map(|r| r.superchat)
+        .unwrap_or(cfg.superchat);
+    ChatId(chat)
+}
+
+/// Moves user topic to another superchat if routes match differently after tags changed. Returns true if user was moved.
+/// Staff messages sent to old topic are no longer relayed
+pub async fn reroute(bot: &Bot, cfg: &TelegramConfig, db: &Arc<Box<dyn Database>>, loc: &Arc<LocalizationBundle>, user: UserEntity) -> Result<bool> {
+    let tags = db.get_tags(&user).await?.into_iter().map(|t| t.tag).collect::<Vec<_>>();
+    let target = superchat(cfg, user.lang_code.as_deref(), user.start_payload.as_deref(), &tags);
+    let current = cfg.superchat_of(&user);
+    if target == current {
+        return Ok(false);
+    }
+    let topic = bot.create_forum_topic(target, topic_name(cfg, &***db, &user).await?, TOPIC_COLOR, "").await?;
+    let moved = UserEntity { superchat: Some(target.0), topic: topic.thread_id.0.0 as i64, info_message: None, ..user.clone() };
+    db.update_user(moved.clone()).await?;
+
+    let html = bot.parse_mode(ParseMode::Html);
+    html.send_message(current, loc.localize(cfg.staff_lang(current), StaffMessages::UserMovedTo { link: topic_link(cfg, &moved) }))
+        .message_thread_id(ThreadId(MessageId(user.topic as i32)))
+        .await?;
+    html.send_message(target, loc.localize(cfg.staff_lang(target), StaffMessages::UserMovedFrom { link: topic_link(cfg, &user) }))
+        .message_thread_id(topic.thread_id)
+        .await?;
+    update_user_info_msg(bot, moved, cfg.clone(), db.clone(), loc.clone()).await?;
+    Ok(true)
+}
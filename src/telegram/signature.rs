@@ -0,0 +1,127 @@
+use serde::Deserialize;
+use teloxide::prelude::{Message, UserId};
+use teloxide::types::MessageEntity;
+use crate::database::{Database, UserEntity};
+use crate::localization::{CommonMessages, LocalizationBundle};
+
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+/// Telegram limit of message text, in UTF-16 code units
+pub const TEXT_LIMIT: usize = 4096;
+/// Telegram limit of media caption, in UTF-16 code units
+pub const CAPTION_LIMIT: usize = 1024;
+
+#[derive(Deserialize, Debug, Clone, Default)]
+pub enum StaffMode {
+    /// Messages are sent as bot without any staff info
+    #[default]
+    Anonymous,
+    /// Staff first name is added to messages
+    Signature,
+    /// Staff alias from `/alias` command is added to messages. Staff without alias stay anonymous
+    Alias,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+pub enum SignaturePosition {
+    #[default]
+    Footer,
+    Header,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct SignatureConfig {
+    #[serde(default)]
+    pub mode: StaffMode,
+    #[serde(default)]
+    pub position: SignaturePosition,
+}
+
+pub struct Signature {
+    text: String,
+    position: SignaturePosition,
+}
+
+impl Signature {
+    /// Creates signature of staff member who sent the message to the user
+    pub async fn of(cfg: &SignatureConfig, db: &dyn Database, loc: &LocalizationBundle, user: &UserEntity, msg: &Message) -> Result<Option<Signature>> {
+        let Some(staff) = msg.from() else {
+            return Ok(None);
+        };
+        let name = match cfg.mode {
+            StaffMode::Anonymous => return Ok(None),
+            StaffMode::Signature => staff.first_name.clone(),
+            StaffMode::Alias => match db.get_staff_alias(staff.id).await? {
+                Some(alias) => alias.alias,
+                None => return Ok(None),
+            },
+        };
+        let text = match cfg.position {
+            SignaturePosition::Footer => loc.localize(user.lang_code.clone(), CommonMessages::StaffSignature { name }),
+            SignaturePosition::Header => loc.localize(user.lang_code.clone(), CommonMessages::StaffHeader { name }),
+        };
+        Ok(Some(Signature { text, position: cfg.position.clone() }))
+    }
+
+    /// Adds signature to message text or caption, keeping entities in place.
+    /// Text that wouldn't fit in `limit` together with signature is shortened
+    pub fn apply(&self, text: Option<String>, mut entities: Vec<MessageEntity>, limit: usize) -> (Option<String>, Vec<MessageEntity>) {
+        let length = self.text.encode_utf16().count();
+        let Some(text) = text.filter(|t| !t.is_empty()) else {
+            return (Some(self.text.clone()), vec![self.entity(0, length)]);
+        };
+        let text = truncate(text, &mut entities, limit.saturating_sub(length + 2));
+        match self.position {
+            SignaturePosition::Footer => {
+                let signed = format!("{}\n\n{}", text, self.text);
+                entities.push(self.entity(signed.encode_utf16().count() - length, length));
+                (Some(signed), entities)
+            }
+            SignaturePosition::Header => {
+                let shift = length + 2;
+                for entity in entities.iter_mut() {
+                    entity.offset += shift;
+                }
+                entities.insert(0, self.entity(0, length));
+                (Some(format!("{}\n\n{}", self.text, text)), entities)
+            }
+        }
+    }
+
+    /// Footer is italic and header is bold, with or without text
+    fn entity(&self, offset: usize, length: usize) -> MessageEntity {
+        match self.position {
+            SignaturePosition::Footer => MessageEntity::italic(offset, length),
+            SignaturePosition::Header => MessageEntity::bold(offset, length),
+        }
+    }
+}
+
+/// Cuts text to `limit` UTF-16 code units ending with `…`, clipping entities to what is left
+fn truncate(text: String, entities: &mut Vec<MessageEntity>, limit: usize) -> String {
+    if text.encode_utf16().count() <= limit {
+        return text;
+    }
+    let (mut cut, mut end) = (0, 0);
+    for (i, c) in text.char_indices() {
+        // leave room for ellipsis
+        if cut + c.len_utf16() >= limit {
+            break;
+        }
+        cut += c.len_utf16();
+        end = i + c.len_utf8();
+    }
+    entities.retain(|e| e.offset < cut);
+    for entity in entities.iter_mut() {
+        entity.length = entity.length.min(cut - entity.offset);
+    }
+    format!("{}…", &text[..end])
+}
+
+/// Staff alias if set, otherwise telegram id
+pub async fn staff_name(db: &dyn Database, staff: i64) -> Result<String> {
+    Ok(match db.get_staff_alias(UserId(staff as u64)).await? {
+        Some(alias) => alias.alias,
+        None => staff.to_string(),
+    })
+}
@@ -0,0 +1,177 @@
+use std::sync::Arc;
+use std::time::Duration;
+use chrono::Utc;
+use serde::Deserialize;
+use teloxide::prelude::*;
+use teloxide::types::{MessageId, ParseMode, ThreadId};
+use tracing::{error, info};
+use crate::config::ConfigErrors;
+use crate::database::{ConversationEntity, Database, UserEntity};
+use crate::localization::{clean, escape, LocalizationBundle, StaffMessages};
+use crate::metrics;
+use crate::telegram::{topic_link, topic_name, TelegramConfig};
+
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct SlaConfig {
+    /// Max seconds between conversation start and first staff reply
+    #[serde(default)]
+    pub first_response: Option<i64>,
+    /// Max seconds user message can stay unanswered
+    #[serde(default)]
+    pub response: Option<i64>,
+    #[serde(default = "default_check_interval")]
+    pub check_interval: u64,
+    /// Thread of default superchat for alerts. Alerts are posted into user topic if not set
+    #[serde(default)]
+    pub escalation_thread: Option<i32>,
+    /// Staff to mention in alerts, like `@username`
+    #[serde(default)]
+    pub mentions: Vec<String>,
+    /// Custom emoji id to set as topic icon while SLA is breached
+    #[serde(default)]
+    pub breach_icon: Option<String>,
+    #[serde(default = "default_breach_prefix")]
+    pub breach_prefix: String,
+}
+
+impl SlaConfig {
+    pub fn validate(&self, errors: &mut ConfigErrors) {
+        if self.first_response.is_some_and(|s| s <= 0) {
+            errors.add("telegram.sla.first_response", "should be a positive number of seconds");
+        }
+        if self.response.is_some_and(|s| s <= 0) {
+            errors.add("telegram.sla.response", "should be a positive number of seconds");
+        }
+        if self.check_interval == 0 {
+            errors.add("telegram.sla.check_interval", "should be at least 1 second");
+        }
+    }
+}
+
+fn default_check_interval() -> u64 {
+    60
+}
+
+fn default_breach_prefix() -> String {
+    "🔥 ".to_string()
+}
+
+pub(super) enum Breach {
+    FirstResponse { waiting: i64 },
+    Response { waiting: i64 },
+}
+
+impl Breach {
+    /// Breach of conversation at `now`. Targets are exceeded only after waiting longer than them,
+    /// conversations that were already reported are skipped
+    pub(super) fn find(sla: &SlaConfig, conversation: &ConversationEntity, now: i64) -> Option<Breach> {
+        if conversation.breached_at.is_some() {
+            return None;
+        }
+        if conversation.first_response_at.is_none() {
+            let waiting = now - conversation.opened_at;
+            if sla.first_response.is_some_and(|max| waiting > max) {
+                return Some(Breach::FirstResponse { waiting });
+            }
+        }
+        let waiting = now - conversation.unanswered_since?;
+        if sla.response.is_some_and(|max| waiting > max) {
+            return Some(Breach::Response { waiting });
+        }
+        None
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Breach::FirstResponse { .. } => "first_response",
+            Breach::Response { .. } => "response",
+        }
+    }
+
+    fn describe(&self) -> StaffMessages {
+        match self {
+            Breach::FirstResponse { waiting } => StaffMessages::SlaNoFirstResponse { minutes: waiting / 60 },
+            Breach::Response { waiting } => StaffMessages::SlaUnanswered { minutes: waiting / 60 },
+        }
+    }
+}
+
+/// Periodically checks open conversations against configured SLA targets
+pub async fn watch(bot: Bot, cfg: TelegramConfig, db: Arc<Box<dyn Database>>, loc: Arc<LocalizationBundle>) {
+    let Some(sla) = cfg.sla.clone() else {
+        return;
+    };
+    info!("Checking SLA every {} seconds", sla.check_interval);
+    let mut interval = tokio::time::interval(Duration::from_secs(sla.check_interval));
+    loop {
+        interval.tick().await;
+        if let Err(e) = check(&bot, &cfg, &sla, &**db, &loc).await {
+            error!("Failed to check SLA: {}", e);
+        }
+    }
+}
+
+pub(super) async fn check(bot: &Bot, cfg: &TelegramConfig, sla: &SlaConfig, db: &dyn Database, loc: &LocalizationBundle) -> Result<()> {
+    let now = Utc::now().timestamp();
+    for mut conversation in db.get_unanswered_conversations().await? {
+        let Some(breach) = Breach::find(sla, &conversation, now) else {
+            continue;
+        };
+        let Some(user) = db.get_user(conversation.user_id).await? else {
+            continue;
+        };
+        conversation.breached_at = Some(now);
+        db.update_conversation(conversation).await?;
+        metrics::sla_breach(breach.name());
+        alert(bot, cfg, sla, db, loc, &user, &breach).await?;
+        mark(bot, cfg, sla, db, &user).await?;
+    }
+    Ok(())
+}
+
+async fn alert(bot: &Bot, cfg: &TelegramConfig, sla: &SlaConfig, db: &dyn Database, loc: &LocalizationBundle, user: &UserEntity, breach: &Breach) -> Result<()> {
+    let (chat, thread) = match sla.escalation_thread {
+        Some(thread) => (ChatId(cfg.superchat), thread),
+        None => (cfg.superchat_of(user), user.topic as i32),
+    };
+    let lang = cfg.staff_lang(chat);
+    let mut msg = loc.localize(lang.clone(), StaffMessages::SlaBreach {
+        link: topic_link(cfg, user),
+        name: escape(&topic_name(cfg, db, user).await?),
+        reason: loc.localize(lang, breach.describe()),
+    });
+    if !sla.mentions.is_empty() {
+        msg = format!("{}\n{}", msg, clean(&sla.mentions.join(" ")));
+    }
+    bot.parse_mode(ParseMode::Html)
+        .send_message(chat, msg)
+        .message_thread_id(ThreadId(MessageId(thread)))
+        .await?;
+    Ok(())
+}
+
+async fn mark(bot: &Bot, cfg: &TelegramConfig, sla: &SlaConfig, db: &dyn Database, user: &UserEntity) -> Result<()> {
+    let mut edit = bot.edit_forum_topic(cfg.superchat_of(user), ThreadId(MessageId(user.topic as i32)))
+        .name(topic_name(cfg, db, user).await?);
+    if let Some(ref icon) = sla.breach_icon {
+        edit = edit.icon_custom_emoji_id(icon);
+    }
+    edit.await?;
+    Ok(())
+}
+
+/// Restores topic name and icon after staff replied to conversation with breached SLA
+pub async fn resolve(bot: &Bot, cfg: &TelegramConfig, db: &dyn Database, user: &UserEntity) -> Result<()> {
+    let Some(ref sla) = cfg.sla else {
+        return Ok(());
+    };
+    let mut edit = bot.edit_forum_topic(cfg.superchat_of(user), ThreadId(MessageId(user.topic as i32)))
+        .name(topic_name(cfg, db, user).await?);
+    if sla.breach_icon.is_some() {
+        edit = edit.icon_custom_emoji_id("");
+    }
+    edit.await?;
+    Ok(())
+}
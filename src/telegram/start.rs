@@ -0,0 +1,33 @@
+use serde::Deserialize;
+use crate::database::{Database, UserEntity};
+use crate::telegram::notes;
+
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+/// Handling of `/start` deep-link payloads like `t.me/bot?start=order_123-ref_ads`.
+/// Payload is a `-` separated list of `key_value` pairs, Telegram allows only letters, digits, `_` and `-` in it
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct StartConfig {
+    /// Payload keys saved as user notes. Payload is set by whoever made the link, so other keys are ignored
+    #[serde(default)]
+    pub notes: Vec<String>,
+}
+
+/// Splits payload into key and value pairs. Parts without value are skipped
+pub fn parse(payload: &str) -> Vec<(&str, &str)> {
+    payload.split('-')
+        .filter_map(|part| part.split_once('_'))
+        .filter(|(key, value)| !key.is_empty() && !value.is_empty())
+        .collect()
+}
+
+/// Saves payload on user and creates notes for configured keys
+pub async fn apply(cfg: &StartConfig, db: &dyn Database, user: &UserEntity, payload: &str, date: i64) -> Result<()> {
+    db.update_user(UserEntity { start_payload: Some(payload.to_string()), ..user.clone() }).await?;
+    for (key, value) in parse(payload) {
+        if cfg.notes.iter().any(|k| k == key) {
+            notes::auto(db, user, key, value, date).await?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,174 @@
+use std::sync::Arc;
+use chrono::Utc;
+use serde::Deserialize;
+use teloxide::prelude::*;
+use teloxide::types::{InlineKeyboardButton, InlineKeyboardMarkup, MessageId, ThreadId};
+use crate::database::{ConversationEntity, Database, InsertRatingEntity, UserEntity};
+use crate::localization::{CommonMessages, LocalizationBundle, StaffMessages};
+use crate::metrics;
+use crate::telegram::utils::{send_localized, MessageBuilder};
+use crate::telegram::{HandlerResult, TelegramConfig};
+
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct SurveyConfig {
+    /// Seconds after picking stars when any text message is taken as rating comment.
+    /// Replies to rating prompt are comments regardless
+    #[serde(default = "default_comment_window")]
+    pub comment_window: i64,
+}
+
+impl Default for SurveyConfig {
+    fn default() -> Self {
+        SurveyConfig { comment_window: default_comment_window() }
+    }
+}
+
+fn default_comment_window() -> i64 {
+    600
+}
+
+#[derive(Clone)]
+pub enum SurveyCallback {
+    Rate { conversation: i32, stars: i16 },
+    Skip { conversation: i32 },
+}
+
+impl SurveyCallback {
+    pub fn parse(data: &str) -> Option<SurveyCallback> {
+        let mut parts = data.split(':');
+        let callback = match parts.next()? {
+            "rate" => SurveyCallback::Rate {
+                conversation: parts.next()?.parse().ok()?,
+                stars: parts.next()?.parse().ok().filter(|s| (1..=5).contains(s))?,
+            },
+            "rate_skip" => SurveyCallback::Skip {
+                conversation: parts.next()?.parse().ok()?,
+            },
+            _ => return None,
+        };
+        Some(callback)
+    }
+
+    fn data(&self) -> String {
+        match self {
+            SurveyCallback::Rate { conversation, stars } => format!("rate:{}:{}", conversation, stars),
+            SurveyCallback::Skip { conversation } => format!("rate_skip:{}", conversation),
+        }
+    }
+
+    fn conversation(&self) -> i32 {
+        match self {
+            SurveyCallback::Rate { conversation, .. } => *conversation,
+            SurveyCallback::Skip { conversation } => *conversation,
+        }
+    }
+}
+
+/// Asks user to rate closed conversation
+pub async fn send_prompt(bot: &Bot, db: &dyn Database, loc: &LocalizationBundle, user: &UserEntity, conversation: &ConversationEntity) -> Result<()> {
+    let stars = (1..=5)
+        .map(|stars| InlineKeyboardButton::callback(
+            format!("{} ⭐", stars),
+            SurveyCallback::Rate { conversation: conversation.id, stars }.data(),
+        ))
+        .collect::<Vec<_>>();
+    let msg = send_localized(bot, UserId(user.telegram_id as u64), loc.localize_message(user.lang_code.clone(), CommonMessages::RatingPrompt))
+        .reply_markup(InlineKeyboardMarkup::new(vec![stars]))
+        .await?;
+    db.insert_rating(InsertRatingEntity {
+        conversation_id: conversation.id,
+        user_id: user.id,
+        staff_id: conversation.staff_id,
+        prompt_message: msg.id.0 as i64,
+        rating: None,
+        comment: None,
+        awaiting_comment: false,
+        rated_at: None,
+    }).await?;
+    Ok(())
+}
+
+pub async fn callback(bot: Bot, q: CallbackQuery, cb: SurveyCallback, cfg: TelegramConfig, db: Arc<Box<dyn Database>>, loc: Arc<LocalizationBundle>) -> HandlerResult {
+    bot.answer_callback_query(q.id.clone()).await?;
+    let Some(user) = db.get_user_by_tg_id(q.from.id).await? else {
+        return Ok(());
+    };
+    let Some(mut rating) = db.get_rating(cb.conversation()).await? else {
+        return Ok(());
+    };
+    if rating.user_id != user.id {
+        return Ok(());
+    }
+    let uid = UserId(user.telegram_id as u64);
+    let prompt = MessageId(rating.prompt_message as i32);
+    match cb {
+        SurveyCallback::Rate { conversation, stars } => {
+            let first = rating.rating.is_none();
+            rating.rating = Some(stars);
+            rating.awaiting_comment = true;
+            rating.rated_at = Some(Utc::now().timestamp());
+            db.update_rating(rating.clone()).await?;
+            if first {
+                metrics::rating(stars);
+            }
+            let skip = InlineKeyboardButton::callback(
+                loc.localize(user.lang_code.clone(), CommonMessages::RatingSkip),
+                SurveyCallback::Skip { conversation }.data(),
+            );
+            let text = loc.localize_message(user.lang_code.clone(), CommonMessages::RatingCommentPrompt);
+            MessageBuilder::new(bot.edit_message_text(uid, prompt, text.text))
+                .with(text.parse_mode, |mode, r| r.parse_mode(mode.into()))
+                .build()
+                .reply_markup(InlineKeyboardMarkup::new(vec![vec![skip]]))
+                .await?;
+            let text = loc.localize(cfg.staff_lang(cfg.superchat_of(&user)), StaffMessages::UserRated { stars: "⭐".repeat(stars as usize) });
+            bot.send_message(cfg.superchat_of(&user), text)
+                .message_thread_id(ThreadId(MessageId(user.topic as i32)))
+                .await?;
+        }
+        SurveyCallback::Skip { .. } => {
+            rating.awaiting_comment = false;
+            db.update_rating(rating).await?;
+            let text = loc.localize_message(user.lang_code.clone(), CommonMessages::RatingThanks);
+            MessageBuilder::new(bot.edit_message_text(uid, prompt, text.text))
+                .with(text.parse_mode, |mode, r| r.parse_mode(mode.into()))
+                .build()
+                .await?;
+        }
+    }
+    Ok(())
+}
+
+/// Saves text message as rating comment if user was asked for one. Only replies to rating prompt and messages
+/// within [SurveyConfig::comment_window] are comments, later messages end the wait and are relayed as usual.
+/// Returns true if message was consumed
+pub async fn comment(bot: &Bot, cfg: &TelegramConfig, db: &dyn Database, loc: &LocalizationBundle, user: &UserEntity, msg: &Message) -> Result<bool> {
+    let Some(mut rating) = db.get_rating_awaiting_comment(user).await? else {
+        return Ok(false);
+    };
+    let prompt = MessageId(rating.prompt_message as i32);
+    let reply = msg.reply_to_message().is_some_and(|m| m.id == prompt);
+    let in_window = rating.rated_at.is_some_and(|at| msg.date.timestamp() - at <= cfg.survey.comment_window);
+    let text = msg.text().filter(|_| reply || in_window);
+    if text.is_none() && in_window {
+        // media can't be a comment, but user may still send one
+        return Ok(false);
+    }
+    rating.comment = text.map(|t| t.to_string());
+    rating.awaiting_comment = false;
+    db.update_rating(rating.clone()).await?;
+    bot.edit_message_reply_markup(UserId(user.telegram_id as u64), prompt)
+        .await?;
+    let Some(text) = text else {
+        return Ok(false);
+    };
+    send_localized(bot, msg.chat.id, loc.localize_message(user.lang_code.clone(), CommonMessages::RatingThanks))
+        .await?;
+    let text = loc.localize(cfg.staff_lang(cfg.superchat_of(user)), StaffMessages::UserCommented { text: text.to_string() });
+    bot.send_message(cfg.superchat_of(user), text)
+        .message_thread_id(ThreadId(MessageId(user.topic as i32)))
+        .await?;
+    Ok(true)
+}
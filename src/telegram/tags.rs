@@ -0,0 +1,62 @@
+use crate::database::{Database, InsertTagEntity, UserEntity};
+use crate::localization::{escape, LocalizationBundle, StaffMessages};
+use crate::telegram::{topic_link, topic_name, TelegramConfig};
+
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+/// Lowercase tag without leading `#`. Returns `None` if tag has characters other than letters, digits and `_`,
+/// so rendered tags stay clickable hashtags
+pub fn normalize(tag: &str) -> Option<String> {
+    let tag = tag.trim_start_matches('#').to_lowercase();
+    let valid = !tag.is_empty()
+        && tag.chars().count() <= 32
+        && tag.chars().all(|c| c.is_alphanumeric() || c == '_');
+    valid.then_some(tag)
+}
+
+/// Handles `/tag a b`. Returns HTML reply for staff
+pub async fn add(db: &dyn Database, loc: &LocalizationBundle, lang: Option<String>, user: &UserEntity, tags: &str) -> Result<String> {
+    let Some(tags) = parse(tags) else {
+        return Ok(loc.localize(lang, StaffMessages::InvalidTags));
+    };
+    for tag in tags.iter() {
+        db.add_tag(InsertTagEntity { user_id: user.id, tag: tag.clone() }).await?;
+    }
+    Ok(loc.localize(lang, StaffMessages::Tagged { tags: render(&tags) }))
+}
+
+/// Handles `/untag a b`. Returns HTML reply for staff
+pub async fn remove(db: &dyn Database, loc: &LocalizationBundle, lang: Option<String>, user: &UserEntity, tags: &str) -> Result<String> {
+    let Some(tags) = parse(tags) else {
+        return Ok(loc.localize(lang, StaffMessages::InvalidTags));
+    };
+    for tag in tags.iter() {
+        db.delete_tag(user, tag).await?;
+    }
+    Ok(loc.localize(lang, StaffMessages::Untagged { tags: render(&tags) }))
+}
+
+/// Handles `/tagged a`. Returns HTML list of users with links to their topics
+pub async fn tagged(cfg: &TelegramConfig, db: &dyn Database, loc: &LocalizationBundle, lang: Option<String>, tag: &str) -> Result<String> {
+    let Some(tag) = normalize(tag.trim()) else {
+        return Ok(loc.localize(lang, StaffMessages::InvalidTags));
+    };
+    let users = db.get_users_by_tag(&tag).await?;
+    if users.is_empty() {
+        return Ok(loc.localize(lang, StaffMessages::NoUsersTagged { tag: render(&[tag]) }));
+    }
+    let mut reply = format!("{}\n", loc.localize(lang, StaffMessages::UsersTagged { tag: render(&[tag]) }));
+    for user in users {
+        reply = format!("{}\n<a href=\"{}\">{}</a>", reply, topic_link(cfg, &user), escape(&topic_name(cfg, db, &user).await?));
+    }
+    Ok(reply)
+}
+
+fn render(tags: &[String]) -> String {
+    tags.iter().map(|t| format!("#{}", escape(t))).collect::<Vec<_>>().join(" ")
+}
+
+fn parse(tags: &str) -> Option<Vec<String>> {
+    let tags = tags.split_whitespace().map(normalize).collect::<Option<Vec<_>>>()?;
+    (!tags.is_empty()).then_some(tags)
+}
@@ -0,0 +1,156 @@
+use std::net::TcpListener;
+use std::sync::{Arc, Mutex};
+use axum::body::{Body, Bytes};
+use axum::extract::{FromRequest, Multipart, Path, State};
+use axum::http::{header, Request};
+use axum::routing::post;
+use axum::{Json, Router};
+use serde_json::{json, Map, Value};
+use teloxide::Bot;
+
+/// Bot API method call received by [FakeApi]
+#[derive(Clone, Debug)]
+pub struct Call {
+    pub method: String,
+    pub params: Value,
+}
+
+#[derive(Default)]
+struct Inner {
+    calls: Vec<Call>,
+    next_id: i64,
+    /// Methods answered with error
+    failing: Vec<String>,
+}
+
+/// Local Bot API server that records every call and answers with plausible results
+#[derive(Clone)]
+pub struct FakeApi {
+    url: reqwest::Url,
+    inner: Arc<Mutex<Inner>>,
+}
+
+impl FakeApi {
+    pub fn start() -> FakeApi {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap()).parse().unwrap();
+        let inner = Arc::new(Mutex::new(Inner { next_id: 1000, ..Inner::default() }));
+        let app = Router::new()
+            .route("/:token/:method", post(handle))
+            .with_state(inner.clone());
+        let server = axum::Server::from_tcp(listener).unwrap().serve(app.into_make_service());
+        tokio::spawn(server);
+        FakeApi { url, inner }
+    }
+
+    pub fn bot(&self) -> Bot {
+        Bot::new("test").set_api_url(self.url.clone())
+    }
+
+    pub fn calls(&self) -> Vec<Call> {
+        self.inner.lock().unwrap().calls.clone()
+    }
+
+    pub fn calls_to(&self, method: &str) -> Vec<Value> {
+        self.calls()
+            .into_iter()
+            .filter(|c| c.method == method)
+            .map(|c| c.params)
+            .collect()
+    }
+
+    pub fn clear(&self) {
+        self.inner.lock().unwrap().calls.clear();
+    }
+
+    /// Makes calls to `method` fail with Bad Request
+    pub fn fail(&self, method: &str) {
+        self.inner.lock().unwrap().failing.push(method.to_string());
+    }
+}
+
+async fn handle(State(inner): State<Arc<Mutex<Inner>>>, Path((_, method)): Path<(String, String)>, request: Request<Body>) -> Json<Value> {
+    let multipart = request.headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("multipart/form-data"));
+    let params = if multipart {
+        let mut form = Multipart::from_request(request, &()).await.unwrap();
+        let mut params = Map::new();
+        while let Some(field) = form.next_field().await.unwrap() {
+            let name = field.name().unwrap_or_default().to_string();
+            let text = field.text().await.unwrap();
+            params.insert(name, serde_json::from_str(&text).unwrap_or(Value::String(text)));
+        }
+        Value::Object(params)
+    } else {
+        let body = Bytes::from_request(request, &()).await.unwrap();
+        serde_json::from_slice(&body).unwrap_or(Value::Null)
+    };
+
+    // Bot API method names are case-insensitive, teloxide sends them capitalized
+    let method = method[..1].to_lowercase() + &method[1..];
+    let mut inner = inner.lock().unwrap();
+    inner.next_id += 1;
+    let id = inner.next_id;
+    let failing = inner.failing.contains(&method);
+    let result = respond(&method, &params, id);
+    inner.calls.push(Call { method, params });
+    if failing {
+        return Json(json!({ "ok": false, "error_code": 400, "description": "Bad Request: failed by test" }));
+    }
+    Json(json!({ "ok": true, "result": result }))
+}
+
+fn respond(method: &str, params: &Value, id: i64) -> Value {
+    match method {
+        "getMe" => me(),
+        "createForumTopic" => json!({
+            "message_thread_id": id,
+            "name": params["name"],
+            "icon_color": params["icon_color"],
+        }),
+        "editForumTopic" => json!(true),
+        "copyMessage" => json!({ "message_id": id }),
+        "getUserProfilePhotos" => json!({ "total_count": 0, "photos": [] }),
+        m if m.starts_with("send") => message(params, id),
+        m if m.starts_with("editMessage") => message(params, params["message_id"].as_i64().unwrap_or(id)),
+        _ => json!(true),
+    }
+}
+
+fn message(params: &Value, id: i64) -> Value {
+    let chat_id = params["chat_id"].as_i64().unwrap_or_default();
+    let chat = if chat_id < 0 {
+        json!({ "id": chat_id, "type": "supergroup", "title": "Support", "is_forum": true })
+    } else {
+        json!({ "id": chat_id, "type": "private", "first_name": "User" })
+    };
+    let text = params["text"].as_str()
+        .or_else(|| params["caption"].as_str())
+        .unwrap_or_default();
+    let mut message = json!({
+        "message_id": id,
+        "date": 0,
+        "chat": chat,
+        "text": text,
+    });
+    if let Some(thread) = params["message_thread_id"].as_i64() {
+        message["message_thread_id"] = json!(thread);
+        message["is_topic_message"] = json!(true);
+    }
+    message
+}
+
+pub fn me() -> Value {
+    json!({
+        "id": 1,
+        "is_bot": true,
+        "first_name": "Support",
+        "username": "support_bot",
+        "can_join_groups": true,
+        "can_read_all_group_messages": true,
+        "supports_inline_queries": false,
+        "can_connect_to_business": false,
+    })
+}
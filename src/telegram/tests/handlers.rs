@@ -0,0 +1,617 @@
+use serde_json::json;
+use crate::database::{MessageType, NoteType};
+use crate::localization;
+use super::{command, text, Harness, SUPERCHAT, USER};
+
+#[tokio::test]
+async fn user_msg_creates_topic_and_relays_message() {
+    let mut h = Harness::new();
+    h.user_sends(text("Hello")).await;
+
+    let topics = h.api.calls_to("createForumTopic");
+    assert_eq!(topics.len(), 1);
+    assert_eq!(topics[0]["chat_id"], json!(SUPERCHAT));
+
+    let user = h.user().await;
+    assert_eq!(user.first_name.as_deref(), Some("John"));
+    assert_eq!(user.lang_code.as_deref(), Some("en"));
+    assert!(user.info_message.is_some());
+
+    let renames = h.api.calls_to("editForumTopic");
+    assert_eq!(renames[0]["name"], json!(format!("#T{:#06} John Doe", user.id)));
+    let pins = h.api.calls_to("pinChatMessage");
+    assert_eq!(pins[0]["message_id"], json!(user.info_message.unwrap()));
+    assert_eq!(h.api.calls_to("getUserProfilePhotos")[0]["user_id"], json!(USER));
+
+    let copies = h.api.calls_to("copyMessage");
+    assert_eq!(copies.len(), 1, "message should be relayed to topic");
+    assert_eq!(copies[0]["chat_id"], json!(SUPERCHAT));
+    assert_eq!(copies[0]["from_chat_id"], json!(USER));
+    assert_eq!(copies[0]["message_thread_id"], json!(user.topic));
+    let sent = h.api.calls_to("sendMessage");
+    assert!(sent.iter().any(|m| m["chat_id"] == json!(USER)), "user should get auto reply");
+}
+
+#[tokio::test]
+async fn user_msg_reuses_topic() {
+    let mut h = Harness::new();
+    h.user_sends(text("Hello")).await;
+    h.user_sends(text("Are you there?")).await;
+
+    assert_eq!(h.api.calls_to("createForumTopic").len(), 1);
+    let user = h.user().await;
+    let relayed = h.api.calls_to("copyMessage")
+        .into_iter()
+        .filter(|m| m["message_thread_id"] == json!(user.topic))
+        .count();
+    assert_eq!(relayed, 2);
+}
+
+#[tokio::test]
+async fn superchat_msg_relays_staff_reply() {
+    let mut h = Harness::new();
+    h.user_sends(text("Hello")).await;
+    let user = h.user().await;
+    h.api.clear();
+
+    let id = h.staff_sends(user.topic, text("Hi! How can we help?")).await;
+
+    let copies = h.api.calls_to("copyMessage");
+    assert_eq!(copies.len(), 1);
+    assert_eq!(copies[0]["chat_id"], json!(USER));
+    assert_eq!(copies[0]["from_chat_id"], json!(SUPERCHAT));
+    assert_eq!(copies[0]["message_id"], json!(id));
+    let reactions = h.api.calls_to("setMessageReaction");
+    assert_eq!(reactions[0]["message_id"], json!(id));
+    let stored = h.db.get_message(&user, MessageType::Outgoing, id as i64).await.unwrap();
+    assert!(stored.is_some());
+}
+
+#[tokio::test]
+async fn user_edit_updates_relayed_message() {
+    let mut h = Harness::new();
+    let id = h.user_sends(text("Helo")).await;
+    let user = h.user().await;
+    let relayed = h.db.get_message(&user, MessageType::Incoming, id as i64).await.unwrap().unwrap();
+    h.api.clear();
+
+    h.user_edits(id, text("Hello")).await;
+
+    let edits = h.api.calls_to("editMessageText");
+    assert_eq!(edits.len(), 1);
+    assert_eq!(edits[0]["chat_id"], json!(SUPERCHAT));
+    assert_eq!(edits[0]["message_id"], json!(relayed.tx_msg_id));
+    assert_eq!(edits[0]["text"], json!("Hello"));
+}
+
+#[tokio::test]
+async fn staff_edit_updates_relayed_message() {
+    let mut h = Harness::new();
+    h.user_sends(text("Hello")).await;
+    let user = h.user().await;
+    let id = h.staff_sends(user.topic, text("Hi")).await;
+    let relayed = h.db.get_message(&user, MessageType::Outgoing, id as i64).await.unwrap().unwrap();
+    h.api.clear();
+
+    h.staff_edits(user.topic, id, text("Hi there")).await;
+
+    let edits = h.api.calls_to("editMessageText");
+    assert_eq!(edits.len(), 1);
+    assert_eq!(edits[0]["chat_id"], json!(USER));
+    assert_eq!(edits[0]["message_id"], json!(relayed.tx_msg_id));
+}
+
+#[tokio::test]
+async fn notes_are_saved_and_shown_in_info_message() {
+    let mut h = Harness::new();
+    h.user_sends(text("Hello")).await;
+    let user = h.user().await;
+    h.api.clear();
+
+    h.staff_sends(user.topic, command("/setnote order 123")).await;
+
+    let notes = h.db.get_notes(&user).await.unwrap();
+    assert_eq!(notes.len(), 1);
+    assert_eq!(notes[0].key, "order");
+    assert_eq!(notes[0].value, "123");
+    let edits = h.api.calls_to("editMessageText");
+    assert_eq!(edits[0]["message_id"], json!(user.info_message.unwrap()));
+    assert!(edits[0]["text"].as_str().unwrap().contains("123"));
+    assert!(h.api.calls_to("sendMessage").iter().all(|m| m["chat_id"] != json!(USER)), "commands should not be relayed");
+
+    h.staff_sends(user.topic, command("/delnote order")).await;
+    assert!(h.db.get_notes(&user).await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn internal_messages_are_not_relayed() {
+    let mut h = Harness::with_config(json!({ "internal": { "prefixes": ["//"] } }));
+    h.user_sends(text("Hello")).await;
+    let user = h.user().await;
+    h.api.clear();
+
+    h.staff_sends(user.topic, text("// looks like a refund case")).await;
+
+    assert!(h.api.calls_to("copyMessage").is_empty());
+    assert_eq!(h.api.calls_to("setMessageReaction").len(), 1);
+}
+
+#[tokio::test]
+async fn signed_staff_reply_is_sent_instead_of_copied() {
+    let mut h = Harness::with_config(json!({ "signature": { "mode": "Signature" } }));
+    h.user_sends(text("Hello")).await;
+    let user = h.user().await;
+    h.api.clear();
+
+    h.staff_sends(user.topic, text("Hi")).await;
+
+    assert!(h.api.calls_to("copyMessage").is_empty());
+    let sent = h.api.calls_to("sendMessage");
+    assert_eq!(sent.len(), 1);
+    assert_eq!(sent[0]["chat_id"], json!(USER));
+    assert!(sent[0]["text"].as_str().unwrap().contains("Anna"));
+}
+
+#[tokio::test]
+async fn signed_caption_fits_limit_and_header_is_bold() {
+    let mut h = Harness::with_config(json!({ "signature": { "mode": "Signature", "position": "Header" } }));
+    h.user_sends(text("Hello")).await;
+    let user = h.user().await;
+    h.api.clear();
+    let photo = json!([{ "file_id": "p", "file_unique_id": "p", "width": 1, "height": 1 }]);
+
+    h.staff_sends(user.topic, json!({
+        "photo": photo,
+        "caption": "a".repeat(1100),
+        "caption_entities": [{ "type": "bold", "offset": 1000, "length": 100 }, { "type": "italic", "offset": 1050, "length": 50 }],
+    })).await;
+    h.staff_sends(user.topic, json!({ "photo": photo })).await;
+
+    let copies = h.api.calls_to("copyMessage");
+    let caption = copies[0]["caption"].as_str().unwrap();
+    assert_eq!(caption.encode_utf16().count(), 1024);
+    assert!(caption.starts_with("Anna, Support:\n\naaa") && caption.ends_with("a…"), "{caption}");
+    let header = "Anna, Support:".len();
+    assert_eq!(copies[0]["caption_entities"], json!([
+        { "type": "bold", "offset": 0, "length": header },
+        { "type": "bold", "offset": 1000 + header + 2, "length": 1024 - 1 - 1000 - header - 2 },
+    ]));
+    assert_eq!(copies[1]["caption"], json!("Anna, Support:"));
+    assert_eq!(copies[1]["caption_entities"], json!([{ "type": "bold", "offset": 0, "length": header }]));
+}
+
+#[tokio::test]
+async fn user_quiz_is_recreated_in_topic() {
+    let mut h = Harness::new();
+    h.user_sends(json!({ "poll": {
+        "id": "1",
+        "question": "2 + 2?",
+        "options": [{ "text": "3", "voter_count": 0 }, { "text": "4", "voter_count": 0 }],
+        "total_voter_count": 0,
+        "is_closed": false,
+        "is_anonymous": false,
+        "type": "quiz",
+        "allows_multiple_answers": false,
+        "correct_option_id": 1,
+    } })).await;
+    let user = h.user().await;
+
+    let polls = h.api.calls_to("sendPoll");
+    assert_eq!(polls.len(), 1);
+    assert_eq!(polls[0]["message_thread_id"], json!(user.topic));
+    assert_eq!(polls[0]["options"], json!(["3", "4"]));
+    assert_eq!(polls[0]["type"], json!("quiz"));
+    assert_eq!(polls[0]["correct_option_id"], json!(1));
+}
+
+#[tokio::test]
+async fn unsupported_message_gets_notice_and_placeholder() {
+    let mut h = Harness::new();
+    h.user_sends(json!({ "game": {
+        "title": "Game",
+        "description": "Game",
+        "photo": [],
+    } })).await;
+    let user = h.user().await;
+
+    let sent = h.api.calls_to("sendMessage");
+    let replies = sent.iter().filter(|m| m["chat_id"] == json!(USER)).collect::<Vec<_>>();
+    assert_eq!(replies.len(), 1, "user should get only unsupported notice: {replies:?}");
+    assert_eq!(replies[0]["text"], json!("Games not supported"));
+    let placeholder = sent.iter()
+        .find(|m| m["message_thread_id"] == json!(user.topic) && m["text"].as_str().unwrap().contains("game"))
+        .expect("placeholder should be sent to topic");
+    assert_eq!(placeholder["chat_id"], json!(SUPERCHAT));
+    assert!(h.db.get_open_conversation(&user).await.unwrap().is_some(), "unsupported message should still open conversation");
+}
+
+#[tokio::test]
+async fn renamed_user_updates_topic_and_info_message() {
+    let mut h = Harness::new();
+    h.user_sends(text("Hello")).await;
+    let user = h.user().await;
+    h.api.clear();
+
+    h.user_sends(json!({
+        "text": "It's me",
+        "chat": { "id": USER, "type": "private", "first_name": "Johnny" },
+    })).await;
+
+    let renamed = h.user().await;
+    assert_eq!(renamed.first_name.as_deref(), Some("Johnny"));
+    assert!(renamed.last_name.is_none());
+    let topics = h.api.calls_to("editForumTopic");
+    assert_eq!(topics.len(), 1);
+    assert_eq!(topics[0]["name"], json!(format!("#T{:#06} Johnny ", user.id)));
+    let edits = h.api.calls_to("editMessageText");
+    assert_eq!(edits[0]["message_id"], json!(user.info_message.unwrap()));
+    assert!(h.api.calls_to("sendMessage").iter().any(|m| m["text"] == json!("User renamed: John Doe → Johnny")));
+
+    h.api.clear();
+    h.user_sends(json!({
+        "text": "Again",
+        "chat": { "id": USER, "type": "private", "first_name": "Johnny" },
+    })).await;
+    assert!(h.api.calls_to("editForumTopic").is_empty());
+}
+
+#[tokio::test]
+async fn info_message_shows_stats_and_agent() {
+    let mut h = Harness::new();
+    h.user_sends(text("Hello")).await;
+    let user = h.user().await;
+    h.staff_sends(user.topic, command("/alias Anna")).await;
+    h.api.clear();
+
+    h.staff_sends(user.topic, text("Hi")).await;
+
+    let edits = h.api.calls_to("editMessageText");
+    let info = edits.iter()
+        .find(|m| m["message_id"] == json!(user.info_message.unwrap()))
+        .expect("info message should be refreshed when agent is assigned");
+    let info = info["text"].as_str().unwrap();
+    assert!(info.contains("1 from user, 1 from staff"), "{info}");
+    assert!(info.contains("<b>Agent: </b> Anna"), "{info}");
+
+    h.api.clear();
+    h.staff_sends(user.topic, command("/close")).await;
+    let edits = h.api.calls_to("editMessageText");
+    assert!(edits.iter().any(|m| m["text"].as_str().unwrap().contains("<b>Past tickets: </b> 1")));
+}
+
+#[tokio::test]
+async fn banned_user_messages_are_ignored() {
+    let mut h = Harness::new();
+    h.user_sends(text("Hello")).await;
+    let user = h.user().await;
+    h.api.clear();
+
+    h.staff_sends(user.topic, command("/ban")).await;
+    assert!(h.user().await.banned);
+    let edits = h.api.calls_to("editMessageText");
+    assert!(edits.iter().any(|m| m["text"].as_str().unwrap().contains("<b>Banned: </b> yes")));
+    h.api.clear();
+
+    h.user_sends(text("Spam")).await;
+    h.user_sends(json!({
+        "text": "Renamed spam",
+        "chat": { "id": USER, "type": "private", "first_name": "Spammer" },
+    })).await;
+    assert!(h.api.calls().is_empty(), "banned user should get no reply, nothing relayed and no topic updates");
+    assert_eq!(h.user().await.first_name.as_deref(), Some("John"));
+
+    h.staff_sends(user.topic, command("/unban")).await;
+    assert!(!h.user().await.banned);
+    h.api.clear();
+    h.user_sends(text("Sorry")).await;
+    assert_eq!(h.api.calls_to("copyMessage").len(), 1);
+}
+
+#[tokio::test]
+async fn notes_are_typed_and_keep_history() {
+    let mut h = Harness::new();
+    h.user_sends(text("Hello")).await;
+    let user = h.user().await;
+    h.db.save_staff_alias(crate::database::InsertStaffAliasEntity { telegram_id: 99, alias: "Bob".to_string() }).await.unwrap();
+    h.db.insert_note_history(crate::database::InsertNoteHistoryEntity {
+        user_id: user.id,
+        key: "site".to_string(),
+        value: Some("old".to_string()),
+        type_: 0,
+        staff_id: Some(99),
+        created_at: 1700000000,
+    }).await.unwrap();
+    h.api.clear();
+
+    h.staff_sends(user.topic, command("/setnote site https://example.com")).await;
+    let replies = h.api.calls_to("sendMessage");
+    assert!(replies[0]["text"].as_str().unwrap().contains("replaced <code>old</code> set by Bob"), "{}", replies[0]["text"]);
+    let info = h.api.calls_to("editMessageText");
+    assert!(info[0]["text"].as_str().unwrap().contains("<a href=\"https://example.com\">"));
+
+    h.staff_sends(user.topic, command("/setnote due:date tomorrow")).await;
+    assert!(h.db.get_notes(&user).await.unwrap().iter().all(|n| n.key != "due"), "invalid typed note should not be saved");
+    h.staff_sends(user.topic, command("/setnote due 2024-03-05")).await;
+    let info = h.api.calls_to("editMessageText").pop().unwrap()["text"].as_str().unwrap().to_string();
+    assert!(info.contains("<b>due: </b>Mar 5, 2024"), "{info}");
+
+    h.staff_sends(user.topic, command("/delnote site")).await;
+    h.api.clear();
+    h.staff_sends(user.topic, command("/notehistory site")).await;
+    let history = h.api.calls_to("sendMessage")[0]["text"].as_str().unwrap().to_string();
+    assert!(history.contains("<b>Bob</b>: set <code>old</code>"), "{history}");
+    assert!(history.contains("set <code>https://example.com</code> (url)"), "{history}");
+    assert!(history.ends_with("deleted"), "{history}");
+}
+
+#[tokio::test]
+async fn tags_are_shown_in_topic_name_and_listed() {
+    let mut h = Harness::new();
+    h.user_sends(text("Hello")).await;
+    let user = h.user().await;
+    h.api.clear();
+
+    h.staff_sends(user.topic, command("/tag #VIP beta")).await;
+
+    let tags = h.db.get_tags(&user).await.unwrap();
+    assert_eq!(tags.iter().map(|t| t.tag.as_str()).collect::<Vec<_>>(), vec!["vip", "beta"]);
+    let renames = h.api.calls_to("editForumTopic");
+    assert_eq!(renames[0]["name"], json!(format!("[vip, beta] #T{:#06} John Doe", user.id)));
+    assert!(h.api.calls_to("editMessageText")[0]["text"].as_str().unwrap().contains("#vip #beta"));
+
+    h.staff_sends(user.topic, command("/tag bad-tag")).await;
+    assert_eq!(h.db.get_tags(&user).await.unwrap().len(), 2);
+
+    h.api.clear();
+    h.staff_sends(user.topic, command("/tagged vip")).await;
+    let list = h.api.calls_to("sendMessage")[0]["text"].as_str().unwrap().to_string();
+    assert!(list.contains(&format!("/{}\">[vip, beta] #T{:#06}", user.topic, user.id)), "{list}");
+
+    h.staff_sends(user.topic, command("/untag vip")).await;
+    assert_eq!(h.db.get_tags(&user).await.unwrap().len(), 1);
+    assert!(h.db.get_users_by_tag("vip").await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn users_are_routed_by_language_and_start_payload() {
+    let routes = json!({ "routes": [
+        { "superchat": -1002, "start": ["vip_"] },
+        { "superchat": -1003, "lang": ["EN"] },
+    ] });
+    let mut h = Harness::with_config(routes.clone());
+    h.user_sends(text("Hello")).await;
+    let user = h.user().await;
+    assert_eq!(user.superchat, Some(-1003));
+    assert_eq!(h.api.calls_to("createForumTopic")[0]["chat_id"], json!(-1003));
+    assert_eq!(h.api.calls_to("copyMessage")[0]["chat_id"], json!(-1003));
+
+    h.api.clear();
+    h.staff_sends_to(-1003, user.topic, text("Hi")).await;
+    assert_eq!(h.api.calls_to("copyMessage")[0]["chat_id"], json!(USER));
+    h.staff_sends(user.topic, text("Wrong superchat")).await;
+    assert_eq!(h.api.calls_to("copyMessage").len(), 1, "topic ids are scoped to superchat");
+
+    let mut h = Harness::with_config(routes);
+    h.user_sends(command("/start vip_42")).await;
+    let user = h.user().await;
+    assert_eq!(user.superchat, Some(-1002));
+    assert_eq!(user.start_payload.as_deref(), Some("vip_42"));
+    assert_eq!(h.api.calls_to("pinChatMessage")[0]["chat_id"], json!(-1002));
+}
+
+#[tokio::test]
+async fn tagged_user_is_moved_to_routed_superchat() {
+    let mut h = Harness::with_config(json!({ "routes": [{ "superchat": -1002, "tags": ["#vip"] }] }));
+    h.user_sends(text("Hello")).await;
+    let old = h.user().await;
+    assert_eq!(old.superchat, Some(SUPERCHAT));
+    h.api.clear();
+
+    h.staff_sends(old.topic, command("/tag vip")).await;
+
+    let user = h.user().await;
+    assert_eq!(user.superchat, Some(-1002));
+    assert_ne!(user.info_message, old.info_message);
+    let topics = h.api.calls_to("createForumTopic");
+    assert_eq!(topics[0]["chat_id"], json!(-1002));
+    assert_eq!(topics[0]["name"], json!(format!("[vip] #T{:#06} John Doe", user.id)));
+    let sent = h.api.calls_to("sendMessage");
+    assert!(sent.iter().any(|m| m["chat_id"] == json!(SUPERCHAT) && m["message_thread_id"] == json!(old.topic)
+        && m["text"].as_str().unwrap().contains(&format!("/{}\">another superchat", user.topic))));
+    assert_eq!(h.api.calls_to("pinChatMessage")[0]["chat_id"], json!(-1002));
+
+    h.api.clear();
+    h.user_sends(text("Still there?")).await;
+    assert_eq!(h.api.calls_to("copyMessage")[0]["chat_id"], json!(-1002));
+    assert_eq!(h.api.calls_to("copyMessage")[0]["message_thread_id"], json!(user.topic));
+}
+
+#[tokio::test]
+async fn start_payload_is_stored_and_creates_notes() {
+    let mut h = Harness::with_config(json!({ "start": { "notes": ["order"] } }));
+    h.user_sends(command("/start order_123-ref_ads-broken")).await;
+
+    let user = h.user().await;
+    assert_eq!(user.start_payload.as_deref(), Some("order_123-ref_ads-broken"));
+    let notes = h.db.get_notes(&user).await.unwrap();
+    assert_eq!(notes.iter().map(|n| (n.key.as_str(), n.value.as_str())).collect::<Vec<_>>(), vec![("order", "123")]);
+    assert_eq!(notes[0].type_, NoteType::Number as i16);
+    let info = h.api.calls_to("editMessageText").pop().unwrap()["text"].as_str().unwrap().to_string();
+    assert!(info.contains("<b>Source: </b> <code>order_123-ref_ads-broken</code>"), "{info}");
+    assert!(info.contains("<b>order: </b>123"), "{info}");
+
+    h.api.clear();
+    h.user_sends(command("/start order_124")).await;
+    assert_eq!(h.api.calls_to("createForumTopic").len(), 0);
+    assert_eq!(h.user().await.start_payload.as_deref(), Some("order_124"));
+    assert_eq!(h.db.get_notes(&user).await.unwrap()[0].value, "124");
+
+    h.staff_sends(user.topic, command("/notehistory order")).await;
+    let history = h.api.calls_to("sendMessage").pop().unwrap()["text"].as_str().unwrap().to_string();
+    assert!(history.contains("<b>deep link</b>: set <code>124</code> (number)"), "{history}");
+}
+
+#[tokio::test]
+async fn localization_reload_reports_errors_and_keeps_messages() {
+    let tmp = tempfile::tempdir().unwrap();
+    let dir = tmp.path();
+    let cfg = serde_json::from_value(json!({ "paths": [dir.to_str().unwrap()] })).unwrap();
+    let mut h = Harness::new();
+    h.loc = std::sync::Arc::new(localization::from_config(Some(cfg)).await.unwrap());
+
+    h.staff_sends(1, command("/reloadloc")).await;
+    assert!(h.api.calls_to("sendMessage")[0]["text"].as_str().unwrap().starts_with("Localization reloaded"));
+
+    std::fs::remove_dir_all(dir).unwrap();
+    h.api.clear();
+    h.staff_sends(1, command("/reloadloc")).await;
+    let reply = h.api.calls_to("sendMessage")[0]["text"].as_str().unwrap().to_string();
+    assert!(reply.starts_with("Failed to reload localization"), "{reply}");
+
+    h.user_sends(command("/start")).await;
+    let welcome = h.api.calls_to("sendMessage").pop().unwrap();
+    assert_eq!(welcome["text"], json!("Welcome to support chat! Ask your questions here"));
+}
+
+#[tokio::test]
+async fn language_choice_overrides_client_language() {
+    let tmp = tempfile::tempdir().unwrap();
+    let dir = tmp.path();
+    std::fs::write(dir.join("en.json"), r#"{"common.userReply": {"defaultMessage": "Thanks"}}"#).unwrap();
+    std::fs::write(dir.join("ru.json"), r#"{"common.userReply": {"defaultMessage": "Спасибо"}, "common.languageChanged": {"defaultMessage": "Язык изменён"}}"#).unwrap();
+    let cfg = serde_json::from_value(json!({ "paths": [dir.to_str().unwrap()] })).unwrap();
+    let mut h = Harness::new();
+    h.loc = std::sync::Arc::new(localization::from_config(Some(cfg)).await.unwrap());
+
+    h.user_sends(command("/language")).await;
+    let prompt = h.api.calls_to("sendMessage").pop().unwrap();
+    let data = prompt["reply_markup"]["inline_keyboard"].as_array().unwrap().iter()
+        .flat_map(|row| row.as_array().unwrap())
+        .map(|b| b["callback_data"].as_str().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(data, vec!["lang:en", "lang:ru", "lang:"]);
+
+    h.user_clicks("lang:ru").await;
+    let user = h.user().await;
+    assert_eq!(user.lang_code.as_deref(), Some("ru"));
+    assert!(user.lang_selected);
+    assert_eq!(h.api.calls_to("sendMessage").pop().unwrap()["text"], json!("Язык изменён"));
+
+    h.api.clear();
+    h.user_sends(text("Hello")).await;
+    assert_eq!(h.user().await.lang_code.as_deref(), Some("ru"), "client language should not override choice");
+    assert_eq!(h.api.calls_to("sendMessage").pop().unwrap()["text"], json!("Спасибо"));
+
+    h.user_clicks("lang:de").await;
+    assert_eq!(h.user().await.lang_code.as_deref(), Some("ru"), "unknown language should be ignored");
+
+    h.user_clicks("lang:").await;
+    let user = h.user().await;
+    assert_eq!(user.lang_code.as_deref(), Some("en"));
+    assert!(!user.lang_selected);
+}
+
+#[tokio::test]
+async fn staff_messages_and_commands_follow_configured_languages() {
+    let tmp = tempfile::tempdir().unwrap();
+    let dir = tmp.path();
+    std::fs::write(dir.join("ru.json"), r#"{
+        "staff.noteSaved": {"defaultMessage": "Заметка сохранена"},
+        "staff.noteDate": {"defaultMessage": "{date, date}"},
+        "commands.user.faq": {"defaultMessage": "частые вопросы"},
+        "commands.staff.close": {"defaultMessage": "закрыть диалог"}
+    }"#).unwrap();
+    let cfg = serde_json::from_value(json!({ "paths": [dir.to_str().unwrap()] })).unwrap();
+    let mut h = Harness::with_config(json!({ "staff_language": "ru" }));
+    h.loc = std::sync::Arc::new(localization::from_config(Some(cfg)).await.unwrap());
+    h.user_sends(text("Hello")).await;
+    let topic = h.user().await.topic;
+
+    h.api.clear();
+    h.staff_sends(topic, command("/setnote order 123")).await;
+    assert_eq!(h.api.calls_to("sendMessage")[0]["text"], json!("Заметка сохранена"));
+    h.staff_sends(topic, command("/setnote due 2024-03-05")).await;
+    let info = h.api.calls_to("editMessageText").pop().unwrap()["text"].as_str().unwrap().to_string();
+    assert!(info.contains("<b>due: </b>5 мар. 2024\u{202f}г."), "{info}");
+
+    h.api.clear();
+    h.user_sends(command("/help")).await;
+    let help = h.api.calls_to("sendMessage")[0]["text"].as_str().unwrap().to_string();
+    assert!(help.starts_with("These commands are supported:\n\n/help — "), "{help}");
+    assert!(help.contains("/faq — show FAQ."), "{help}");
+
+    h.api.clear();
+    crate::telegram::commands::register(&h.api.bot(), &h.cfg, &h.loc).await.unwrap();
+    let calls = h.api.calls_to("setMyCommands");
+    assert_eq!(calls.len(), 3);
+    let description = |call: &serde_json::Value, command: &str| call["commands"].as_array().unwrap().iter()
+        .find(|c| c["command"].as_str().unwrap().trim_start_matches('/') == command)
+        .map(|c| c["description"].clone())
+        .unwrap();
+    assert_eq!(description(&calls[0], "faq"), json!("show FAQ."));
+    assert_eq!(calls[1]["language_code"], json!("ru"));
+    assert_eq!(description(&calls[1], "faq"), json!("частые вопросы"));
+    assert_eq!(calls[2]["scope"]["chat_id"], json!(SUPERCHAT));
+    assert_eq!(description(&calls[2], "close"), json!("закрыть диалог"));
+}
+
+#[tokio::test]
+async fn messages_are_translated_between_user_and_staff() {
+    let mut h = Harness::new();
+    h.user_sends(text("Hello")).await;
+    let topic = h.user().await.topic;
+
+    h.api.clear();
+    h.staff_sends(topic, command("/tr Hello")).await;
+    assert_eq!(h.api.calls_to("sendMessage")[0]["text"], json!("Translation is not configured"));
+
+    h.tr = Some(std::sync::Arc::new(crate::translation::Translation::new(
+        serde_json::from_value(json!({ "type": "Stub", "staff_languages": ["en", "ru"] })).unwrap(),
+    ).unwrap()));
+    let user = h.user().await;
+    h.db.update_user_profile(&crate::database::UserEntity { lang_code: Some("tr".to_string()), lang_selected: true, ..user }).await.unwrap();
+
+    h.api.clear();
+    h.user_sends(text("Merhaba")).await;
+    let sent = h.api.calls_to("sendMessage");
+    let translation = sent.iter().position(|m| m["chat_id"] == json!(SUPERCHAT)).unwrap();
+    let reply = sent.iter().position(|m| m["chat_id"] == json!(USER)).unwrap();
+    assert!(reply < translation, "auto reply should not wait for translation");
+    let translation = &sent[translation];
+    assert_eq!(translation["text"], json!("🌐 tr: [en] Merhaba"));
+    assert_eq!(translation["message_thread_id"], json!(topic));
+
+    h.api.clear();
+    h.staff_sends(topic, command("/tr Hello")).await;
+    let sent = h.api.calls_to("sendMessage");
+    assert_eq!(sent[0]["chat_id"], json!(USER));
+    assert_eq!(sent[0]["text"], json!("[tr] Hello"));
+    assert_eq!(sent[1]["text"], json!("🌐 Sent in tr: [tr] Hello"));
+    assert_eq!(h.api.calls_to("setMessageReaction").len(), 1, "sent reply should be recorded like relayed one");
+}
+
+#[test]
+fn failed_update_is_counted_once_by_outer_handler() {
+    let recorder = crate::metrics::builder().unwrap().build_recorder();
+    let handle = recorder.handle();
+    let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+    metrics::with_local_recorder(&recorder, || runtime.block_on(async {
+        let mut h = Harness::new();
+        h.user_sends(text("Hello")).await;
+        h.api.fail("copyMessage");
+        // edit of message bot hasn't seen is handled as new message
+        let edit = json!({ "edited_message": {
+            "message_id": 99,
+            "date": 1700000000,
+            "edit_date": 1700000100,
+            "chat": { "id": USER, "type": "private", "first_name": "John", "last_name": "Doe" },
+            "from": { "id": USER, "is_bot": false, "first_name": "John" },
+            "text": "Edited",
+        } });
+        assert!(h.dispatch(edit).await.is_err());
+    }));
+
+    let rendered = handle.render();
+    assert!(rendered.contains("support_handler_errors_total{endpoint=\"user_update\"} 1"), "{rendered}");
+    assert!(!rendered.contains("endpoint=\"user_msg\""), "{rendered}");
+}
\ No newline at end of file
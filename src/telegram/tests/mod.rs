@@ -0,0 +1,163 @@
+mod api;
+mod handlers;
+mod sla;
+mod survey;
+
+use std::ops::ControlFlow;
+use std::sync::Arc;
+use serde_json::{json, Value};
+use teloxide::prelude::*;
+use teloxide::types::Me;
+use crate::database::{self, Database, UserEntity};
+use crate::localization::LocalizationBundle;
+use crate::telegram::{schema, HandlerResult, TelegramConfig};
+use crate::translation::Translation;
+pub use api::FakeApi;
+
+pub const SUPERCHAT: i64 = -1001234567890;
+pub const USER: i64 = 42;
+pub const STAFF: i64 = 7;
+
+/// Runs update handlers against [FakeApi] and in-memory database
+pub struct Harness {
+    pub api: FakeApi,
+    pub db: Arc<Box<dyn Database>>,
+    pub cfg: TelegramConfig,
+    pub loc: Arc<LocalizationBundle>,
+    pub tr: Option<Arc<Translation>>,
+    update_id: i32,
+    message_id: i32,
+}
+
+impl Harness {
+    pub fn new() -> Harness {
+        Harness::with_config(json!({}))
+    }
+
+    /// Creates harness with telegram config fields overridden by `overrides`
+    pub fn with_config(overrides: Value) -> Harness {
+        let mut cfg = json!({ "token": "test", "superchat": SUPERCHAT });
+        for (k, v) in overrides.as_object().unwrap() {
+            cfg[k] = v.clone();
+        }
+        Harness {
+            api: FakeApi::start(),
+            db: Arc::new(database::sqlite_in_memory().unwrap()),
+            cfg: serde_json::from_value(cfg).unwrap(),
+            loc: Arc::new(LocalizationBundle::new()),
+            tr: None,
+            update_id: 0,
+            message_id: 0,
+        }
+    }
+
+    pub async fn dispatch(&mut self, update: Value) -> HandlerResult {
+        self.update_id += 1;
+        let mut update = update;
+        update["update_id"] = json!(self.update_id);
+        let update: Update = serde_json::from_str(&update.to_string()).unwrap();
+        let me: Me = serde_json::from_value(api::me()).unwrap();
+        let deps = dptree::deps![update, me, self.api.bot(), self.cfg.clone(), self.db.clone(), self.loc.clone(), self.tr.clone()];
+        match schema(self.cfg.superchats()).dispatch(deps).await {
+            ControlFlow::Break(result) => result,
+            ControlFlow::Continue(_) => panic!("update was not handled"),
+        }
+    }
+
+    /// Sends message from user to bot. Returns message id
+    pub async fn user_sends(&mut self, content: Value) -> i32 {
+        let id = self.next_message_id();
+        let message = merge(json!({
+            "message_id": id,
+            "date": 1700000000,
+            "chat": { "id": USER, "type": "private", "first_name": "John", "last_name": "Doe" },
+            "from": user(),
+        }), content);
+        self.dispatch(json!({ "message": message })).await.unwrap();
+        id
+    }
+
+    pub async fn user_edits(&mut self, id: i32, content: Value) {
+        let message = merge(json!({
+            "message_id": id,
+            "date": 1700000000,
+            "edit_date": 1700000100,
+            "chat": { "id": USER, "type": "private", "first_name": "John", "last_name": "Doe" },
+            "from": user(),
+        }), content);
+        self.dispatch(json!({ "edited_message": message })).await.unwrap();
+    }
+
+    /// Sends message from staff to user topic. Returns message id
+    pub async fn staff_sends(&mut self, topic: i64, content: Value) -> i32 {
+        self.staff_sends_to(SUPERCHAT, topic, content).await
+    }
+
+    /// Sends message from staff to user topic in given superchat. Returns message id
+    pub async fn staff_sends_to(&mut self, superchat: i64, topic: i64, content: Value) -> i32 {
+        let id = self.next_message_id();
+        let message = merge(json!({
+            "message_id": id,
+            "date": 1700000600,
+            "chat": { "id": superchat, "type": "supergroup", "title": "Support", "is_forum": true },
+            "from": staff(),
+            "message_thread_id": topic,
+            "is_topic_message": true,
+        }), content);
+        self.dispatch(json!({ "message": message })).await.unwrap();
+        id
+    }
+
+    pub async fn staff_edits(&mut self, topic: i64, id: i32, content: Value) {
+        let message = merge(json!({
+            "message_id": id,
+            "date": 1700000600,
+            "edit_date": 1700000700,
+            "chat": { "id": SUPERCHAT, "type": "supergroup", "title": "Support", "is_forum": true },
+            "from": staff(),
+            "message_thread_id": topic,
+            "is_topic_message": true,
+        }), content);
+        self.dispatch(json!({ "edited_message": message })).await.unwrap();
+    }
+
+    /// Presses inline keyboard button with callback `data`
+    pub async fn user_clicks(&mut self, data: &str) {
+        let query = json!({ "id": self.update_id.to_string(), "from": user(), "chat_instance": "1", "data": data });
+        self.dispatch(json!({ "callback_query": query })).await.unwrap();
+    }
+
+    pub async fn user(&self) -> UserEntity {
+        self.db.get_user_by_tg_id(UserId(USER as u64)).await.unwrap().expect("user should exist")
+    }
+
+    fn next_message_id(&mut self) -> i32 {
+        self.message_id += 1;
+        self.message_id
+    }
+}
+
+pub fn text(text: &str) -> Value {
+    json!({ "text": text })
+}
+
+/// Text message with bot command entity at the start
+pub fn command(text: &str) -> Value {
+    let length = text.split(' ').next().unwrap().encode_utf16().count();
+    json!({ "text": text, "entities": [{ "type": "bot_command", "offset": 0, "length": length }] })
+}
+
+fn user() -> Value {
+    json!({ "id": USER, "is_bot": false, "first_name": "John", "last_name": "Doe", "language_code": "en" })
+}
+
+fn staff() -> Value {
+    json!({ "id": STAFF, "is_bot": false, "first_name": "Anna" })
+}
+
+fn merge(mut base: Value, content: Value) -> Value {
+    for (k, v) in content.as_object().unwrap() {
+        base[k] = v.clone();
+    }
+    base
+}
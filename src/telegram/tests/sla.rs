@@ -0,0 +1,86 @@
+use serde_json::json;
+use crate::database::ConversationEntity;
+use crate::telegram::sla::{self, Breach, SlaConfig};
+use super::{text, Harness, SUPERCHAT};
+
+fn config(first_response: Option<i64>, response: Option<i64>) -> SlaConfig {
+    serde_json::from_value(json!({ "first_response": first_response, "response": response })).unwrap()
+}
+
+fn conversation(first_response_at: Option<i64>, unanswered_since: Option<i64>) -> ConversationEntity {
+    ConversationEntity {
+        id: 1,
+        user_id: 1,
+        opened_at: 1000,
+        first_response_at,
+        unanswered_since,
+        closed_at: None,
+        breached_at: None,
+        staff_id: None,
+    }
+}
+
+#[test]
+fn first_response_is_checked_before_reply() {
+    let sla = config(Some(600), Some(3600));
+    let waiting = conversation(None, Some(1000));
+    assert!(matches!(Breach::find(&sla, &waiting, 1601), Some(Breach::FirstResponse { waiting: 601 })));
+    assert!(Breach::find(&sla, &waiting, 1600).is_none(), "exactly at threshold is not a breach");
+
+    let answered = conversation(Some(1200), Some(2000));
+    assert!(Breach::find(&sla, &answered, 2000 + 3600).is_none());
+    assert!(matches!(Breach::find(&sla, &answered, 2000 + 3601), Some(Breach::Response { waiting: 3601 })));
+
+    let only_response = config(None, Some(3600));
+    assert!(matches!(Breach::find(&only_response, &waiting, 1000 + 3601), Some(Breach::Response { .. })));
+}
+
+#[test]
+fn breached_and_answered_conversations_are_skipped() {
+    let sla = config(Some(600), Some(3600));
+    let breached = ConversationEntity { breached_at: Some(1700), ..conversation(None, Some(1000)) };
+    assert!(Breach::find(&sla, &breached, 100_000).is_none());
+    assert!(Breach::find(&sla, &conversation(Some(1200), None), 100_000).is_none());
+}
+
+#[tokio::test]
+async fn breach_is_escalated_marked_and_cleared_by_reply() {
+    let mut h = Harness::with_config(json!({ "sla": { "response": 60, "escalation_thread": 5, "breach_icon": "123" } }));
+    h.user_sends(text("Hello")).await;
+    let user = h.user().await;
+    let sla = h.cfg.sla.clone().unwrap();
+
+    h.api.clear();
+    sla::check(&h.api.bot(), &h.cfg, &sla, &**h.db, &h.loc).await.unwrap();
+    let alerts = h.api.calls_to("sendMessage");
+    assert_eq!(alerts.len(), 1);
+    assert_eq!(alerts[0]["chat_id"], json!(SUPERCHAT));
+    assert_eq!(alerts[0]["message_thread_id"], json!(5));
+    let marks = h.api.calls_to("editForumTopic");
+    assert!(marks[0]["name"].as_str().unwrap().starts_with("🔥 "), "{}", marks[0]);
+    assert_eq!(marks[0]["icon_custom_emoji_id"], json!("123"));
+    assert!(h.db.get_open_conversation(&user).await.unwrap().unwrap().breached_at.is_some());
+
+    h.api.clear();
+    sla::check(&h.api.bot(), &h.cfg, &sla, &**h.db, &h.loc).await.unwrap();
+    assert!(h.api.calls_to("sendMessage").is_empty(), "breach is reported once");
+
+    h.staff_sends(user.topic, text("Sorry for the wait")).await;
+    let marks = h.api.calls_to("editForumTopic");
+    assert!(!marks[0]["name"].as_str().unwrap().starts_with("🔥"), "{}", marks[0]);
+    assert_eq!(marks[0]["icon_custom_emoji_id"], json!(""));
+    assert!(h.db.get_open_conversation(&user).await.unwrap().unwrap().breached_at.is_none());
+}
+
+#[tokio::test]
+async fn breach_alert_goes_to_user_topic_without_escalation_thread() {
+    let mut h = Harness::with_config(json!({ "sla": { "first_response": 60 } }));
+    h.user_sends(text("Hello")).await;
+    let user = h.user().await;
+
+    h.api.clear();
+    sla::check(&h.api.bot(), &h.cfg, h.cfg.sla.as_ref().unwrap(), &**h.db, &h.loc).await.unwrap();
+    let alerts = h.api.calls_to("sendMessage");
+    assert_eq!(alerts[0]["message_thread_id"], json!(user.topic));
+    assert!(h.api.calls_to("editForumTopic")[0].get("icon_custom_emoji_id").is_none());
+}
\ No newline at end of file
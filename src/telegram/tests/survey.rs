@@ -0,0 +1,152 @@
+use serde_json::{json, Value};
+use crate::database::InsertRatingEntity;
+use super::{command, text, Harness, SUPERCHAT, USER};
+
+/// Closes conversation with user and returns id of rated conversation and rating prompt message
+async fn close(h: &mut Harness) -> (i32, i32) {
+    h.user_sends(text("Hello")).await;
+    let user = h.user().await;
+    h.staff_sends(user.topic, command("/close")).await;
+    let prompt = h.api.calls_to("sendMessage").into_iter()
+        .find(|m| m["chat_id"] == json!(USER) && !m["reply_markup"].is_null())
+        .expect("user should be asked for rating");
+    let data = prompt["reply_markup"]["inline_keyboard"][0][0]["callback_data"].as_str().unwrap();
+    let conversation = data.split(':').nth(1).unwrap().parse().unwrap();
+    let rating = h.db.get_rating(conversation).await.unwrap().unwrap();
+    h.api.clear();
+    (conversation, rating.prompt_message as i32)
+}
+
+/// Moves time of picking stars before message date by `seconds`
+async fn rated_ago(h: &Harness, conversation: i32, seconds: i64) {
+    let mut rating = h.db.get_rating(conversation).await.unwrap().unwrap();
+    rating.rated_at = Some(1700000000 - seconds);
+    h.db.update_rating(rating).await.unwrap();
+}
+
+fn reply_to(id: i32, content: Value) -> Value {
+    let mut content = content;
+    content["reply_to_message"] = json!({
+        "message_id": id,
+        "date": 0,
+        "chat": { "id": USER, "type": "private", "first_name": "John" },
+        "text": "Rate us",
+    });
+    content
+}
+
+#[tokio::test]
+async fn comment_after_rating_is_saved_and_not_relayed() {
+    let mut h = Harness::new();
+    let (conversation, prompt) = close(&mut h).await;
+
+    h.user_clicks(&format!("rate:{}:4", conversation)).await;
+    let rating = h.db.get_rating(conversation).await.unwrap().unwrap();
+    assert_eq!(rating.rating, Some(4));
+    assert!(rating.awaiting_comment);
+    let edits = h.api.calls_to("editMessageText");
+    assert_eq!(edits[0]["message_id"], json!(prompt));
+    assert_eq!(edits[0]["reply_markup"]["inline_keyboard"][0][0]["callback_data"], json!(format!("rate_skip:{}", conversation)));
+    h.api.clear();
+
+    h.user_sends(text("Quick and helpful")).await;
+    assert!(h.api.calls_to("copyMessage").is_empty(), "comment should not be relayed");
+    let rating = h.db.get_rating(conversation).await.unwrap().unwrap();
+    assert_eq!(rating.comment.as_deref(), Some("Quick and helpful"));
+    assert!(!rating.awaiting_comment);
+    let sent = h.api.calls_to("sendMessage");
+    assert!(sent.iter().any(|m| m["chat_id"] == json!(USER)), "user should be thanked");
+    assert!(sent.iter().any(|m| m["chat_id"] == json!(SUPERCHAT) && m["text"].as_str().unwrap().contains("Quick and helpful")));
+}
+
+#[tokio::test]
+async fn skipped_comment_does_not_eat_next_message() {
+    let mut h = Harness::new();
+    let (conversation, _) = close(&mut h).await;
+
+    h.user_clicks(&format!("rate:{}:5", conversation)).await;
+    h.user_clicks(&format!("rate_skip:{}", conversation)).await;
+    h.api.clear();
+    h.user_sends(text("Another question")).await;
+
+    assert_eq!(h.api.calls_to("copyMessage").len(), 1);
+    let rating = h.db.get_rating(conversation).await.unwrap().unwrap();
+    assert_eq!(rating.rating, Some(5));
+    assert_eq!(rating.comment, None);
+}
+
+#[tokio::test]
+async fn late_message_is_relayed_and_ends_wait() {
+    let mut h = Harness::new();
+    let (conversation, prompt) = close(&mut h).await;
+    h.user_clicks(&format!("rate:{}:3", conversation)).await;
+    rated_ago(&h, conversation, 601).await;
+    h.api.clear();
+
+    h.user_sends(text("Another question")).await;
+
+    assert_eq!(h.api.calls_to("copyMessage").len(), 1, "message after comment window should be relayed");
+    let rating = h.db.get_rating(conversation).await.unwrap().unwrap();
+    assert_eq!(rating.comment, None);
+    assert!(!rating.awaiting_comment);
+    assert_eq!(h.api.calls_to("editMessageReplyMarkup")[0]["message_id"], json!(prompt), "skip button should be removed");
+}
+
+#[tokio::test]
+async fn reply_to_prompt_is_comment_after_window() {
+    let mut h = Harness::new();
+    let (conversation, prompt) = close(&mut h).await;
+    h.user_clicks(&format!("rate:{}:2", conversation)).await;
+    rated_ago(&h, conversation, 3600).await;
+    h.api.clear();
+
+    h.user_sends(reply_to(prompt, text("Took too long"))).await;
+
+    assert!(h.api.calls_to("copyMessage").is_empty());
+    let rating = h.db.get_rating(conversation).await.unwrap().unwrap();
+    assert_eq!(rating.comment.as_deref(), Some("Took too long"));
+}
+
+#[tokio::test]
+async fn new_conversation_ends_wait() {
+    let mut h = Harness::new();
+    let (conversation, _) = close(&mut h).await;
+    h.user_clicks(&format!("rate:{}:5", conversation)).await;
+    h.api.clear();
+
+    let photo = json!({ "photo": [{ "file_id": "p", "file_unique_id": "p", "width": 1, "height": 1 }] });
+    h.user_sends(photo).await;
+    assert_eq!(h.api.calls_to("copyMessage").len(), 1);
+    assert!(!h.db.get_rating(conversation).await.unwrap().unwrap().awaiting_comment);
+
+    h.user_sends(text("And one more thing")).await;
+    assert_eq!(h.api.calls_to("copyMessage").len(), 2);
+    assert_eq!(h.db.get_rating(conversation).await.unwrap().unwrap().comment, None);
+}
+
+#[tokio::test]
+async fn rating_of_other_user_is_ignored() {
+    let mut h = Harness::new();
+    h.user_sends(text("Hello")).await;
+    let user = h.user().await;
+    let other = h.db.insert_rating(InsertRatingEntity {
+        conversation_id: 99,
+        user_id: user.id + 1,
+        staff_id: None,
+        prompt_message: 1,
+        rating: None,
+        comment: None,
+        awaiting_comment: false,
+        rated_at: None,
+    }).await.unwrap();
+    h.api.clear();
+
+    h.user_clicks("rate:99:1").await;
+    h.user_clicks("rate_skip:99").await;
+
+    let rating = h.db.get_rating(99).await.unwrap().unwrap();
+    assert_eq!(rating.rating, None);
+    assert_eq!(rating.rated_at, other.rated_at);
+    assert!(h.api.calls_to("editMessageText").is_empty());
+    assert_eq!(h.api.calls().len(), 2, "only callback queries should be answered");
+}
\ No newline at end of file
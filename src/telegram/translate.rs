@@ -0,0 +1,65 @@
+use teloxide::prelude::*;
+use teloxide::types::MessageId;
+use tracing::warn;
+use crate::database::{Database, UserEntity};
+use crate::localization::{LocalizationBundle, StaffMessages};
+use crate::telegram::relay::Destination;
+use crate::telegram::signature::{Signature, TEXT_LIMIT};
+use crate::telegram::utils::MessageBuilder;
+use crate::telegram::TelegramConfig;
+use crate::translation::Translation;
+
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+/// Posts translation of user message to the topic. Message is already relayed, so failures are only logged
+pub async fn incoming(bot: &Bot, cfg: &TelegramConfig, loc: &LocalizationBundle, tr: &Translation, user: &UserEntity, msg: &Message, topic: Destination) {
+    let Some(text) = msg.text().or(msg.caption()) else {
+        return;
+    };
+    let staff_lang = cfg.staff_lang(topic.chat);
+    let translated = match tr.for_staff(text, user.lang_code.as_deref(), staff_lang.as_deref()).await {
+        Ok(Some(translated)) => translated,
+        Ok(None) => return,
+        Err(e) => {
+            warn!("Failed to translate message of user {}: {}", user.id, e);
+            return;
+        }
+    };
+    let text = loc.localize(staff_lang, StaffMessages::Translation {
+        source: translated.source.unwrap_or("?".to_string()),
+        text: translated.text,
+    });
+    let sent = MessageBuilder::new(bot.send_message(topic.chat, text))
+        .with(topic.thread, |t, v| v.message_thread_id(t))
+        .build()
+        .disable_notification(true)
+        .await;
+    if let Err(e) = sent {
+        warn!("Failed to post translation for user {}: {}", user.id, e);
+    }
+}
+
+/// Translates staff text of `/tr` into user language. Error is reply for staff explaining why text can't be sent
+pub async fn for_user(loc: &LocalizationBundle, tr: Option<&Translation>, user: &UserEntity, text: &str, staff_lang: Option<String>) -> std::result::Result<String, String> {
+    let reply = match (tr, user.lang_code.as_deref()) {
+        (None, _) => StaffMessages::TranslationDisabled,
+        (Some(_), None) => StaffMessages::TranslationNoLanguage,
+        (Some(tr), Some(lang)) => match tr.for_user(text, lang).await {
+            Ok(translated) => return Ok(translated),
+            Err(e) => StaffMessages::TranslationFailed { error: e.to_string() },
+        },
+    };
+    Err(loc.localize(staff_lang, reply))
+}
+
+/// Sends translated text to user with staff signature
+pub async fn send(bot: &Bot, cfg: &TelegramConfig, db: &dyn Database, loc: &LocalizationBundle, user: &UserEntity, msg: &Message, text: &str) -> Result<MessageId> {
+    let (signed, entities) = match Signature::of(&cfg.signature, db, loc, user, msg).await? {
+        Some(signature) => signature.apply(Some(text.to_string()), vec![], TEXT_LIMIT),
+        None => (Some(text.to_string()), vec![]),
+    };
+    let sent = bot.send_message(ChatId(user.telegram_id), signed.unwrap_or_default())
+        .entities(entities)
+        .await?;
+    Ok(sent.id)
+}
\ No newline at end of file
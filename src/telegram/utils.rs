@@ -1,4 +1,9 @@
 use std::ops::{Deref, DerefMut};
+use teloxide::payloads::SendMessage;
+use teloxide::prelude::*;
+use teloxide::requests::JsonRequest;
+use teloxide::types::{MediaKind, Message, MessageKind, Recipient};
+use crate::localization::Localized;
 
 pub struct MessageBuilder<T>(T);
 
@@ -32,4 +37,40 @@ impl<T> DerefMut for MessageBuilder<T> {
     fn deref_mut(&mut self) -> &mut Self::Target {
         &mut self.0
     }
-}
\ No newline at end of file
+}
+
+/// Message request with text and parse mode of localized message
+pub fn send_localized(bot: &Bot, chat: impl Into<Recipient>, msg: Localized) -> JsonRequest<SendMessage> {
+    MessageBuilder::new(bot.send_message(chat, msg.text))
+        .with(msg.parse_mode, |mode, r| r.parse_mode(mode.into()))
+        .build()
+}
+
+pub fn media_kind_name(msg: &Message) -> &'static str {
+    let common = match msg.kind {
+        MessageKind::Common(ref common) => common,
+        MessageKind::Dice(_) => return "dice",
+        MessageKind::Invoice(_) => return "invoice",
+        MessageKind::Giveaway(_) => return "giveaway",
+        MessageKind::GiveawayWinners(_) => return "giveaway_winners",
+        _ => return "other",
+    };
+    match common.media_kind {
+        MediaKind::Animation(_) => "animation",
+        MediaKind::Audio(_) => "audio",
+        MediaKind::Contact(_) => "contact",
+        MediaKind::Document(_) => "document",
+        MediaKind::Game(_) => "game",
+        MediaKind::Venue(_) => "venue",
+        MediaKind::Location(_) => "location",
+        MediaKind::Photo(_) => "photo",
+        MediaKind::Poll(_) => "poll",
+        MediaKind::Sticker(_) => "sticker",
+        MediaKind::Story(_) => "story",
+        MediaKind::Text(_) => "text",
+        MediaKind::Video(_) => "video",
+        MediaKind::VideoNote(_) => "video_note",
+        MediaKind::Voice(_) => "voice",
+        _ => "other",
+    }
+}
@@ -0,0 +1,57 @@
+use std::time::Duration;
+use async_trait::async_trait;
+use serde::Deserialize;
+use serde_json::json;
+use crate::translation::{Result, Translated, Translator};
+
+pub struct LibreTranslate {
+    client: reqwest::Client,
+    url: String,
+    api_key: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Response {
+    #[serde(rename = "translatedText")]
+    translated_text: String,
+    #[serde(rename = "detectedLanguage")]
+    detected_language: Option<DetectedLanguage>,
+}
+
+#[derive(Deserialize)]
+struct DetectedLanguage {
+    language: String,
+}
+
+#[derive(Deserialize)]
+struct ErrorResponse {
+    error: String,
+}
+
+impl LibreTranslate {
+    pub fn new(url: String, api_key: Option<String>, timeout: Duration) -> Result<LibreTranslate> {
+        let client = reqwest::Client::builder().timeout(timeout).build()?;
+        Ok(LibreTranslate { client, url: url.trim_end_matches('/').to_string(), api_key })
+    }
+}
+
+#[async_trait]
+impl Translator for LibreTranslate {
+    async fn translate(&self, text: &str, source: Option<&str>, target: &str) -> Result<Translated> {
+        let mut body = json!({ "q": text, "source": source.unwrap_or("auto"), "target": target, "format": "text" });
+        if let Some(ref key) = self.api_key {
+            body["api_key"] = json!(key);
+        }
+        let response = self.client.post(format!("{}/translate", self.url)).json(&body).send().await?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let error = response.json::<ErrorResponse>().await.map(|e| e.error).unwrap_or_default();
+            return Err(format!("LibreTranslate responded {status}: {error}").into());
+        }
+        let response = response.json::<Response>().await?;
+        Ok(Translated {
+            text: response.translated_text,
+            source: response.detected_language.map(|d| d.language).or(source.map(|s| s.to_string())),
+        })
+    }
+}
\ No newline at end of file
@@ -0,0 +1,133 @@
+use std::error::Error;
+use std::time::Duration;
+use async_trait::async_trait;
+use serde::Deserialize;
+use tracing::info;
+use crate::config::ConfigErrors;
+
+mod libre;
+#[cfg(test)]
+mod tests;
+
+type Result<T> = std::result::Result<T, Box<dyn Error + Send + Sync>>;
+
+pub struct Translated {
+    pub text: String,
+    /// Detected source language, if translator reports it
+    pub source: Option<String>,
+}
+
+#[async_trait]
+pub trait Translator: Send + Sync {
+    /// Translates text into `target` language. Source language is detected if not set
+    async fn translate(&self, text: &str, source: Option<&str>, target: &str) -> Result<Translated>;
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(tag = "type")]
+pub enum TranslatorConfig {
+    /// LibreTranslate compatible `/translate` endpoint, like self-hosted `http://localhost:5000`
+    LibreTranslate {
+        url: String,
+        #[serde(default)]
+        api_key: Option<String>,
+        /// Seconds to wait for translation before giving up
+        #[serde(default = "default_timeout")]
+        timeout: u64,
+    },
+    /// Prefixes text with target language instead of translating, for tests and dry runs
+    Stub,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct TranslationConfig {
+    #[serde(flatten)]
+    pub translator: TranslatorConfig,
+    /// Languages staff reads. Messages in them aren't translated, others are translated into staff language
+    /// of the superchat or the first of these
+    #[serde(default = "default_staff_languages")]
+    pub staff_languages: Vec<String>,
+}
+
+impl TranslationConfig {
+    pub fn validate(&self, errors: &mut ConfigErrors) {
+        if let TranslatorConfig::LibreTranslate { ref url, timeout, .. } = self.translator {
+            let valid = reqwest::Url::parse(url).is_ok_and(|u| u.scheme() == "http" || u.scheme() == "https");
+            if !valid {
+                errors.add("translation.url", format!("{} is not an http(s) URL", url));
+            }
+            if timeout == 0 {
+                errors.add("translation.timeout", "should be at least 1 second");
+            }
+        }
+        if self.staff_languages.is_empty() {
+            errors.add("translation.staff_languages", "should list at least one language");
+        }
+    }
+}
+
+fn default_staff_languages() -> Vec<String> {
+    vec!["en".to_string()]
+}
+
+fn default_timeout() -> u64 {
+    10
+}
+
+/// Translation between users and staff
+pub struct Translation {
+    translator: Box<dyn Translator>,
+    staff_languages: Vec<String>,
+}
+
+impl Translation {
+    pub fn new(config: TranslationConfig) -> Result<Translation> {
+        let translator: Box<dyn Translator> = match config.translator {
+            TranslatorConfig::LibreTranslate { url, api_key, timeout } => {
+                info!("Translating messages with LibreTranslate at {url}");
+                Box::new(libre::LibreTranslate::new(url, api_key, Duration::from_secs(timeout))?)
+            }
+            TranslatorConfig::Stub => {
+                info!("Using stub translator, messages are not really translated");
+                Box::new(Stub)
+            }
+        };
+        Ok(Translation { translator, staff_languages: config.staff_languages.iter().map(|l| base(l)).collect() })
+    }
+
+    /// Translates user message into `staff_lang` or the first staff language. Source language is `user_lang`,
+    /// translator detects it only when user language is unknown. Returns `None` for messages in staff languages
+    pub async fn for_staff(&self, text: &str, user_lang: Option<&str>, staff_lang: Option<&str>) -> Result<Option<Translated>> {
+        let target = staff_lang.map(base)
+            .or(self.staff_languages.first().cloned())
+            .unwrap_or("en".to_string());
+        let readable = |lang: &str| lang == target || self.staff_languages.iter().any(|l| l == lang);
+        let source = user_lang.map(base);
+        if source.as_deref().is_some_and(readable) {
+            return Ok(None);
+        }
+        let mut translated = self.translator.translate(text, source.as_deref(), &target).await?;
+        translated.source = source.or(translated.source.map(|l| base(&l)));
+        let readable = translated.source.as_deref().is_some_and(readable);
+        Ok((!readable).then_some(translated))
+    }
+
+    /// Translates staff text into user language
+    pub async fn for_user(&self, text: &str, user_lang: &str) -> Result<String> {
+        Ok(self.translator.translate(text, None, &base(user_lang)).await?.text)
+    }
+}
+
+/// Translators know languages, not regional variants: `pt` for `pt-BR`
+fn base(lang: &str) -> String {
+    lang.split(['-', '_']).next().unwrap_or_default().to_lowercase()
+}
+
+struct Stub;
+
+#[async_trait]
+impl Translator for Stub {
+    async fn translate(&self, text: &str, source: Option<&str>, target: &str) -> Result<Translated> {
+        Ok(Translated { text: format!("[{}] {}", target, text), source: source.map(|s| s.to_string()) })
+    }
+}
\ No newline at end of file
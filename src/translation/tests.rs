@@ -0,0 +1,51 @@
+use std::sync::{Arc, Mutex};
+use async_trait::async_trait;
+use super::{Result, Translated, Translation, Translator};
+
+/// Records source languages it was called with and detects every text as `detected`
+struct Recording {
+    calls: Arc<Mutex<Vec<Option<String>>>>,
+    detected: &'static str,
+}
+
+#[async_trait]
+impl Translator for Recording {
+    async fn translate(&self, text: &str, source: Option<&str>, target: &str) -> Result<Translated> {
+        self.calls.lock().unwrap().push(source.map(|s| s.to_string()));
+        Ok(Translated { text: format!("[{}] {}", target, text), source: Some(self.detected.to_string()) })
+    }
+}
+
+fn translation(detected: &'static str) -> (Translation, Arc<Mutex<Vec<Option<String>>>>) {
+    let calls = Arc::new(Mutex::new(Vec::new()));
+    let translator = Box::new(Recording { calls: calls.clone(), detected });
+    (Translation { translator, staff_languages: vec!["en".to_string(), "ru".to_string()] }, calls)
+}
+
+#[tokio::test]
+async fn known_readable_language_is_not_translated() {
+    let (tr, calls) = translation("tr");
+    assert!(tr.for_staff("Привет", Some("ru-RU"), None).await.unwrap().is_none());
+    assert!(tr.for_staff("Hola", Some("es"), Some("es")).await.unwrap().is_none(), "target language is readable");
+    assert!(calls.lock().unwrap().is_empty(), "translator should not be called");
+}
+
+#[tokio::test]
+async fn known_language_is_used_as_source() {
+    let (tr, calls) = translation("en");
+    let translated = tr.for_staff("Merhaba", Some("tr"), Some("ru")).await.unwrap().unwrap();
+    assert_eq!(translated.text, "[ru] Merhaba");
+    assert_eq!(translated.source.as_deref(), Some("tr"));
+    assert_eq!(*calls.lock().unwrap(), vec![Some("tr".to_string())]);
+}
+
+#[tokio::test]
+async fn unknown_language_is_detected() {
+    let (tr, calls) = translation("ru");
+    assert!(tr.for_staff("Привет", None, None).await.unwrap().is_none());
+
+    let (tr, _) = translation("pt-BR");
+    let translated = tr.for_staff("Olá", None, None).await.unwrap().unwrap();
+    assert_eq!(translated.source.as_deref(), Some("pt"));
+    assert_eq!(*calls.lock().unwrap(), vec![None]);
+}
\ No newline at end of file